@@ -0,0 +1,54 @@
+//! Headless speed of the emulator, `cargo bench --no-default-features`
+//!
+//! - frames per second running the nestest rom (its menu keeps the CPU and the PPU busy)
+//! - millions of instructions per second of the CPU alone, on a [`RamBus`]
+
+use std::{path::PathBuf, time::Instant};
+
+use nes_emulator::{
+    apu::audio::NullSink,
+    cpu::{cpu::CPU, cpu_bus::RamBus},
+    engine::instance::EmulatorInstance,
+};
+
+const FRAMES: u32 = 3000;
+const INSTRUCTIONS: u32 = 20_000_000;
+
+fn frames_per_second() -> f64 {
+    let mut emulator = EmulatorInstance::new(PathBuf::from("tests/nestest/nestest.nes"))
+        .expect("nestest.nes is needed for the benchmark");
+
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        emulator.run_frame(&mut NullSink);
+    }
+    FRAMES as f64 / start.elapsed().as_secs_f64()
+}
+
+fn cpu_mips() -> f64 {
+    // a loop with loads, stores, arithmetic, read-modify-writes and branches:
+    // LDX #$00, loop: LDA $0200,X, ADC #$03, STA $0200,X, ROL $10, INC $11, DEX, BNE loop, JMP $8000
+    let mut cpu = CPU::with_bus(RamBus::with_program(&[
+        0xa2, 0x00,
+        0xbd, 0x00, 0x02,
+        0x69, 0x03,
+        0x9d, 0x00, 0x02,
+        0x26, 0x10,
+        0xe6, 0x11,
+        0xca,
+        0xd0, 0xf1,
+        0x4c, 0x00, 0x80,
+    ]));
+    cpu.reset_interrupt();
+
+    let start = Instant::now();
+    for _ in 0..INSTRUCTIONS {
+        cpu.step();
+    }
+    INSTRUCTIONS as f64 / start.elapsed().as_secs_f64() / 1_000_000.0
+}
+
+fn main() {
+    println!("nestest: {:.1} frames per second ({} frames)", frames_per_second(), FRAMES);
+    println!("cpu:     {:.1} million instructions per second", cpu_mips());
+}
@@ -1,6 +1,15 @@
-use super::{square::SquareWave, triangle::TriangleWave, noise::Noise};
+use crate::engine::region::Region;
+use crate::engine::save_state::{SaveStateError, StateReader, StateWriter};
+use crate::memory::dma::Dma;
+use super::{square::SquareWave, triangle::TriangleWave, noise::Noise, dmc::Dmc};
+use super::{audio::AudioSink, blip::BlipBuffer, filter::OutputFilters};
+
+/// Most the resampling rate is moved to keep the output buffer half full, 0.5% is too little to hear
+const MAX_RATE_ADJUSTMENT: f64 = 0.005;
+
+/// Samples a single flush can hold, way more than an instruction (or an OAM DMA) lasts at any sample rate
+const MAX_SAMPLES_PER_FLUSH: usize = 8192;
 
-const CPU_FREQ: f64 = 1_789_773.0;
 /// NES Audio Processing Unit
 /// 
 /// for more info:
@@ -8,31 +17,64 @@ const CPU_FREQ: f64 = 1_789_773.0;
 #[derive(Debug)]
 pub struct APU {
     clock: u64,
+    /// CPU cycles since the frame counter was reset
     frame_counter: usize,
-    frame_sequence: u8,
-    cycles_since_sample: f64,
+    five_step_mode: bool,
+    irq_inhibit: bool,
+    frame_irq: bool,
+    /// A $4017 write resets the frame counter 3 or 4 cycles later, this counts them down
+    frame_reset_delay: u8,
+    pending_five_step_mode: bool,
+    /// CPU cycles since the samples were last flushed
+    audio_clock: u32,
+    /// Channel outputs after the last recorded step, the mixer only runs when they change
+    last_levels: [f32; 5],
+    last_output: f32,
+    /// Of the sink the samples were last flushed to, 0 if none (no steps are recorded then)
+    sample_rate: u32,
+    blip: BlipBuffer,
+    filters: OutputFilters,
     pub volume: f32,
+    region: Region,
     pub pulse1: SquareWave,
     pub pulse2: SquareWave,
     pub triangle: TriangleWave,
-    pub noise: Noise
+    pub noise: Noise,
+    pub dmc: Dmc,
 }
 impl Default for APU {
     fn default() -> Self {
         Self {
-            cycles_since_sample: 0.0,
+            audio_clock: 0,
+            last_levels: [0.0; 5],
+            last_output: 0.0,
+            sample_rate: 0,
+            blip: BlipBuffer::new(MAX_SAMPLES_PER_FLUSH),
+            filters: OutputFilters::new(44100),
             clock: 0,
             frame_counter: 0,
-            frame_sequence: 0,
+            five_step_mode: false,
+            irq_inhibit: false,
+            frame_irq: false,
+            frame_reset_delay: 0,
+            pending_five_step_mode: false,
             volume: 1.0,
+            region: Region::Ntsc,
             pulse1: SquareWave::new(true),
             pulse2: SquareWave::new(false),
             triangle: TriangleWave::default(),
             noise: Noise::new(),
+            dmc: Dmc::new(),
         }
     }
 }
 impl APU {
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+        self.noise.region = region;
+        self.dmc.region = region;
+    }
+
     pub fn write_register(&mut self, addr: u16, data: u8) {
         match addr {
             //pulse 1
@@ -56,96 +98,464 @@ impl APU {
             0x400E => self.noise.write_noise(data),
             0x400F => self.noise.write_length_counter(data),
 
+            0x4010 => self.dmc.write_control(data),
+            0x4011 => self.dmc.write_direct_load(data),
+            0x4012 => self.dmc.write_sample_address(data),
+            0x4013 => self.dmc.write_sample_length(data),
+
             0x4015 => {
-                self.pulse1.enabled     = data & 0x01 != 0;
-                self.pulse2.enabled     = data & 0x02 != 0;
-                self.triangle.enabled   = data & 0x04 != 0;
+                self.pulse1.set_enabled(data & 0x01 != 0);
+                self.pulse2.set_enabled(data & 0x02 != 0);
+                self.triangle.set_enabled(data & 0x04 != 0);
+                self.noise.set_enabled(data & 0x08 != 0);
+                self.dmc.set_enabled(data & 0x10 != 0);
             }
 
-            0x4017 => {} //unmute immediately
+            0x4017 => self.write_frame_counter(data),
             _ => {}
         }
     }
 
-    pub fn step(&mut self) {
+    /// Runs every channel for one CPU cycle, `dma` fetches the DMC samples
+    pub fn step(&mut self, dma: &mut Dma) {
         self.clock += 1;
-        self.frame_counter += 1;
+        self.clock_frame_counter();
 
-        if self.frame_counter >= 7457 { // cpu cycles per sec divided by 240Hz
-            self.frame_counter -= 7457;
-            self.frame_sequence = (self.frame_sequence + 1) % 4;
+        if self.clock % 2 == 0 {
+            self.pulse1.step();
+            self.pulse2.step();
+            self.noise.step();
+        }
 
-            self.pulse1.clock_envelope();
-            self.pulse2.clock_envelope();
-            self.triangle.clock_linear_counter();
-            self.noise.clock_envelope();
+        self.triangle.step();
+        self.dmc.step(dma);
 
-            if self.frame_sequence % 2 == 0 {
-                self.pulse1.clock_length();
-                self.pulse1.clock_sweep();
+        if self.sample_rate != 0 {
+            let levels = self.channel_levels();
+            if levels != self.last_levels {
+                self.last_levels = levels;
+                let output = Self::mix(levels);
+                self.blip.add_delta(self.audio_clock, output - self.last_output);
+                self.last_output = output;
+            }
+            self.audio_clock += 1;
+        }
+    }
+
+    /// The IRQ line of the APU, held until it's acknowledged
+    pub fn irq_pending(&self) -> bool {
+        self.frame_irq || self.dmc.irq_flag
+    }
 
-                self.pulse2.clock_length();
-                self.pulse2.clock_sweep();
+    /// Same as rewriting $4017 with its last value and silencing every channel (the reset button)
+    pub fn reset(&mut self) {
+        self.write_register(0x4015, 0);
+        self.frame_irq = false;
+        let last_write = ((self.five_step_mode as u8) << 7) | ((self.irq_inhibit as u8) << 6);
+        self.write_frame_counter(last_write);
+    }
 
-                self.triangle.clock_length();
+    /// $4017 MI-- ----
+    ///
+    /// The inhibit flag (I) applies right away, the mode (M) when the frame counter gets reset:
+    /// 3 cycles after a write on an APU cycle (even), 4 after one between two.
+    /// Resetting it in the 5-step mode also clocks the quarter and half frame units
+    fn write_frame_counter(&mut self, data: u8) {
+        self.irq_inhibit = data & 0x40 != 0;
+        if self.irq_inhibit {
+            self.frame_irq = false;
+        }
+        self.pending_five_step_mode = data & 0x80 != 0;
+        self.frame_reset_delay = if self.clock.is_multiple_of(2) { 3 } else { 4 };
+    }
 
-                self.noise.clock_length();
+    /// The frame counter (or frame sequencer), clocks the envelopes, the linear counter,
+    /// the length counters and the sweeps 4 times per frame and raises the frame IRQ
+    ///
+    /// ```text
+    /// 4-step mode           5-step mode
+    /// 7457   Q              7457   Q
+    /// 14913  Q H            14913  Q H
+    /// 22371  Q              22371  Q
+    /// 29828      IRQ        29829  (nothing)
+    /// 29829  Q H IRQ        37281  Q H
+    /// 29830      IRQ, 0     37282  0
+    /// ```
+    /// (NTSC cycles, see [`Region::frame_counter_steps`])
+    ///
+    /// for more info:
+    /// https://www.nesdev.org/wiki/APU_Frame_Counter
+    fn clock_frame_counter(&mut self) {
+        if self.frame_reset_delay > 0 {
+            self.frame_reset_delay -= 1;
+            if self.frame_reset_delay == 0 {
+                self.five_step_mode = self.pending_five_step_mode;
+                self.frame_counter = 0;
+                if self.five_step_mode {
+                    self.clock_quarter_frame();
+                    self.clock_half_frame();
+                }
+                return;
             }
         }
 
-        if self.clock % 2 == 0 {
-            self.pulse1.step();
-            self.pulse2.step();
-            self.noise.step();
+        self.frame_counter += 1;
+        let [quarter_1, half_1, quarter_3, irq, last_5_step] = *self.region.frame_counter_steps();
+        let cycle = self.frame_counter;
+
+        if cycle == quarter_1 || cycle == quarter_3 {
+            self.clock_quarter_frame();
+        } else if cycle == half_1 {
+            self.clock_quarter_frame();
+            self.clock_half_frame();
+        } else if !self.five_step_mode {
+            if cycle == irq {
+                self.set_frame_irq();
+            } else if cycle == irq + 1 {
+                self.clock_quarter_frame();
+                self.clock_half_frame();
+                self.set_frame_irq();
+            } else if cycle == irq + 2 {
+                self.set_frame_irq();
+                self.frame_counter = 0;
+            }
+        } else if cycle == last_5_step {
+            self.clock_quarter_frame();
+            self.clock_half_frame();
+        } else if cycle == last_5_step + 1 {
+            self.frame_counter = 0;
         }
+    }
 
-        self.triangle.step();
+    fn set_frame_irq(&mut self) {
+        if !self.irq_inhibit {
+            self.frame_irq = true;
+        }
     }
-    
-    pub fn tick(&mut self, cycles: u8, sample_rate: u32, fullness: f64) -> Option<f32> {
-        self.cycles_since_sample += cycles as f64;
 
-        let rate_adjustment = if fullness < 0.4 {
-            0.98
-        } else if fullness > 0.6 {
-            1.02
-        } else {
-            1.0
-        };
+    /// Envelopes and the triangle linear counter (240 Hz in the 4-step mode)
+    fn clock_quarter_frame(&mut self) {
+        self.pulse1.clock_envelope();
+        self.pulse2.clock_envelope();
+        self.triangle.clock_linear_counter();
+        self.noise.clock_envelope();
+    }
+
+    /// Length counters and sweeps (120 Hz in the 4-step mode)
+    fn clock_half_frame(&mut self) {
+        self.pulse1.clock_length();
+        self.pulse1.clock_sweep();
 
-        let cycles_per_sample = (CPU_FREQ / sample_rate as f64) * rate_adjustment;
+        self.pulse2.clock_length();
+        self.pulse2.clock_sweep();
 
-        if self.cycles_since_sample >= cycles_per_sample {
-            self.cycles_since_sample -= cycles_per_sample;
-            return Some(self.get_sample());
+        self.triangle.clock_length();
+
+        self.noise.clock_length();
+    }
+    
+    /// Gives `audio` the samples finished since the last call, through the output filters,
+    /// the bus calls it after every instruction
+    ///
+    /// The output changes are recorded by [`APU::step`] at the cycle they happen and resampled
+    /// by the [`BlipBuffer`], instead of picking one mixer output every few cycles.
+    pub fn flush_samples(&mut self, audio: &mut impl AudioSink) {
+        let Some(sample_rate) = audio.sample_rate() else {
+            self.sample_rate = 0;
+            self.audio_clock = 0;
+            return;
+        };
+        if sample_rate != self.sample_rate {
+            self.sample_rate = sample_rate;
+            self.filters = OutputFilters::new(sample_rate);
+            self.discard_samples();
         }
-        None
+
+        let volume = self.volume;
+        let filters = &mut self.filters;
+        self.blip.end_frame(self.audio_clock, |sample| audio.push_sample(filters.process(sample) * volume));
+        self.audio_clock = 0;
+
+        // dynamic rate control: a bit more samples per cycle while the buffer is emptier than half, a bit less
+        // while it's fuller, a continuous nudge instead of jumps between rates that would wobble the pitch
+        let fullness = audio.fullness().clamp(0.0, 1.0);
+        let rate_adjustment = 1.0 + MAX_RATE_ADJUSTMENT * (2.0 * fullness - 1.0);
+
+        self.blip.set_rates(self.region.cpu_frequency() * rate_adjustment, sample_rate as f64);
     }
 
+    /// Drops the steps recorded since the last flush (the frames emulated while rewinding are muted)
+    pub fn discard_samples(&mut self) {
+        self.blip.clear();
+        self.audio_clock = 0;
+    }
 
+    /// Output of the mixer (0.0 to 1.0), before the volume and the filters
+    ///
+    /// for more info:
+    /// https://www.nesdev.org/wiki/APU_Mixer
     pub fn get_sample(&mut self) -> f32 {
-        let p1 = self.pulse1.get_amplitude();
-        let p2 = self.pulse2.get_amplitude();
-        let tg = self.triangle.get_amplitude();
-        let ns = self.noise.get_amplitude();
+        Self::mix(self.channel_levels())
+    }
 
+    fn channel_levels(&mut self) -> [f32; 5] {
+        [
+            self.pulse1.get_amplitude(),
+            self.pulse2.get_amplitude(),
+            self.triangle.get_amplitude(),
+            self.noise.get_amplitude(),
+            self.dmc.get_amplitude(),
+        ]
+    }
+
+    fn mix([p1, p2, tg, ns, dm]: [f32; 5]) -> f32 {
         let pulse_out = if p1 + p2 > 0.0 {
             95.88 / ((8128.0 / (p1 + p2)) + 100.0)
         } else {
             0.0
         };
 
-        let tnd_out = if tg + ns > 0.0 {
-            159.79 / ((1.0 / ((tg / 8227.0) + (ns / 12241.0))) + 100.0)
+        let tnd_out = if tg + ns + dm > 0.0 {
+            159.79 / ((1.0 / ((tg / 8227.0) + (ns / 12241.0) + (dm / 22638.0))) + 100.0)
         } else {
             0.0
         };
 
-        let mixed = (pulse_out + tnd_out) * self.volume;
-        mixed
+        pulse_out + tnd_out
+    }
+
+    /// $4015 without side effects
+    ///
+    /// ```text
+    /// IF-D NT21
+    /// I: DMC IRQ, F: frame IRQ, D: DMC sample not finished
+    /// N, T, 2, 1: length counters of the noise, triangle and pulse channels not 0
+    /// ```
+    /// (bit 5 is open bus)
+    pub fn peek_status(&self) -> u8 {
+        (self.pulse1.is_active() as u8)
+            | (self.pulse2.is_active() as u8) << 1
+            | (self.triangle.is_active() as u8) << 2
+            | (self.noise.is_active() as u8) << 3
+            | (self.dmc.is_active() as u8) << 4
+            | (self.frame_irq as u8) << 6
+            | (self.dmc.irq_flag as u8) << 7
+    }
+
+    /// $4015, reading it acknowledges the frame IRQ (not the DMC one)
+    pub fn read_status(&mut self) -> u8 {
+        let status = self.peek_status();
+        self.frame_irq = false;
+        status
+    }
+
+    /// The volume is a user setting, so it stays the same after loading a state
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_u64(self.clock);
+        w.write_u32(self.frame_counter as u32);
+        w.write_bool(self.five_step_mode);
+        w.write_bool(self.irq_inhibit);
+        w.write_bool(self.frame_irq);
+        w.write_u8(self.frame_reset_delay);
+        w.write_bool(self.pending_five_step_mode);
+
+        self.pulse1.save_state(w);
+        self.pulse2.save_state(w);
+        self.triangle.save_state(w);
+        self.noise.save_state(w);
+        self.dmc.save_state(w);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.clock = r.read_u64()?;
+        self.frame_counter = r.read_u32()? as usize % (self.region.frame_counter_steps()[4] + 2);
+        self.five_step_mode = r.read_bool()?;
+        self.irq_inhibit = r.read_bool()?;
+        self.frame_irq = r.read_bool()?;
+        self.frame_reset_delay = r.read_u8()?;
+        self.pending_five_step_mode = r.read_bool()?;
+        self.discard_samples();
+
+        self.pulse1.load_state(r)?;
+        self.pulse2.load_state(r)?;
+        self.triangle.load_state(r)?;
+        self.noise.load_state(r)?;
+        self.dmc.load_state(r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Steps until the frame IRQ is raised, `None` if it isn't within `max_cycles`
+    fn cycles_until_irq(apu: &mut APU, max_cycles: usize) -> Option<usize> {
+        let mut dma = Dma::default();
+        (1..=max_cycles).find(|_| {
+            apu.step(&mut dma);
+            apu.irq_pending()
+        })
+    }
+
+    #[test]
+    fn four_step_mode_raises_the_frame_irq() {
+        let mut apu = APU::default();
+        assert_eq!(cycles_until_irq(&mut apu, 40000), Some(29828));
+
+        // reading $4015 acknowledges it, but it's raised again on the next 2 cycles
+        assert_eq!(apu.read_status() & 0x40, 0x40);
+        assert_eq!(apu.peek_status() & 0x40, 0);
+        assert_eq!(cycles_until_irq(&mut apu, 1), Some(1));
+        apu.read_status();
+        assert_eq!(cycles_until_irq(&mut apu, 1), Some(1));
+        apu.read_status();
+
+        // then the sequence starts over
+        assert_eq!(cycles_until_irq(&mut apu, 40000), Some(29830 - 2));
+    }
+
+    #[test]
+    fn inhibit_and_five_step_mode_have_no_irq() {
+        let mut apu = APU::default();
+        apu.write_register(0x4017, 0x40);
+        assert_eq!(cycles_until_irq(&mut apu, 100000), None);
+
+        let mut apu = APU::default();
+        apu.write_register(0x4017, 0x80);
+        assert_eq!(cycles_until_irq(&mut apu, 100000), None);
+
+        // setting the inhibit flag acknowledges the irq right away
+        let mut apu = APU::default();
+        assert!(cycles_until_irq(&mut apu, 40000).is_some());
+        apu.write_register(0x4017, 0x40);
+        assert!(!apu.irq_pending());
+    }
+
+    #[test]
+    fn write_resets_the_sequence_after_a_delay() {
+        let mut apu = APU::default();
+        let mut dma = Dma::default();
+        for _ in 0..10000 {
+            apu.step(&mut dma);
+        }
+        // cycle 10000 is even, an apu cycle
+        apu.write_register(0x4017, 0x00);
+        assert_eq!(cycles_until_irq(&mut apu, 40000), Some(3 + 29828));
+
+        let mut apu = APU::default();
+        for _ in 0..10001 {
+            apu.step(&mut dma);
+        }
+        apu.write_register(0x4017, 0x00);
+        assert_eq!(cycles_until_irq(&mut apu, 40000), Some(4 + 29828));
+    }
+
+    #[test]
+    fn status_reports_the_length_counters() {
+        let mut apu = APU::default();
+        apu.write_register(0x4015, 0x0F);
+        apu.write_register(0x4003, 0x08);
+        apu.write_register(0x4007, 0x08);
+        apu.write_register(0x400B, 0x08);
+        apu.write_register(0x400F, 0x08);
+        assert_eq!(apu.peek_status(), 0x0F);
+
+        // disabling a channel clears its length counter, and new lengths are ignored
+        apu.write_register(0x4015, 0x05);
+        assert_eq!(apu.peek_status(), 0x05);
+        apu.write_register(0x4007, 0x08);
+        apu.write_register(0x400F, 0x08);
+        assert_eq!(apu.peek_status(), 0x05);
+
+        apu.write_register(0x4015, 0x00);
+        assert_eq!(apu.peek_status(), 0x00);
+    }
+
+    #[test]
+    fn five_step_write_clocks_the_length_counters() {
+        let mut apu = APU::default();
+        apu.write_register(0x4015, 0x01);
+        apu.write_register(0x4003, 0x18); // length 2
+        apu.write_register(0x4017, 0x80);
+
+        let mut dma = Dma::default();
+        for _ in 0..4 {
+            apu.step(&mut dma);
+        }
+        assert_eq!(apu.peek_status() & 0x01, 0x01);
+        apu.write_register(0x4017, 0x80);
+        for _ in 0..4 {
+            apu.step(&mut dma);
+        }
+        assert_eq!(apu.peek_status() & 0x01, 0x00);
+    }
+
+    #[test]
+    fn status_reports_the_dmc_and_the_irqs() {
+        let mut apu = APU::default();
+        let mut dma = Dma::default();
+        apu.write_register(0x4010, 0x8F); // irq, fastest rate
+        apu.write_register(0x4013, 0x00); // 1 byte
+        apu.write_register(0x4015, 0x10);
+        assert_eq!(apu.peek_status(), 0x10);
+
+        // the byte is fetched on the next cycle, which ends the sample
+        apu.step(&mut dma);
+        let addr = dma.take_dmc_address().unwrap();
+        dma.finish_dmc(addr as u8);
+        apu.step(&mut dma);
+        assert_eq!(apu.peek_status(), 0x80);
+
+        for _ in 0..29828 {
+            apu.step(&mut dma);
+        }
+        // only the frame irq is acknowledged by the read, the dmc one by writing $4015
+        assert_eq!(apu.read_status(), 0xC0);
+        assert_eq!(apu.read_status(), 0x80);
+        apu.write_register(0x4015, 0x00);
+        assert_eq!(apu.read_status(), 0x00);
+        assert!(!apu.irq_pending());
+    }
+
+    struct CountingSink {
+        fullness: f64,
+        samples: usize,
+    }
+
+    impl AudioSink for CountingSink {
+        fn sample_rate(&self) -> Option<u32> {
+            Some(44100)
+        }
+
+        fn fullness(&self) -> f64 {
+            self.fullness
+        }
+
+        fn push_sample(&mut self, _sample: f32) {
+            self.samples += 1;
+        }
+    }
+
+    /// Samples made in a second, flushing every 4 cycles like after short instructions
+    fn samples_per_second(fullness: f64) -> usize {
+        let mut apu = APU::default();
+        let mut dma = Dma::default();
+        let mut sink = CountingSink { fullness, samples: 0 };
+        for _ in 0..1_789_773 / 4 {
+            for _ in 0..4 {
+                apu.step(&mut dma);
+            }
+            apu.flush_samples(&mut sink);
+        }
+        sink.samples
     }
 
-    pub fn read_status(&self) -> u8 {
-        0
+    #[test]
+    fn rate_follows_the_buffer_level() {
+        assert!((44_090..=44_110).contains(&samples_per_second(0.5)));
+        // 0.5% faster or slower at most
+        assert!((44_300..=44_330).contains(&samples_per_second(0.0)));
+        assert!((43_870..=43_900).contains(&samples_per_second(1.0)));
+        assert!(samples_per_second(0.3) > samples_per_second(0.4));
     }
 }
@@ -1,42 +1,137 @@
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use ringbuf::{HeapRb, traits::{Consumer, Split}};
+#[cfg(feature = "audio")]
+use cpal::{FromSample, SampleFormat, SizedSample, traits::{DeviceTrait, HostTrait, StreamTrait}};
+#[cfg(feature = "audio")]
+use ringbuf::{HeapRb, traits::{Consumer, Observer, Producer, Split}};
+#[cfg(feature = "audio")]
+use std::{thread, time::{Duration, Instant}};
+#[cfg(feature = "audio")]
+use crate::engine::console::{LogType, print_logs};
 
+/// Where the samples mixed by the APU go
+///
+/// The emulator core only knows about this trait, so it can run without any audio device
+/// (tests, headless tools) by passing a [`NullSink`].
+pub trait AudioSink {
+    /// Samples per second it expects, `None` if nothing is listening (the APU doesn't mix anything then)
+    fn sample_rate(&self) -> Option<u32>;
+
+    /// How full the output buffer is (0.0 to 1.0), the APU resamples a bit faster or slower to keep it around the middle
+    fn fullness(&self) -> f64 {
+        0.5
+    }
+
+    fn push_sample(&mut self, sample: f32);
+}
+
+/// Throws every sample away
+pub struct NullSink;
+
+impl AudioSink for NullSink {
+    fn sample_rate(&self) -> Option<u32> {
+        None
+    }
+
+    fn push_sample(&mut self, _sample: f32) {}
+}
+
+impl<S: AudioSink + ?Sized> AudioSink for &mut S {
+    fn sample_rate(&self) -> Option<u32> {
+        (**self).sample_rate()
+    }
+
+    fn fullness(&self) -> f64 {
+        (**self).fullness()
+    }
+
+    fn push_sample(&mut self, sample: f32) {
+        (**self).push_sample(sample)
+    }
+}
+
+/// `None` works as a [`NullSink`], for when the audio device couldn't be opened
+impl<S: AudioSink> AudioSink for Option<S> {
+    fn sample_rate(&self) -> Option<u32> {
+        self.as_ref().and_then(|sink| sink.sample_rate())
+    }
+
+    fn fullness(&self) -> f64 {
+        self.as_ref().map_or(0.5, |sink| sink.fullness())
+    }
+
+    fn push_sample(&mut self, sample: f32) {
+        if let Some(sink) = self {
+            sink.push_sample(sample);
+        }
+    }
+}
+
+/// Plays the samples on an output device of the system
+///
+/// The stream uses the format the device prefers (its sample rate, channel count and sample type),
+/// the APU resamples to that rate and every channel gets the same (mono) sample.
+#[cfg(feature = "audio")]
 pub struct AudioOutput {
     pub producer: ringbuf::HeapProd<f32>,
+    sample_rate: u32,
+    /// Samples the buffer should hold, the latency picked in the settings
+    target_len: usize,
+    /// Reports the real buffer level to the APU so it adjusts its rate, otherwise it always looks half full
+    pub dynamic_rate: bool,
     _stream: cpal::Stream
 }
 
+#[cfg(feature = "audio")]
 impl AudioOutput {
-    pub fn new(sample_rate: u32) -> Option<(Self, u32)> {
+    /// Opens `device_name` (the default device if `None` or if it isn't connected),
+    /// `latency_ms` is how much audio is kept buffered ahead of the device
+    ///
+    /// The stream wakes up the thread that opens it every time it takes samples, see [`AudioOutput::wait_for_room`]
+    pub fn new(device_name: Option<&str>, latency_ms: u32) -> Option<Self> {
         let host = cpal::default_host();
-        let device = if let Some(d) = host.default_output_device() {
+        let named_device = device_name.and_then(|name| {
+            let found = host.output_devices().ok()?.find(|d| d.name().is_ok_and(|n| n == name));
+            if found.is_none() {
+                print_logs(LogType::Warning, format!("Audio device '{}' not found, using the default one", name));
+            }
+            found
+        });
+        let device = if let Some(d) = named_device.or_else(|| host.default_output_device()) {
             d
         } else {
             eprintln!("[WARNING]: No audio output device found. The emulator will play without audio.");
             return None;
         };
 
-        let config = if let Ok(c) = device.default_output_config() {
+        let supported = if let Ok(c) = device.default_output_config() {
             c
         } else {
             eprintln!("[WARNING]: Failed to get default audio output config. The emulator will play without audio.");
             return None
         };
+        let sample_rate = supported.sample_rate().0;
+        let channels = supported.channels() as usize;
 
-        let rb = HeapRb::<f32>::new(4016);
-        let (producer, mut consumer) = rb.split();
-
-        let stream = if let Ok(s) = device.build_output_stream(
-            &config.into(), 
-            move |data: &mut [f32], _| {
-                for frame in data.chunks_mut(2) {
-                    let sample = consumer.try_pop().unwrap_or(0.0);
-                    frame[0] = sample;
-                    frame[1] = sample;
-                }
-            }, |err| {
-                eprintln!("[WARNING]: Failed to create an audio stream. The emulator will play without audio. {:?}", err)
-            }, None) {   
+        let latency_frames = (sample_rate as u64 * latency_ms as u64 / 1000).max(1) as u32;
+        // twice the latency since it's kept half full, plus 100 ms so a frame never overflows it
+        let rb = HeapRb::<f32>::new(latency_frames as usize * 2 + sample_rate as usize / 10);
+        let (producer, consumer) = rb.split();
+
+        let mut config: cpal::StreamConfig = supported.config();
+        // the device pulls in chunks of at most half the latency, unless it can't go that low
+        if let cpal::SupportedBufferSize::Range { min, max } = supported.buffer_size() {
+            config.buffer_size = cpal::BufferSize::Fixed((latency_frames / 2).clamp(*min, *max));
+        }
+
+        let stream = match supported.sample_format() {
+            SampleFormat::F32 => build_stream::<f32>(&device, &config, channels, consumer),
+            SampleFormat::I16 => build_stream::<i16>(&device, &config, channels, consumer),
+            SampleFormat::U16 => build_stream::<u16>(&device, &config, channels, consumer),
+            other => {
+                eprintln!("[WARNING]: Unsupported audio sample format ({}). The emulator will play without audio.", other);
+                return None
+            }
+        };
+        let stream = if let Ok(s) = stream {
             s
         } else {
             eprintln!("[WARNING]: Build output stream failed. No audio devide");
@@ -47,9 +142,84 @@ impl AudioOutput {
             return None
         }
 
-        Some((AudioOutput {
+        print_logs(LogType::Info, format!(
+            "Audio output: {} ({} Hz, {} channels, {}, {} ms)",
+            device.name().unwrap_or_default(), sample_rate, channels, supported.sample_format(), latency_ms,
+        ));
+
+        Some(AudioOutput {
             producer,
+            sample_rate,
+            target_len: latency_frames as usize,
+            dynamic_rate: true,
             _stream: stream
-        }, sample_rate))
+        })
+    }
+
+    /// Buffer level, 0.5 when it holds the target latency
+    fn fill_level(&self) -> f64 {
+        (self.producer.occupied_len() as f64 / (self.target_len * 2) as f64).min(1.0)
+    }
+
+    /// Blocks until the device has played the buffer down to the target latency, or until `timeout`,
+    /// it has to be called from the thread that opened the stream
+    pub fn wait_for_room(&self, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        while self.producer.occupied_len() > self.target_len {
+            let now = Instant::now();
+            if now >= deadline {
+                return;
+            }
+            thread::park_timeout(deadline - now);
+        }
+    }
+
+    /// Names of the output devices of the system, for the settings
+    pub fn device_names() -> Vec<String> {
+        let host = cpal::default_host();
+        host.output_devices()
+            .map(|devices| devices.filter_map(|d| d.name().ok()).collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Stream of `T` samples, the buffer has `channels` samples per frame
+#[cfg(feature = "audio")]
+fn build_stream<T: SizedSample + FromSample<f32>>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    channels: usize,
+    mut consumer: ringbuf::HeapCons<f32>,
+) -> Result<cpal::Stream, cpal::BuildStreamError> {
+    let waiting = thread::current();
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _| {
+            for frame in data.chunks_mut(channels) {
+                let sample = T::from_sample(consumer.try_pop().unwrap_or(0.0));
+                frame.fill(sample);
+            }
+            waiting.unpark();
+        }, |err| {
+            eprintln!("[WARNING]: Failed to create an audio stream. The emulator will play without audio. {:?}", err)
+        }, None)
+}
+
+#[cfg(feature = "audio")]
+impl AudioSink for AudioOutput {
+    fn sample_rate(&self) -> Option<u32> {
+        Some(self.sample_rate)
+    }
+
+    fn fullness(&self) -> f64 {
+        if self.dynamic_rate {
+            self.fill_level()
+        } else {
+            0.5
+        }
+    }
+
+    fn push_sample(&mut self, sample: f32) {
+        let _ = self.producer.try_push(sample);
     }
 }
\ No newline at end of file
@@ -0,0 +1,161 @@
+/// Impulses are placed with 1/32 of a sample of precision
+const PHASES: usize = 32;
+/// Length of an impulse, in output samples, the output is delayed by half of it
+const TAPS: usize = 16;
+/// Fraction of the output Nyquist frequency kept by the impulses
+const CUTOFF: f64 = 0.9;
+
+/// Band-limited step synthesis (in the style of blargg's blip_buf)
+///
+/// Instead of sampling the mixer output at the sample rate, which folds everything above half of it
+/// back as noise (the aliasing that makes high pulses and the noise channel hiss), the APU records
+/// every change of its output as a step at the CPU cycle it happened. Each step is added as a
+/// band-limited impulse (a windowed sinc) to a buffer of differences, and integrating that buffer
+/// gives the output samples.
+///
+/// Times are in clocks (CPU cycles) since the last [`BlipBuffer::end_frame`].
+#[derive(Debug)]
+pub struct BlipBuffer {
+    /// One impulse per phase, each one adds up to 1 so a step keeps its exact height
+    kernel: Box<[[f32; TAPS]; PHASES]>,
+    /// Differences between consecutive output samples, the first one is the next sample to be read
+    deltas: Vec<f32>,
+    samples_per_clock: f64,
+    /// Where the frame starts, in samples after the next one to be read (less than 1)
+    offset: f64,
+    /// Sum of the differences read so far, the last output sample
+    integrator: f32,
+}
+
+impl BlipBuffer {
+    /// `capacity` is the most samples a frame can produce, the clocks past it are dropped
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            kernel: Box::new(make_kernel()),
+            deltas: vec![0.0; capacity + TAPS],
+            samples_per_clock: 0.0,
+            offset: 0.0,
+            integrator: 0.0,
+        }
+    }
+
+    pub fn set_rates(&mut self, clock_rate: f64, sample_rate: f64) {
+        self.samples_per_clock = sample_rate / clock_rate;
+    }
+
+    /// Adds a step of `delta` to the output, `clock` clocks after the start of the frame
+    pub fn add_delta(&mut self, clock: u32, delta: f32) {
+        let position = self.offset + clock as f64 * self.samples_per_clock;
+        let index = position as usize;
+        let Some(deltas) = self.deltas.get_mut(index..index + TAPS) else { return };
+
+        let phase = ((position - index as f64) * PHASES as f64) as usize;
+        for (out, tap) in deltas.iter_mut().zip(&self.kernel[phase]) {
+            *out += delta * tap;
+        }
+    }
+
+    /// Ends the frame after `clocks` clocks and passes every sample it completed to `output`,
+    /// the next frame starts right after it
+    pub fn end_frame(&mut self, clocks: u32, mut output: impl FnMut(f32)) {
+        let end = self.offset + clocks as f64 * self.samples_per_clock;
+        // the impulses can still change the samples after the end, they stay for the next frame
+        let ready = (end as usize).min(self.deltas.len() - TAPS);
+
+        for &delta in &self.deltas[..ready] {
+            self.integrator += delta;
+            output(self.integrator);
+        }
+        // nothing was added past the last impulse
+        self.deltas.copy_within(ready..ready + TAPS, 0);
+        self.deltas[TAPS..ready + TAPS].fill(0.0);
+
+        // only less than a sample is left, unless the frame didn't fit
+        self.offset = end.fract();
+    }
+
+    /// Drops the pending steps, the output stays at its current level
+    pub fn clear(&mut self) {
+        self.deltas.fill(0.0);
+        self.offset = 0.0;
+    }
+}
+
+/// Blackman-windowed sinc impulses, shifted by each phase
+fn make_kernel() -> [[f32; TAPS]; PHASES] {
+    let mut kernel = [[0.0; TAPS]; PHASES];
+    for (phase, taps) in kernel.iter_mut().enumerate() {
+        let shift = phase as f64 / PHASES as f64;
+        let mut sum = 0.0;
+        for (k, tap) in taps.iter_mut().enumerate() {
+            // distance to the center of the impulse, in samples
+            let x = k as f64 - shift - (TAPS / 2) as f64 + 1.0;
+            let sinc = if x == 0.0 {
+                1.0
+            } else {
+                let t = std::f64::consts::PI * CUTOFF * x;
+                t.sin() / t
+            };
+            let w = std::f64::consts::TAU * (x + TAPS as f64 / 2.0) / TAPS as f64;
+            let window = 0.42 - 0.5 * w.cos() + 0.08 * (2.0 * w).cos();
+
+            *tap = sinc * window;
+            sum += *tap;
+        }
+        for tap in taps.iter_mut() {
+            *tap /= sum;
+        }
+    }
+    kernel.map(|taps| taps.map(|tap| tap as f32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(blip: &mut BlipBuffer, clocks: u32) -> Vec<f32> {
+        let mut samples = Vec::new();
+        blip.end_frame(clocks, |sample| samples.push(sample));
+        samples
+    }
+
+    #[test]
+    fn frames_produce_samples_at_the_output_rate() {
+        let mut blip = BlipBuffer::new(4096);
+        blip.set_rates(1_789_773.0, 44_100.0);
+
+        let total: usize = (0..60).map(|_| read(&mut blip, 29_830).len()).sum();
+        // 60 frames of 29830 cycles are 0.99998 seconds
+        assert!((44_098..=44_100).contains(&total), "{}", total);
+    }
+
+    #[test]
+    fn steps_settle_at_their_height() {
+        let mut blip = BlipBuffer::new(4096);
+        blip.set_rates(1_789_773.0, 44_100.0);
+
+        blip.add_delta(100, 0.5);
+        blip.add_delta(5000, -0.25);
+        let samples = read(&mut blip, 10_000);
+
+        // the impulses start at samples 2 and 123 (40.6 clocks per sample)
+        assert!(samples[..2].iter().all(|&s| s == 0.0));
+        assert!((samples[122] - 0.5).abs() < 0.01, "{}", samples[122]);
+        assert!((samples.last().unwrap() - 0.25).abs() < 0.01);
+
+        // a step right at the end of a frame still shows up in the next one
+        blip.add_delta(9_999, 1.0);
+        read(&mut blip, 10_000);
+        assert!((read(&mut blip, 10_000).last().unwrap() - 1.25).abs() < 0.01);
+    }
+
+    #[test]
+    fn long_frames_drop_what_doesnt_fit() {
+        let mut blip = BlipBuffer::new(100);
+        blip.set_rates(1_789_773.0, 44_100.0);
+        blip.add_delta(1_000_000, 1.0);
+        assert_eq!(read(&mut blip, 1_000_000).len(), 100);
+        // and the next one starts over
+        assert!((24..=25).contains(&read(&mut blip, 1_000).len()));
+    }
+}
@@ -0,0 +1,323 @@
+use crate::engine::region::Region;
+use crate::engine::save_state::{SaveStateError, StateReader, StateWriter};
+use crate::memory::dma::Dma;
+
+/// Delta Modulation Channel, plays 1-bit delta encoded samples read from the cartridge
+///
+/// Every bit of the sample moves the 7-bit output level up or down by 2.
+/// The bytes are fetched by the DMA unit ([`Dma::request_dmc`]), which steals a few cycles from the CPU for each one.
+///
+/// for more info:
+/// https://www.nesdev.org/wiki/APU_DMC
+#[derive(Debug, Default)]
+pub struct Dmc {
+    irq_enabled: bool,
+    loop_flag: bool,
+    rate_index: u8,
+    timer_value: u16,
+
+    // memory reader
+    sample_address: u16,
+    sample_length: u16,
+    current_address: u16,
+    bytes_remaining: u16,
+    sample_buffer: Option<u8>,
+    /// a byte was asked to the DMA and hasn't arrived yet
+    fetching: bool,
+
+    // output unit
+    shift_register: u8,
+    bits_remaining: u8,
+    silence: bool,
+    output_level: u8,
+
+    pub irq_flag: bool,
+
+    /// picks the rate table, set by [`crate::apu::apu::APU::set_region`]
+    pub region: Region,
+}
+impl Dmc {
+    pub fn new() -> Self {
+        Dmc {
+            sample_address: 0xC000,
+            sample_length: 1,
+            current_address: 0xC000,
+            bits_remaining: 8,
+            silence: true,
+            ..Default::default()
+        }
+    }
+
+    /// Runs for one CPU cycle, asking `dma` for the next sample byte when the buffer is empty
+    pub fn step(&mut self, dma: &mut Dma) {
+        if self.fetching {
+            if let Some(sample) = dma.take_dmc_sample() {
+                self.fetching = false;
+                self.load_sample(sample);
+            }
+        }
+        if self.sample_buffer.is_none() && self.bytes_remaining > 0 && !self.fetching {
+            self.fetching = true;
+            dma.request_dmc(self.current_address);
+        }
+
+        // the periods are in cpu cycles
+        if self.timer_value == 0 {
+            self.timer_value = self.region.dmc_periods()[self.rate_index as usize] - 1;
+            self.clock_output();
+        } else {
+            self.timer_value -= 1;
+        }
+    }
+
+    fn clock_output(&mut self) {
+        if !self.silence {
+            if self.shift_register & 0x01 != 0 {
+                if self.output_level <= 125 {
+                    self.output_level += 2;
+                }
+            } else if self.output_level >= 2 {
+                self.output_level -= 2;
+            }
+        }
+        self.shift_register >>= 1;
+
+        self.bits_remaining -= 1;
+        if self.bits_remaining == 0 {
+            self.bits_remaining = 8;
+            match self.sample_buffer.take() {
+                Some(sample) => {
+                    self.silence = false;
+                    self.shift_register = sample;
+                }
+                None => self.silence = true,
+            }
+        }
+    }
+
+    fn load_sample(&mut self, sample: u8) {
+        self.sample_buffer = Some(sample);
+        // the channel was disabled while the byte was on its way, there's nothing left to count
+        if self.bytes_remaining == 0 {
+            return;
+        }
+        // wraps to $8000, not to $0000
+        self.current_address = self.current_address.checked_add(1).unwrap_or(0x8000);
+        self.bytes_remaining -= 1;
+
+        if self.bytes_remaining == 0 {
+            if self.loop_flag {
+                self.restart();
+            } else if self.irq_enabled {
+                self.irq_flag = true;
+            }
+        }
+    }
+
+    fn restart(&mut self) {
+        self.current_address = self.sample_address;
+        self.bytes_remaining = self.sample_length;
+    }
+
+    pub fn get_amplitude(&self) -> f32 {
+        self.output_level as f32
+    }
+
+    /// True while there are bytes left to play ($4015 bit 4)
+    pub fn is_active(&self) -> bool {
+        self.bytes_remaining > 0
+    }
+
+    /// $4015 bit 4, clearing it stops the sample, setting it restarts it only if it had ended.
+    /// Either way the IRQ is acknowledged
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.irq_flag = false;
+        if !enabled {
+            self.bytes_remaining = 0;
+        } else if self.bytes_remaining == 0 {
+            self.restart();
+        }
+    }
+
+    ///$4010 IL-- RRRR
+    pub fn write_control(&mut self, data: u8) {
+        self.irq_enabled = data & 0x80 != 0;
+        self.loop_flag = data & 0x40 != 0;
+        self.rate_index = data & 0x0F;
+        if !self.irq_enabled {
+            self.irq_flag = false;
+        }
+    }
+
+    ///$4011 -DDD DDDD
+    pub fn write_direct_load(&mut self, data: u8) {
+        self.output_level = data & 0x7F;
+    }
+
+    ///$4012 sample address = $C000 + A * 64
+    pub fn write_sample_address(&mut self, data: u8) {
+        self.sample_address = 0xC000 | ((data as u16) << 6);
+    }
+
+    ///$4013 sample length = L * 16 + 1 bytes
+    pub fn write_sample_length(&mut self, data: u8) {
+        self.sample_length = ((data as u16) << 4) + 1;
+    }
+
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_bool(self.irq_enabled);
+        w.write_bool(self.loop_flag);
+        w.write_u8(self.rate_index);
+        w.write_u16(self.timer_value);
+        w.write_u16(self.sample_address);
+        w.write_u16(self.sample_length);
+        w.write_u16(self.current_address);
+        w.write_u16(self.bytes_remaining);
+        w.write_bool(self.sample_buffer.is_some());
+        w.write_u8(self.sample_buffer.unwrap_or(0));
+        w.write_bool(self.fetching);
+        w.write_u8(self.shift_register);
+        w.write_u8(self.bits_remaining);
+        w.write_bool(self.silence);
+        w.write_u8(self.output_level);
+        w.write_bool(self.irq_flag);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.irq_enabled = r.read_bool()?;
+        self.loop_flag = r.read_bool()?;
+        self.rate_index = r.read_u8()? & 0x0F;
+        self.timer_value = r.read_u16()?;
+        self.sample_address = r.read_u16()?;
+        self.sample_length = r.read_u16()?;
+        self.current_address = r.read_u16()?;
+        self.bytes_remaining = r.read_u16()?;
+        let has_sample = r.read_bool()?;
+        let sample = r.read_u8()?;
+        self.sample_buffer = has_sample.then_some(sample);
+        self.fetching = r.read_bool()?;
+        self.shift_register = r.read_u8()?;
+        // 0 would underflow on the next output clock
+        self.bits_remaining = r.read_u8()?.clamp(1, 8);
+        self.silence = r.read_bool()?;
+        self.output_level = r.read_u8()? & 0x7F;
+        self.irq_flag = r.read_bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs the channel for `cycles` cycles, serving its fetches from `memory` like the bus would
+    fn run(dmc: &mut Dmc, dma: &mut Dma, memory: impl Fn(u16) -> u8, cycles: usize) -> Vec<u16> {
+        let mut fetched = Vec::new();
+        for _ in 0..cycles {
+            dmc.step(dma);
+            if let Some(addr) = dma.take_dmc_address() {
+                fetched.push(addr);
+                dma.finish_dmc(memory(addr));
+            }
+        }
+        fetched
+    }
+
+    #[test]
+    fn sample_bits_move_the_output_level() {
+        let mut dmc = Dmc::new();
+        let mut dma = Dma::default();
+        dmc.write_control(0x0F); // fastest rate, 54 cycles per bit
+        dmc.write_direct_load(64);
+        dmc.write_sample_address(0x00);
+        dmc.write_sample_length(0x00); // 1 byte
+        dmc.set_enabled(true);
+
+        // the first 8 bits play silence while the byte waits in the buffer
+        let fetched = run(&mut dmc, &mut dma, |_| 0xFF, 54 * 8);
+        assert_eq!(fetched, vec![0xC000]);
+        assert_eq!(dmc.output_level, 64);
+        assert!(!dmc.is_active());
+
+        run(&mut dmc, &mut dma, |_| 0xFF, 54 * 8);
+        assert_eq!(dmc.output_level, 64 + 2 * 8);
+    }
+
+    #[test]
+    fn output_level_stays_in_range() {
+        let mut dmc = Dmc::new();
+        let mut dma = Dma::default();
+        dmc.write_control(0x4F); // looping
+        dmc.write_direct_load(124);
+        dmc.set_enabled(true);
+
+        run(&mut dmc, &mut dma, |_| 0xFF, 54 * 8 * 4);
+        assert_eq!(dmc.output_level, 126);
+
+        dmc.write_direct_load(3);
+        run(&mut dmc, &mut dma, |_| 0x00, 54 * 8 * 8);
+        assert_eq!(dmc.output_level, 1);
+    }
+
+    #[test]
+    fn looping_restarts_and_irq_fires_at_the_end() {
+        let mut dmc = Dmc::new();
+        let mut dma = Dma::default();
+        dmc.write_control(0x4F);
+        dmc.write_sample_address(0xFF); // $FFC0
+        dmc.write_sample_length(0x04); // 65 bytes, wraps to $8000
+        dmc.set_enabled(true);
+
+        let fetched = run(&mut dmc, &mut dma, |_| 0, 54 * 8 * 70);
+        assert_eq!(fetched[63], 0xFFFF);
+        assert_eq!(fetched[64], 0x8000);
+        assert_eq!(fetched[65], 0xFFC0);
+        assert!(dmc.is_active());
+        assert!(!dmc.irq_flag);
+
+        dmc.write_control(0x8F);
+        run(&mut dmc, &mut dma, |_| 0, 54 * 8 * 70);
+        assert!(!dmc.is_active());
+        assert!(dmc.irq_flag);
+
+        // disabling the irq acknowledges it
+        dmc.write_control(0x0F);
+        assert!(!dmc.irq_flag);
+    }
+
+    #[test]
+    fn disabling_stops_the_sample() {
+        let mut dmc = Dmc::new();
+        let mut dma = Dma::default();
+        dmc.write_control(0x0F);
+        dmc.write_sample_length(0xFF);
+        dmc.set_enabled(true);
+        run(&mut dmc, &mut dma, |_| 0, 54 * 8 * 2);
+        assert!(dmc.is_active());
+
+        dmc.set_enabled(false);
+        assert!(!dmc.is_active());
+        let fetched = run(&mut dmc, &mut dma, |_| 0, 54 * 8 * 4);
+        assert!(fetched.is_empty());
+    }
+
+    #[test]
+    fn disabling_during_a_fetch() {
+        let mut dmc = Dmc::new();
+        let mut dma = Dma::default();
+        dmc.write_control(0x8F);
+        dmc.write_sample_length(0x01);
+        dmc.set_enabled(true);
+
+        dmc.step(&mut dma);
+        let addr = dma.take_dmc_address().unwrap();
+        dmc.set_enabled(false);
+        dma.finish_dmc(addr as u8);
+
+        // the byte still arrives, but the sample is over and it doesn't end it again
+        dmc.step(&mut dma);
+        assert!(!dmc.is_active());
+        assert!(!dmc.irq_flag);
+        assert!(run(&mut dmc, &mut dma, |_| 0, 54 * 8 * 2).is_empty());
+    }
+}
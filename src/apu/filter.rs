@@ -0,0 +1,90 @@
+/// First-order high-pass or low-pass filter, for one sample rate
+#[derive(Debug, Clone, Copy)]
+pub struct OnePoleFilter {
+    high_pass: bool,
+    alpha: f32,
+    prev_input: f32,
+    prev_output: f32,
+}
+
+impl OnePoleFilter {
+    pub fn high_pass(cutoff: f32, sample_rate: f32) -> Self {
+        let rc = 1.0 / (std::f32::consts::TAU * cutoff);
+        let dt = 1.0 / sample_rate;
+        Self { high_pass: true, alpha: rc / (rc + dt), prev_input: 0.0, prev_output: 0.0 }
+    }
+
+    pub fn low_pass(cutoff: f32, sample_rate: f32) -> Self {
+        let rc = 1.0 / (std::f32::consts::TAU * cutoff);
+        let dt = 1.0 / sample_rate;
+        Self { high_pass: false, alpha: dt / (rc + dt), prev_input: 0.0, prev_output: 0.0 }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let output = if self.high_pass {
+            self.alpha * (self.prev_output + input - self.prev_input)
+        } else {
+            self.prev_output + self.alpha * (input - self.prev_output)
+        };
+        self.prev_input = input;
+        self.prev_output = output;
+        output
+    }
+}
+
+/// The filters between the 2A03 and the audio output of the console:
+/// a 90 Hz high-pass, a 440 Hz high-pass and a 14 kHz low-pass
+///
+/// The high-passes also take the DC offset out of the mixer output (which is always positive).
+///
+/// for more info:
+/// https://www.nesdev.org/wiki/APU_Mixer
+#[derive(Debug, Clone, Copy)]
+pub struct OutputFilters {
+    filters: [OnePoleFilter; 3],
+}
+
+impl OutputFilters {
+    pub fn new(sample_rate: u32) -> Self {
+        let rate = sample_rate as f32;
+        Self {
+            filters: [
+                OnePoleFilter::high_pass(90.0, rate),
+                OnePoleFilter::high_pass(440.0, rate),
+                OnePoleFilter::low_pass(14_000.0, rate),
+            ],
+        }
+    }
+
+    pub fn process(&mut self, sample: f32) -> f32 {
+        self.filters.iter_mut().fold(sample, |sample, filter| filter.process(sample))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Peak output once a sine of `frequency` has gone through the filters for a second
+    fn gain(frequency: f32) -> f32 {
+        let mut filters = OutputFilters::new(44_100);
+        (0..44_100)
+            .map(|i| filters.process((std::f32::consts::TAU * frequency * i as f32 / 44_100.0).sin()))
+            .skip(22_050)
+            .fold(0.0, |peak, sample| sample.abs().max(peak))
+    }
+
+    #[test]
+    fn filters_keep_the_middle_of_the_spectrum() {
+        assert!(gain(20.0) < 0.1);
+        assert!(gain(2_000.0) > 0.9);
+        assert!(gain(20_000.0) < 0.75);
+    }
+
+    #[test]
+    fn dc_offset_is_removed() {
+        let mut filters = OutputFilters::new(44_100);
+        let last = (0..44_100).map(|_| filters.process(0.5)).last().unwrap();
+        assert!(last.abs() < 1e-3);
+    }
+}
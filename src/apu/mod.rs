@@ -2,4 +2,7 @@ pub mod apu;
 pub mod square;
 pub mod audio;
 pub mod triangle;
-pub mod noise;
\ No newline at end of file
+pub mod noise;
+pub mod dmc;
+pub mod blip;
+pub mod filter;
\ No newline at end of file
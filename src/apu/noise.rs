@@ -1,10 +1,9 @@
-const NOISE_TIMER_PERIODS: [u16; 16] = [
-    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068
-];
+use crate::engine::region::Region;
+use crate::engine::save_state::{SaveStateError, StateReader, StateWriter};
 
 #[derive(Debug, Default)]
 pub struct Noise {
-    pub enabled: bool,
+    enabled: bool,
     timer_reload: u16,
     timer_value: u16,
 
@@ -23,7 +22,9 @@ pub struct Noise {
     envelope_start:     bool,
     envelope_divider:   u8,
     envelope_volume:    u8,
-    
+
+    /// picks the period table, set by [`crate::apu::apu::APU::set_region`]
+    pub region: Region,
 }
 impl Noise {
     pub fn new() -> Self {
@@ -34,6 +35,18 @@ impl Noise {
         }
     }
 
+    /// $4015 bit 3, the length counter is cleared while disabled
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.length_counter = 0;
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.length_counter > 0
+    }
+
     pub fn step(&mut self) {
         if self.timer_value == 0 {
             self.timer_value = self.timer_reload;
@@ -106,11 +119,46 @@ impl Noise {
     ///$400E
     pub fn write_noise(&mut self, data: u8) {
         self.noise_mode = data & 0x80 != 0;
-        self.timer_reload = NOISE_TIMER_PERIODS[(data & 0x0F) as usize]; 
+        self.timer_reload = self.region.noise_periods()[(data & 0x0F) as usize];
     }
 
     ///$400F
     pub fn write_length_counter(&mut self, data: u8) {
-        self.length_counter = super::square::LENGTH_TABLE[(data >> 3) as usize];
+        if self.enabled {
+            self.length_counter = super::square::LENGTH_TABLE[(data >> 3) as usize];
+        }
+    }
+
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_bool(self.enabled);
+        w.write_u16(self.timer_reload);
+        w.write_u16(self.timer_value);
+        w.write_bool(self.noise_mode);
+        w.write_bool(self.halt);
+        w.write_u8(self.length_counter);
+        w.write_bool(self.use_constant_vol);
+        w.write_u8(self.constant_vol);
+        w.write_u8(self.volume_envelope);
+        w.write_u16(self.lfsr);
+        w.write_bool(self.envelope_start);
+        w.write_u8(self.envelope_divider);
+        w.write_u8(self.envelope_volume);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.enabled = r.read_bool()?;
+        self.timer_reload = r.read_u16()?;
+        self.timer_value = r.read_u16()?;
+        self.noise_mode = r.read_bool()?;
+        self.halt = r.read_bool()?;
+        self.length_counter = r.read_u8()?;
+        self.use_constant_vol = r.read_bool()?;
+        self.constant_vol = r.read_u8()?;
+        self.volume_envelope = r.read_u8()?;
+        self.lfsr = r.read_u16()?;
+        self.envelope_start = r.read_bool()?;
+        self.envelope_divider = r.read_u8()?;
+        self.envelope_volume = r.read_u8()?;
+        Ok(())
     }
 }
\ No newline at end of file
@@ -1,3 +1,5 @@
+use crate::engine::save_state::{SaveStateError, StateReader, StateWriter};
+
 
 const SEQUENCE: [[bool; 8]; 4] = [
     [false, true, false, false, false, false, false, false], // 12.5%
@@ -13,7 +15,7 @@ pub const LENGTH_TABLE: [u8; 32] = [
 
 #[derive(Default, Debug)]
 pub struct SquareWave {
-    pub enabled:    bool,
+    enabled:        bool,
     pub is_pulse1: bool,
     timer_reload:   u16,
     timer_value:    u16,
@@ -47,6 +49,19 @@ impl SquareWave {
             ..Default::default()
         }
     }
+
+    /// $4015, disabling the channel clears its length counter and it ignores new lengths until it's enabled again
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.length_counter = 0;
+        }
+    }
+
+    /// True while the length counter isn't 0 (its bit in $4015)
+    pub fn is_active(&self) -> bool {
+        self.length_counter > 0
+    }
     pub fn step(&mut self) {
         if self.timer_value == 0 {
             self.timer_value = self.timer_reload + 1;
@@ -105,7 +120,9 @@ impl SquareWave {
     pub fn write_timer_hi(&mut self, data: u8) {
         self.timer_reload = (self.timer_reload & 0x00FF) | ((data as u16 & 0b111) << 8);
         self.duty_value = 0;
-        self.length_counter = LENGTH_TABLE[(data >> 3) as usize];
+        if self.enabled {
+            self.length_counter = LENGTH_TABLE[(data >> 3) as usize];
+        }
         self.envelope_start = true;
     }
     pub fn write_control(&mut self, data: u8) {
@@ -150,4 +167,53 @@ impl SquareWave {
             self.sweep_divider -= 1;
         }
     }
+
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_bool(self.enabled);
+        w.write_u16(self.timer_reload);
+        w.write_u16(self.timer_value);
+        w.write_u8(self.duty_cycle);
+        w.write_u8(self.duty_value);
+
+        w.write_u8(self.length_counter);
+        w.write_bool(self.halt);
+
+        w.write_bool(self.envelope_start);
+        w.write_u8(self.envelope_divider);
+        w.write_u8(self.envelope_volume);
+        w.write_bool(self.use_constant_vol);
+        w.write_u8(self.constant_volume);
+
+        w.write_bool(self.sweep_enabled);
+        w.write_u8(self.sweep_divider_period);
+        w.write_u8(self.sweep_divider);
+        w.write_bool(self.sweep_negate);
+        w.write_u8(self.sweep_shift);
+        w.write_bool(self.sweep_reload);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.enabled = r.read_bool()?;
+        self.timer_reload = r.read_u16()?;
+        self.timer_value = r.read_u16()?;
+        self.duty_cycle = r.read_u8()? & 0b11;
+        self.duty_value = r.read_u8()? % 8;
+
+        self.length_counter = r.read_u8()?;
+        self.halt = r.read_bool()?;
+
+        self.envelope_start = r.read_bool()?;
+        self.envelope_divider = r.read_u8()?;
+        self.envelope_volume = r.read_u8()?;
+        self.use_constant_vol = r.read_bool()?;
+        self.constant_volume = r.read_u8()?;
+
+        self.sweep_enabled = r.read_bool()?;
+        self.sweep_divider_period = r.read_u8()?;
+        self.sweep_divider = r.read_u8()?;
+        self.sweep_negate = r.read_bool()?;
+        self.sweep_shift = r.read_u8()? & 0x07;
+        self.sweep_reload = r.read_bool()?;
+        Ok(())
+    }
 }
\ No newline at end of file
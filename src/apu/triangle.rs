@@ -1,3 +1,5 @@
+use crate::engine::save_state::{SaveStateError, StateReader, StateWriter};
+
 const TRIANGLE_SEQUENCE: [f32; 32] = [
     15.0, 14.0, 13.0, 12.0, 11.0, 10.0, 9.0, 8.0,
     7.0,  6.0, 5.0, 4.0, 3.0, 2.0, 1.0, 0.0,
@@ -7,7 +9,7 @@ const TRIANGLE_SEQUENCE: [f32; 32] = [
 
 #[derive(Debug, Default)]
 pub struct TriangleWave {
-    pub enabled: bool,
+    enabled: bool,
     timer_value: u16,
     timer_reload: u16,
     linear_counter: u8,
@@ -39,6 +41,19 @@ impl TriangleWave {
         }
     }
     
+    /// $4015 bit 2, works like [`super::square::SquareWave::set_enabled`]
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.length_counter = 0;
+        }
+    }
+
+    /// $4015 bit 2 when read
+    pub fn is_active(&self) -> bool {
+        self.length_counter > 0
+    }
+
     pub fn get_amplitude(&mut self) -> f32 {
         if self.length_counter == 0 || self.linear_counter == 0 || !self.enabled { return 0.0 }
         let sample = TRIANGLE_SEQUENCE[self.sequence_pos as usize];
@@ -75,6 +90,33 @@ impl TriangleWave {
     pub fn write_timer_hi(&mut self, data: u8) {
         self.timer_reload = (self.timer_reload & 0x00FF) | (data as u16 & 0b111) << 8;
         self.linear_reload_flag = true;
-        self.length_counter = super::square::LENGTH_TABLE[(data >> 3) as usize];
+        if self.enabled {
+            self.length_counter = super::square::LENGTH_TABLE[(data >> 3) as usize];
+        }
+    }
+
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_bool(self.enabled);
+        w.write_u16(self.timer_value);
+        w.write_u16(self.timer_reload);
+        w.write_u8(self.linear_counter);
+        w.write_u8(self.counter_reload);
+        w.write_u8(self.sequence_pos);
+        w.write_bool(self.linear_halt);
+        w.write_bool(self.linear_reload_flag);
+        w.write_u8(self.length_counter);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.enabled = r.read_bool()?;
+        self.timer_value = r.read_u16()?;
+        self.timer_reload = r.read_u16()?;
+        self.linear_counter = r.read_u8()?;
+        self.counter_reload = r.read_u8()?;
+        self.sequence_pos = r.read_u8()? % 32;
+        self.linear_halt = r.read_bool()?;
+        self.linear_reload_flag = r.read_bool()?;
+        self.length_counter = r.read_u8()?;
+        Ok(())
     }
 }
\ No newline at end of file
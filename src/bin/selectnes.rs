@@ -0,0 +1,212 @@
+//! Command line runner, emulates a rom for a number of frames without opening a window
+//!
+//! ```text
+//! selectnes run rom.nes --frames 600 --screenshot out.png --wav out.wav --input movie.fm2 --dump-ram ram.bin
+//! ```
+//! Exits with 1 if the CPU halts (on a KIL opcode) and with 2 if the arguments or the rom are invalid.
+
+use std::{fs, path::PathBuf, process::ExitCode};
+
+use nes_emulator::{
+    apu::audio::AudioSink,
+    engine::{
+        console::{self, LogType, TERMINAL},
+        instance::EmulatorInstance,
+        movie::InputMovie,
+        region::Region,
+    },
+    ppu::ppu::{SCREEN_HEIGHT, SCREEN_WIDTH},
+};
+
+const USAGE: &str = "\
+usage: selectnes run <rom.nes> [options]
+
+options:
+    --frames <n>          frames to emulate (default 600)
+    --region <region>     ntsc, pal or dendy (default: from the rom header)
+    --input <movie.fm2>   controller input, see the fm2 format of FCEUX
+    --screenshot <png>    saves the last frame
+    --wav <wav>           records the audio
+    --dump-ram <bin>      saves the 2 KB of work RAM at the end
+    --verbose             prints the emulator logs";
+
+const WAV_SAMPLE_RATE: u32 = 44100;
+
+#[derive(Default)]
+struct RunArgs {
+    rom: PathBuf,
+    frames: u32,
+    region: Option<Region>,
+    input: Option<PathBuf>,
+    screenshot: Option<PathBuf>,
+    wav: Option<PathBuf>,
+    dump_ram: Option<PathBuf>,
+    verbose: bool,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<RunArgs, String> {
+    match args.next().as_deref() {
+        Some("run") => {}
+        Some(command) => return Err(format!("unknown command '{}'", command)),
+        None => return Err("missing command".into()),
+    }
+
+    let mut run = RunArgs { frames: 600, ..Default::default() };
+    let mut rom = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--frames" => run.frames = value()?.parse().map_err(|_| "--frames must be a number")?,
+            "--region" => run.region = Some(match value()?.to_lowercase().as_str() {
+                "ntsc"  => Region::Ntsc,
+                "pal"   => Region::Pal,
+                "dendy" => Region::Dendy,
+                other   => return Err(format!("unknown region '{}'", other)),
+            }),
+            "--input"      => run.input = Some(value()?.into()),
+            "--screenshot" => run.screenshot = Some(value()?.into()),
+            "--wav"        => run.wav = Some(value()?.into()),
+            "--dump-ram"   => run.dump_ram = Some(value()?.into()),
+            "--verbose"    => run.verbose = true,
+            _ if arg.starts_with("--") => return Err(format!("unknown option '{}'", arg)),
+            _ if rom.is_none() => rom = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument '{}'", arg)),
+        }
+    }
+
+    run.rom = rom.ok_or("missing the rom path")?;
+    Ok(run)
+}
+
+/// Keeps every sample to write them to a .wav file at the end
+struct WavRecorder {
+    samples: Vec<f32>,
+}
+
+impl AudioSink for WavRecorder {
+    fn sample_rate(&self) -> Option<u32> {
+        Some(WAV_SAMPLE_RATE)
+    }
+
+    fn push_sample(&mut self, sample: f32) {
+        self.samples.push(sample);
+    }
+}
+
+impl WavRecorder {
+    /// 16 bit mono PCM
+    fn to_wav(&self) -> Vec<u8> {
+        let data_len = (self.samples.len() * 2) as u32;
+        let mut wav = Vec::with_capacity(44 + data_len as usize);
+
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+        wav.extend_from_slice(b"WAVE");
+
+        wav.extend_from_slice(b"fmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());                     // PCM
+        wav.extend_from_slice(&1u16.to_le_bytes());                     // channels
+        wav.extend_from_slice(&WAV_SAMPLE_RATE.to_le_bytes());
+        wav.extend_from_slice(&(WAV_SAMPLE_RATE * 2).to_le_bytes());    // bytes per second
+        wav.extend_from_slice(&2u16.to_le_bytes());                     // bytes per sample
+        wav.extend_from_slice(&16u16.to_le_bytes());                    // bits per sample
+
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_len.to_le_bytes());
+        for sample in &self.samples {
+            let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            wav.extend_from_slice(&sample.to_le_bytes());
+        }
+        wav
+    }
+}
+
+/// The core only logs to [`TERMINAL`] (shown by the GUI), here they go to stderr
+fn flush_logs(verbose: bool) {
+    let Ok(mut logs) = TERMINAL.lock() else { return };
+    for log in logs.drain(..) {
+        match log.log_type {
+            LogType::Warning => eprintln!("[WARNING]: {}", log.log_msg),
+            _ if verbose     => eprintln!("{}", log.log_msg),
+            _ => {}
+        }
+    }
+}
+
+fn run(args: RunArgs) -> Result<bool, String> {
+    let movie = match &args.input {
+        Some(path) => {
+            let text = fs::read_to_string(path).map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+            InputMovie::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))?
+        }
+        None => InputMovie::default(),
+    };
+
+    let mut emulator = EmulatorInstance::new(args.rom.clone())
+        .map_err(|e| format!("failed to load {}: {}", args.rom.display(), e))?;
+    emulator.set_region(args.region);
+
+    let mut wav = WavRecorder { samples: Vec::new() };
+    let mut audio = args.wav.is_some().then_some(&mut wav);
+
+    for frame in 0..args.frames as usize {
+        let [p1, p2] = movie.frame(frame);
+        emulator.cpu.bus.joypad_1.set_buttons(p1);
+        emulator.cpu.bus.joypad_2.set_buttons(p2);
+
+        emulator.run_frame(&mut audio);
+        flush_logs(args.verbose);
+
+        if emulator.is_halted {
+            eprintln!(
+                "CPU halted on frame {} at ${:04X} (opcode ${:02X})",
+                frame, emulator.cpu.program_counter, emulator.cpu.last_opcode
+            );
+            break;
+        }
+    }
+
+    if let Some(path) = &args.screenshot {
+        image::save_buffer(
+            path,
+            emulator.frame_buffer(),
+            SCREEN_WIDTH as u32,
+            SCREEN_HEIGHT as u32,
+            image::ColorType::Rgba8,
+        ).map_err(|e| format!("couldn't save {}: {}", path.display(), e))?;
+    }
+    if let Some(path) = &args.wav {
+        fs::write(path, wav.to_wav()).map_err(|e| format!("couldn't save {}: {}", path.display(), e))?;
+    }
+    if let Some(path) = &args.dump_ram {
+        fs::write(path, emulator.cpu.bus.ram()).map_err(|e| format!("couldn't save {}: {}", path.display(), e))?;
+    }
+
+    Ok(!emulator.is_halted)
+}
+
+fn main() -> ExitCode {
+    console::LOG_DEBUG_ENABLED.store(false, std::sync::atomic::Ordering::Relaxed);
+
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    let verbose = args.verbose;
+
+    let result = run(args);
+    flush_logs(verbose);
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::from(2)
+        }
+    }
+}
@@ -1,16 +1,13 @@
 // The main (and, as far as I know, only) difference between the Ricoh 2A03 used in the NES and the MOS 6502 is that the 20A4 has integrated audio.
 // Since I haven't implemented audio, this effectively becomes a general-purpose 6502 microchip emulator. (except by the BUS, and NMI)
 
-use crate::cpu::opcodes::{self, opcodes_map};
+use crate::cpu::opcodes::{Instruction, OPCODES};
 use crate::engine::console::{LogType, print_logs};
+use crate::engine::save_state::{SaveStateError, StateReader, StateWriter};
+use crate::cpu::cpu_bus::CpuBus;
 use crate::memory::bus::BUS; 
 use crate::memory::mapper_base::*;
 
-use std::{collections::HashMap};
-
-use std::rc::Rc;
-use std::cell::RefCell;
-
 bitflags! {
 
     /// This struct defines the CPU flags, and the `bitflags!` macro makes them easier to work with.
@@ -37,7 +34,9 @@ const STACK_RESET: u8 = 0xfd;
 
 /// The main (and, as far as I know, only) difference between the Ricoh 2A03 used in the NES and the MOS 6502 is that the 2A03 has integrated audio.
 /// Since I haven't implemented audio, this effectively becomes a general-purpose 6502 microchip emulator.
-pub struct CPU {
+///
+/// Generic over the bus it's plugged into (see [`CpuBus`]), the NES [`BUS`] by default.
+pub struct CPU<B: CpuBus = BUS> {
     pub register_a: u8,
     pub register_x: u8,
     pub register_y: u8,
@@ -62,14 +61,14 @@ pub struct CPU {
     pub program_counter: u16,
     pub stack_pointer: u8, // SP
     pub cycles: u64,
-    pub bus: BUS,
+    pub bus: B,
     
     pub vblank: bool,
 
     pub last_opcode: u8,
     is_halted: bool
 }
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 #[allow(non_camel_case_types)]
 pub enum AddressingMode {
     Immediate,
@@ -89,7 +88,24 @@ pub enum AddressingMode {
 }
 
 impl CPU {
-    pub fn new(mapper: Rc<RefCell<dyn Mapper>>) -> Self {
+    pub fn new(mapper: Box<dyn Mapper>) -> Self {
+        Self::with_bus(BUS::new(mapper))
+    }
+
+    ///Writes the registers of the cpu followed by everything connected to the bus (see `BUS::save_state`)
+    pub fn save_state(&self, w: &mut StateWriter) {
+        self.save_registers(w);
+        self.bus.save_state(w);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.load_registers(r)?;
+        self.bus.load_state(r)
+    }
+}
+
+impl<B: CpuBus> CPU<B> {
+    pub fn with_bus(bus: B) -> Self {
         CPU {
             register_a: 0,
             register_x: 0,
@@ -97,7 +113,7 @@ impl CPU {
             status: CpuFlags::from_bits_truncate(0b100100),
             program_counter: 0,
             stack_pointer: STACK_RESET,
-            bus: BUS::new(mapper),
+            bus,
             vblank: false,
             cycles: 0,
             last_opcode : 0,
@@ -105,7 +121,21 @@ impl CPU {
         }
     }
 
+    /// Address of the operand for instructions that only read it,
+    /// indexed modes spend an extra cycle only when a page is crossed
     fn get_oprand_adress(&mut self, mode: &AddressingMode) -> (u16, bool) {
+        self.oprand_adress(mode, false)
+    }
+
+    /// Address of the operand for stores and read-modify-write instructions,
+    /// they always spend the extra cycle of the indexed modes (the CPU can't undo a write to the wrong page)
+    fn get_write_adress(&mut self, mode: &AddressingMode) -> (u16, bool) {
+        self.oprand_adress(mode, true)
+    }
+
+    /// Each access goes through the bus and takes one cycle, including the dummy reads of the
+    /// cycle-by-cycle tables (https://www.nesdev.org/wiki/CPU_addressing_modes)
+    fn oprand_adress(&mut self, mode: &AddressingMode, always_fix_page: bool) -> (u16, bool) {
         match mode {
             AddressingMode::Immediate => (self.program_counter, false), //pega o proximo imediato proximo valor
             //e joga na memoria (no register A)
@@ -116,11 +146,14 @@ impl CPU {
 
             AddressingMode::ZeroPage_X => {
                 let pos = self.bus.mem_read(self.program_counter);
+                // while the index is added the cpu reads the unindexed address
+                self.bus.mem_read(pos as u16);
                 let addr = pos.wrapping_add(self.register_x) as u16;
                 (addr, false)
             }
             AddressingMode::ZeroPage_Y => {
                 let pos = self.bus.mem_read(self.program_counter);
+                self.bus.mem_read(pos as u16);
                 let addr = pos.wrapping_add(self.register_y) as u16;
                 (addr, false)
             }
@@ -128,16 +161,25 @@ impl CPU {
                 let base = self.bus.mem_read_u16(self.program_counter);
                 let addr = base.wrapping_add(self.register_x as u16);
                 let page_crossed = (base & 0xFF00) != (addr & 0xFF00);
+                // while the high byte is fixed the cpu reads the address with the old high byte
+                // (that's the double read on $2007 / $4016 of `LDA $2007,X`-like instructions)
+                if page_crossed || always_fix_page {
+                    self.bus.mem_read((base & 0xFF00) | (addr & 0x00FF));
+                }
                 (addr, page_crossed)
             }
             AddressingMode::Absolute_Y => {
                 let base = self.bus.mem_read_u16(self.program_counter);
                 let addr = base.wrapping_add(self.register_y as u16);
                 let page_crossed = (base & 0xFF00) != (addr & 0xFF00);
+                if page_crossed || always_fix_page {
+                    self.bus.mem_read((base & 0xFF00) | (addr & 0x00FF));
+                }
                 (addr, page_crossed)
             }
             AddressingMode::Indirect_X => {
                 let base = self.bus.mem_read(self.program_counter);
+                self.bus.mem_read(base as u16);
 
                 let ptr: u8 = (base as u8).wrapping_add(self.register_x);
                 let lo = self.bus.mem_read(ptr as u16);
@@ -152,10 +194,13 @@ impl CPU {
                 let deref_base = (hi as u16) << 8 | (lo as u16);
                 let deref = deref_base.wrapping_add(self.register_y as u16);
                 let page_crossed = (deref_base & 0xFF00) != (deref & 0xFF00);
+                if page_crossed || always_fix_page {
+                    self.bus.mem_read((deref_base & 0xFF00) | (deref & 0x00FF));
+                }
                 (deref, page_crossed)
             }
             AddressingMode::NoneAddressing => {
-                panic!("Panic at PC: {:04X}, Opcode: {:02X}. Tentou buscar endereço em NoneAddressing.", self.program_counter - 1, self.last_opcode);
+                panic!("Panic at PC: {:04X}, Opcode: {:02X}. Tentou buscar endereço em NoneAddressing.", self.program_counter.wrapping_sub(1), self.last_opcode);
             }
 
         }
@@ -163,6 +208,9 @@ impl CPU {
 
     pub fn trigger_cpu_nmi(&mut self) {
         //sends the vblank interuption to the cpu
+        //(2 cycles reading the next opcode, that gets thrown away, then 3 pushes and the vector)
+        self.bus.mem_read(self.program_counter);
+        self.bus.mem_read(self.program_counter);
         self.stack_push_u16(self.program_counter);
 
         let mut flags_to_push = self.status.clone();
@@ -178,6 +226,8 @@ impl CPU {
 
         self.vblank = false; // Limpa a flag vblank
 
+        self.cycles += 7;
+
     }
 
     pub fn trigger_cpu_irq(&mut self) {
@@ -185,6 +235,8 @@ impl CPU {
             return;
         }
 
+        self.bus.mem_read(self.program_counter);
+        self.bus.mem_read(self.program_counter);
         self.stack_push_u16(self.program_counter);
 
         let mut status = self.status.bits();
@@ -213,8 +265,24 @@ impl CPU {
         self.register_a = 0;
         self.register_x = 0;
         self.status = CpuFlags::from_bits_truncate(0b100100);
+        self.is_halted = false;
+
+        self.program_counter = self.bus.mem_read_u16(0xFFFC);
+    }
+
+    /// The reset button, unlike the power-up the registers and the memory are kept
+    ///
+    /// The reset sequence is an interrupt with its 3 pushes turned into reads, so only the stack pointer moves.
+    /// The rest of the system gets the reset through [`CpuBus::reset`]
+    pub fn soft_reset(&mut self) {
+        self.status.insert(CpuFlags::INTERRUPT_DISABLE);
+        self.stack_pointer = self.stack_pointer.wrapping_sub(3);
+        self.is_halted = false;
+
+        self.bus.reset();
 
         self.program_counter = self.bus.mem_read_u16(0xFFFC);
+        self.cycles += 7;
     }
     #[inline]
     fn update_zero_and_negative_flags(&mut self, result:u8) {
@@ -275,7 +343,7 @@ impl CPU {
     /// 
     /// (page crosses)
     fn sta(&mut self, mode: &AddressingMode) -> u8 {
-        let (addr, _) = self.get_oprand_adress(mode);
+        let (addr, _) = self.get_write_adress(mode);
         self.bus.mem_write(addr, self.register_a); //o contrario do LDA, ainda usando os mesmos parametros do LDA
         //mas esse escreve o que esta no register na memoria
 
@@ -286,8 +354,9 @@ impl CPU {
     /// 
     /// (page crosses)
     fn rol(&mut self, mode: &AddressingMode) -> u8 {
-        let (addr, _) = self.get_oprand_adress(mode);
+        let (addr, _) = self.get_write_adress(mode);
         let mut data = self.bus.mem_read(addr);
+        self.bus.mem_write(addr, data); // dummy write of the unmodified value
         let previous_carry = self.status.contains(CpuFlags::CARRY);
 
         if data & 0b1000_0000 != 0 {
@@ -326,8 +395,9 @@ impl CPU {
     /// 
     /// (page crosses)
     fn ror(&mut self, mode: &AddressingMode) -> u8 {
-        let (addr, _) = self.get_oprand_adress(mode);
+        let (addr, _) = self.get_write_adress(mode);
         let mut data = self.bus.mem_read(addr);
+        self.bus.mem_write(addr, data); // dummy write of the unmodified value
         let previous_carry = self.status.contains(CpuFlags::CARRY);
 
         if data & 0b0000_0001 != 0 {
@@ -427,8 +497,9 @@ impl CPU {
     /// 
     /// (page crosses)
     fn dec_mem(&mut self, mode: &AddressingMode) -> u8 {
-        let (addr, _) = self.get_oprand_adress(mode);
+        let (addr, _) = self.get_write_adress(mode);
         let data = self.bus.mem_read(addr);
+        self.bus.mem_write(addr, data); // dummy write of the unmodified value
         
         let modification = data.wrapping_sub(1);
         self.bus.mem_write(addr, modification);
@@ -439,8 +510,9 @@ impl CPU {
 
     /// ASL - Arithmetic Shift Left
     fn asl(&mut self, mode: &AddressingMode) -> u8 {
-        let (addr, _) = self.get_oprand_adress(mode);
+        let (addr, _) = self.get_write_adress(mode);
         let mut data = self.bus.mem_read(addr);
+        self.bus.mem_write(addr, data); // dummy write of the unmodified value
         if data >> 7 == 1 {
             self.status.insert(CpuFlags::CARRY);
         }
@@ -465,8 +537,9 @@ impl CPU {
     }
     ///LSR - Logical Shift Right
     fn lsr(&mut self, mode: &AddressingMode) -> u8 {
-        let (addr, _) = self.get_oprand_adress(mode);
+        let (addr, _) = self.get_write_adress(mode);
         let mut data = self.bus.mem_read(addr);
+        self.bus.mem_write(addr, data); // dummy write of the unmodified value
         if (data & 0b0000_0001) != 0 {
             self.status.insert(CpuFlags::CARRY);
         }
@@ -564,8 +637,13 @@ impl CPU {
         //println!("data {}",data);
         data
     }
+    /// Dummy read of the top of the stack, done by the pulls while the stack pointer is incremented
+    fn read_stack_top(&mut self) {
+        self.bus.mem_read(STACK + self.stack_pointer as u16);
+    }
     ///PULL ACCUMULATOR
     fn pla(&mut self) {
+        self.read_stack_top();
         self.register_a = self.stack_pop();
         self.update_zero_and_negative_flags(self.register_a);
     }
@@ -577,6 +655,7 @@ impl CPU {
     ///Pull Processor Status
     fn plp(&mut self) {
 //TODO: necessario revisão e testes
+        self.read_stack_top();
         let flags = CpuFlags::from_bits_truncate(self.stack_pop());
         self.status = flags;
         self.status.remove(CpuFlags::BREAK);
@@ -608,13 +687,16 @@ impl CPU {
         self.stack_push(lo);
     }
     //Jump to Subroutine
+    ///the high byte of the target is only fetched after the return address was pushed
     fn jsr(&mut self) {
+        let lo = self.bus.mem_read(self.program_counter) as u16;
+        self.read_stack_top();
         self.stack_push_u16(self.program_counter.wrapping_add(1));
-        self.program_counter = self.bus.mem_read_u16(self.program_counter)
-        
+        let hi = self.bus.mem_read(self.program_counter.wrapping_add(1)) as u16;
+        self.program_counter = (hi << 8) | lo;
     }
     fn write_register(&mut self, mode: &AddressingMode, register: u8) {
-        let (addr, _) = self.get_oprand_adress(mode);
+        let (addr, _) = self.get_write_adress(mode);
         self.bus.mem_write(addr, register);
     }
     fn stack_pop_u16(&mut self) -> u16 {
@@ -625,6 +707,7 @@ impl CPU {
 
     ///RTI - Return from Interrupt
     fn rti(&mut self) {
+        self.read_stack_top();
         self.status = CpuFlags::from_bits_truncate(self.stack_pop());
         self.status.remove(CpuFlags::BREAK);
         self.status.insert(CpuFlags::BREAK2);
@@ -632,7 +715,10 @@ impl CPU {
     }
     ///RTS - Return from Subroutine
     fn rts(&mut self) {
+        self.read_stack_top();
         self.program_counter = self.stack_pop_u16();
+        // incrementing the pulled address takes one more cycle, reading the last byte of the JSR
+        self.bus.mem_read(self.program_counter);
         self.program_counter = self.program_counter.wrapping_add(1);
     }
 
@@ -640,15 +726,20 @@ impl CPU {
 
 
     fn branch_if(&mut self, condition: bool) -> u8 {
+        // the offset is always fetched, even when the branch isn't taken
+        let offset = self.bus.mem_read(self.program_counter) as i8;
+
         if condition {
             let mut extra_cycles = 1;
-
-            let offset = self.bus.mem_read(self.program_counter) as i8;
             let base_addr = self.program_counter.wrapping_add(1);
             let new_program_counter = base_addr.wrapping_add(offset as u16);
 
+            // the next opcode is read while the offset is added to the low byte,
+            // and once more from the wrong page while the high byte is fixed
+            self.bus.mem_read(base_addr);
             if (base_addr & 0xFF00) != (new_program_counter & 0xFF00) {
                 extra_cycles += 1;
+                self.bus.mem_read((base_addr & 0xFF00) | (new_program_counter & 0x00FF));
             }
 
             self.program_counter = new_program_counter;
@@ -664,6 +755,12 @@ impl CPU {
         let (addr, page_crossed) = self.get_oprand_adress(mode);
         let mem_val = self.bus.mem_read(addr);
 
+        self.set_compare_flags(register, mem_val);
+
+        if page_crossed { 1 } else { 0 }
+    }
+    ///flags of CMP/CPX/CPY (also used by the unofficial DCP and AXS)
+    fn set_compare_flags(&mut self, register: u8, mem_val: u8) {
         if register >= mem_val {
             self.status.insert(CpuFlags::CARRY);
         } else {
@@ -683,8 +780,6 @@ impl CPU {
         } else {
             self.status.remove(CpuFlags::NEGATIVE);
         }
-
-        if page_crossed { 1 } else { 0 }
     }
 
     ///ADC - Add with Carry
@@ -698,6 +793,12 @@ impl CPU {
         let (addr, page_crossed) = self.get_oprand_adress(mode);
         let val = self.bus.mem_read(addr);
 
+        self.add_to_accumulator(val);
+
+        if page_crossed { 1 } else { 0 }
+    }
+    ///the actual sum of the ADC, SBC is the same thing with the value inverted (A + !val + C)
+    fn add_to_accumulator(&mut self, val: u8) {
         let sum = self.register_a as u16 + val as u16  //+ (((self.status & 0b0000_0001) != 0) as u16); //adiciona 1 se for True e 0 se for False (Rust converte True para 1 e False para 0)
         + (if self.status.contains(CpuFlags::CARRY) {
             1
@@ -723,8 +824,6 @@ impl CPU {
         }
         self.register_a = result;
         self.update_zero_and_negative_flags(self.register_a);
-
-        if page_crossed { 1 } else { 0 }
     }
 
     ///SBC - Subtract with Carry
@@ -734,28 +833,7 @@ impl CPU {
         let (addr, page_crossed) = self.get_oprand_adress(mode);
         let val = self.bus.mem_read(addr);
 
-        let val_complement = !val;
-        let carry = if self.status.contains(CpuFlags::CARRY) { 1u16 } else { 0u16 };
-        let sum = self.register_a as u16 + val_complement as u16 + carry;
-
-        if sum > 0xff { //seta a carry flag
-            self.status.insert(CpuFlags::CARRY); 
-        } else {
-            self.status.remove(CpuFlags::CARRY);
-        }
-        
-        let result = sum as u8;
-
-        if ((self.register_a ^ val) & (self.register_a ^ result) & 0b1000_0000) != 0 { //seta a overflow flag
-            //usa os operadores logicos de XOR para verificar quais bits sao diferentes e dps verifica com 0b100...
-            //já q é o bit que quer ser verificado(o unico bit q importa)... se for diferente dos outros significa que
-            //ocorreu um overflow, 
-            self.status.insert(CpuFlags::OVERFLOW);
-        } else {
-            self.status.remove(CpuFlags::OVERFLOW);
-        }
-        self.register_a = result;
-        self.update_zero_and_negative_flags(self.register_a);
+        self.add_to_accumulator(!val);
 
         if page_crossed { 1 } else { 0 }
     }
@@ -775,8 +853,9 @@ impl CPU {
     ///
     ///(page crosses)
     fn inc_mem(&mut self, mode: &AddressingMode) -> u8 {
-        let (addr, _) = self.get_oprand_adress(mode);
+        let (addr, _) = self.get_write_adress(mode);
         let data = self.bus.mem_read(addr);
+        self.bus.mem_write(addr, data); // dummy write of the unmodified value
         
         let modification = data.wrapping_add(1);
         self.bus.mem_write(addr, modification);
@@ -797,6 +876,7 @@ impl CPU {
 
     /// BRK - Force Interrupt
     fn brk(&mut self) {
+        // the byte after BRK was read (and ignored) with the other one-byte instructions
         let return_addr = self.program_counter.wrapping_add(1);
         self.stack_push_u16(return_addr);
 
@@ -810,256 +890,555 @@ impl CPU {
         self.program_counter = self.bus.mem_read_u16(0xFFFE);
     }
 
-    pub fn step(&mut self) -> (bool, u8)  {
+    // ------------------ UNOFFICIAL OPCODES ------------------ //
+    // https://www.nesdev.org/wiki/CPU_unofficial_opcodes
+
+    ///NOPs that still read their operand (the read can have side effects on the PPU/APU registers)
+    /// 
+    /// (page crosses)
+    fn nop_read(&mut self, mode: &AddressingMode) -> u8 {
+        let (addr, page_crossed) = self.get_oprand_adress(mode);
+        self.bus.mem_read(addr);
+
+        if page_crossed { 1 } else { 0 }
+    }
+
+    ///LAX - LDA + LDX with the same value
+    /// 
+    /// (page crosses)
+    fn lax(&mut self, mode: &AddressingMode) -> u8 {
+        let (addr, page_crossed) = self.get_oprand_adress(mode);
+        let val = self.bus.mem_read(addr);
+
+        self.register_a = val;
+        self.register_x = val;
+        self.update_zero_and_negative_flags(val);
+
+        if page_crossed { 1 } else { 0 }
+    }
+
+    ///SAX - stores A & X (no flags affected)
+    fn sax(&mut self, mode: &AddressingMode) {
+        let (addr, _) = self.get_write_adress(mode);
+        self.bus.mem_write(addr, self.register_a & self.register_x);
+    }
+
+    ///DCP - DEC then CMP with the decremented value
+    fn dcp(&mut self, mode: &AddressingMode) {
+        let (addr, _) = self.get_write_adress(mode);
+        let old = self.bus.mem_read(addr);
+        self.bus.mem_write(addr, old); // dummy write of the unmodified value
+        let data = old.wrapping_sub(1);
+        self.bus.mem_write(addr, data);
+
+        self.set_compare_flags(self.register_a, data);
+    }
+
+    ///ISC - INC then SBC with the incremented value
+    fn isc(&mut self, mode: &AddressingMode) {
+        let (addr, _) = self.get_write_adress(mode);
+        let old = self.bus.mem_read(addr);
+        self.bus.mem_write(addr, old); // dummy write of the unmodified value
+        let data = old.wrapping_add(1);
+        self.bus.mem_write(addr, data);
+
+        self.add_to_accumulator(!data);
+    }
+
+    ///SLO - ASL then ORA with the shifted value
+    fn slo(&mut self, mode: &AddressingMode) {
+        let data = self.asl(mode);
+        self.register_a |= data;
+        self.update_zero_and_negative_flags(self.register_a);
+    }
+
+    ///RLA - ROL then AND with the rotated value
+    fn rla(&mut self, mode: &AddressingMode) {
+        let (addr, _) = self.get_write_adress(mode);
+        let data = self.bus.mem_read(addr);
+        self.bus.mem_write(addr, data); // dummy write of the unmodified value
+        let carry_in = self.status.contains(CpuFlags::CARRY) as u8;
+
+        self.status.set(CpuFlags::CARRY, data & 0b1000_0000 != 0);
+        let data = (data << 1) | carry_in;
+        self.bus.mem_write(addr, data);
+
+        self.register_a &= data;
+        self.update_zero_and_negative_flags(self.register_a);
+    }
+
+    ///SRE - LSR then EOR with the shifted value
+    fn sre(&mut self, mode: &AddressingMode) {
+        let data = self.lsr(mode);
+        self.register_a ^= data;
+        self.update_zero_and_negative_flags(self.register_a);
+    }
+
+    ///RRA - ROR then ADC with the rotated value (using the carry that came out of the ROR)
+    fn rra(&mut self, mode: &AddressingMode) {
+        let (addr, _) = self.get_write_adress(mode);
+        let data = self.bus.mem_read(addr);
+        self.bus.mem_write(addr, data); // dummy write of the unmodified value
+        let carry_in = self.status.contains(CpuFlags::CARRY) as u8;
+
+        self.status.set(CpuFlags::CARRY, data & 0b0000_0001 != 0);
+        let data = (data >> 1) | (carry_in << 7);
+        self.bus.mem_write(addr, data);
+
+        self.add_to_accumulator(data);
+    }
+
+    ///ANC - AND #imm, then the carry gets a copy of bit 7 (like after an ASL)
+    fn anc(&mut self, mode: &AddressingMode) {
+        self.and(mode);
+        self.status.set(CpuFlags::CARRY, self.register_a & 0b1000_0000 != 0);
+    }
+
+    ///ALR - AND #imm then LSR A
+    fn alr(&mut self, mode: &AddressingMode) {
+        self.and(mode);
+        self.lsr_accumulator();
+    }
+
+    ///ARR - AND #imm then ROR A, but C and V come from bits 6 and 5 of the result
+    fn arr(&mut self, mode: &AddressingMode) {
+        self.and(mode);
+        let carry_in = self.status.contains(CpuFlags::CARRY) as u8;
+        self.register_a = (self.register_a >> 1) | (carry_in << 7);
+        self.update_zero_and_negative_flags(self.register_a);
+
+        let bit_6 = (self.register_a >> 6) & 1;
+        let bit_5 = (self.register_a >> 5) & 1;
+        self.status.set(CpuFlags::CARRY, bit_6 != 0);
+        self.status.set(CpuFlags::OVERFLOW, bit_6 ^ bit_5 != 0);
+    }
+
+    ///AXS (SBX) - X = (A & X) - #imm, flags set like a CMP (the carry is ignored)
+    fn axs(&mut self, mode: &AddressingMode) {
+        let (addr, _) = self.get_oprand_adress(mode);
+        let val = self.bus.mem_read(addr);
+        let a_and_x = self.register_a & self.register_x;
+
+        self.set_compare_flags(a_and_x, val);
+        self.register_x = a_and_x.wrapping_sub(val);
+    }
+
+    ///XAA (ANE) - A = (A | magic) & X & #imm
+    fn xaa(&mut self, mode: &AddressingMode) {
+        let (addr, _) = self.get_oprand_adress(mode);
+        let val = self.bus.mem_read(addr);
+
+        self.register_a = (self.register_a | 0xEE) & self.register_x & val;
+        self.update_zero_and_negative_flags(self.register_a);
+    }
+
+    ///LXA (LAX #imm) - A = X = (A | magic) & #imm
+    fn lxa(&mut self, mode: &AddressingMode) {
+        let (addr, _) = self.get_oprand_adress(mode);
+        let val = self.bus.mem_read(addr);
+
+        self.register_a = (self.register_a | 0xFF) & val;
+        self.register_x = self.register_a;
+        self.update_zero_and_negative_flags(self.register_a);
+    }
+
+    ///LAS - A = X = SP = memory & SP
+    /// 
+    /// (page crosses)
+    fn las(&mut self, mode: &AddressingMode) -> u8 {
+        let (addr, page_crossed) = self.get_oprand_adress(mode);
+        let val = self.bus.mem_read(addr) & self.stack_pointer;
+
+        self.register_a = val;
+        self.register_x = val;
+        self.stack_pointer = val;
+        self.update_zero_and_negative_flags(val);
+
+        if page_crossed { 1 } else { 0 }
+    }
+
+    ///Shared logic of SHX, SHY, AHX and TAS: stores `data & (high byte of the base address + 1)`,
+    ///and when the indexing crosses a page that same value replaces the high byte of the address
+    fn store_and_high_byte(&mut self, mode: &AddressingMode, data: u8) {
+        let (addr, page_crossed) = self.get_write_adress(mode);
+        let index = match mode {
+            AddressingMode::Absolute_X => self.register_x,
+            _ => self.register_y,
+        };
+        let base_high = (addr.wrapping_sub(index as u16) >> 8) as u8;
+        let val = data & base_high.wrapping_add(1);
+
+        let addr = if page_crossed {
+            ((val as u16) << 8) | (addr & 0x00FF)
+        } else {
+            addr
+        };
+        self.bus.mem_write(addr, val);
+    }
+
+    ///JAM (KIL) - the processor gets stuck until it is reset
+    fn jam(&mut self) {
+        print_logs(LogType::Warning, format!("JAM Opcode {:02X}, at PC:{:04X}, CPU is halted",
+            self.last_opcode, self.program_counter.wrapping_sub(1)
+        ));
+        self.is_halted = true;
+    }
+
+    pub fn step(&mut self) -> (bool, u16)  {
         self.step_with_callback(None::<fn(&mut Self)>)
     }
 
-    pub fn step_with_callback<F>(&mut self, mut callback: Option<F>) -> (bool, u8) 
-    where F: FnMut(&mut CPU) {
-        let ref opcodes: &'static HashMap<u8, &'static opcodes::OpCode> = opcodes_map();
+    pub fn step_with_callback<F>(&mut self, mut callback: Option<F>) -> (bool, u16) 
+    where F: FnMut(&mut Self) {
+        // a JAM opcode locks the cpu until the next reset
+        if self.is_halted {
+            return (true, 0);
+        }
+
+        // a write to $4014 (or a dmc fetch) halts the cpu on the read of the next opcode
+        let dma_cycles = self.bus.run_pending_dma();
+        self.cycles += dma_cycles as u64;
 
         if let Some(ref mut c) = callback {
             c(self)
         }
 
+        // every cycle is a bus access, which already clocked the ppu/apu
+        let start_cycle = self.bus.cycle_counter();
+        let mut interrupt_cycles = 0;
+
+        // an NMI can also arrive while a DMA holds the cpu
+        let interrupts = self.bus.poll_interrupts();
+        self.vblank |= interrupts.nmi;
+
         if self.vblank {
             print_logs(LogType::Debug, format!("Vblank Triggered [PC:{} | A:{} | X:{} | Y:{} CYC: {}]",
                 self.bus.peek(self.program_counter), self.register_a, self.register_x, self.register_y, self.cycles
             ));
             self.trigger_cpu_nmi();
+            interrupt_cycles += 7;
         }
 
-        if self.bus.mapper.borrow_mut().irq_pending() && !self.status.contains(CpuFlags::INTERRUPT_DISABLE) {
+        if interrupts.irq && !self.status.contains(CpuFlags::INTERRUPT_DISABLE) {
             self.trigger_cpu_irq();
+            interrupt_cycles += 7;
         }
 
         self.last_opcode = self.bus.mem_read(self.program_counter);
-        self.program_counter += 1;
+        self.program_counter = self.program_counter.wrapping_add(1);
 
         let program_counter_state = self.program_counter;
 
-        let opcode = match opcodes.get(&self.last_opcode) {
-            Some(op) => op,
-            None => {
-                print_logs(LogType::Warning, format!("Ilegal Opcode {:02X}, at PC:{:04X}, CPU is halted",
-                    self.last_opcode, self.program_counter - 1
-                ));
-                self.is_halted = true;
-                return (true, 0);
-            }
-        };
+        let opcode = &OPCODES[self.last_opcode as usize];
         
         let mut opcycles = opcode.cycles;
 
-        match self.last_opcode {
+        use Instruction::*;
+        // the one-byte instructions (implied, accumulator, stack, BRK) read the byte after the opcode
+        // on their second cycle and throw it away
+        if opcode.len == 1 && opcode.instruction != Jam {
+            self.bus.mem_read(self.program_counter);
+        }
+
+        match opcode.instruction {
             //LDA
-            0xa9 | 0xa5 | 0xb5 | 0xad | 0xbd | 0xb9 | 0xa1 | 0xb1 => {
+            Lda => {
                 //cada uma das instruções representa o comando LDA mas como flags diferentes
                 opcycles += self.lda(&opcode.mode);
             }
 
             //STA
-            0x85 | 0x95 | 0x8d | 0x9d | 0x99 | 0x81 | 0x91 => {
+            Sta => {
                 opcycles += self.sta(&opcode.mode);
             }
 
             //ADC
-            0x69 | 0x65 | 0x75 | 0x6D | 0x7D | 0x79 | 0x61 | 0x71 => {
+            Adc => {
                 opcycles += self.adc(&opcode.mode);
             }
             //SBC
-            0xE9 | 0xE5 | 0xF5 | 0xED | 0xFD | 0xF9 | 0xE1 | 0xF1 => {
+            Sbc => {
                 opcycles += self.sbc(&opcode.mode);
             }
 
             //CMP (COMPARE A)
-            0xC9 | 0xC5 | 0xD5 | 0xCD | 0xDD | 0xD9 | 0xC1 | 0xD1 => {
+            Cmp => {
                 opcycles += self.compare(&opcode.mode, self.register_a);
             }
 
             //CPX (COMPARE X)
-            0xE0 | 0xE4 | 0xEC => {
+            Cpx => {
                 opcycles += self.compare(&opcode.mode, self.register_x);
             }
 
             //CPY (COMPARE Y)
-            0xC0 | 0xC4 | 0xCC => {
+            Cpy => {
                 opcycles += self.compare(&opcode.mode, self.register_y);
             }
 
             //STX - Store X
-            0x86 | 0x96 | 0x8E => {
+            Stx => {
                 self.write_register(&opcode.mode, self.register_x);
             }
             //STY - Store Y
-            0x84 | 0x94 | 0x8C => {
+            Sty => {
                 self.write_register(&opcode.mode, self.register_y);
             }
             //AND BITWISE
-            0x21 | 0x29 | 0x25 | 0x35 | 0x2D | 0x3D | 0x39 | 0x31 => {
+            And => {
                 opcycles += self.and(&opcode.mode);
             }
 
             //ASL - Arithmetic Shift Left
-            0x06 | 0x16 | 0x0E | 0x1E => { 
+            Asl => { 
                 self.asl(&opcode.mode);
             }
-            0x0A => {
+            AslAccumulator => {
                 self.asl_accumulator();
             }
             //LSR - Logical Shift Right
-            0x46 | 0x56 | 0x4e | 0x5e => {
+            Lsr => {
                 self.lsr(&opcode.mode);
             }
-            0x4A => {
+            LsrAccumulator => {
                 self.lsr_accumulator();
             }
             // BIT - Bit Test
-            0x24 | 0x2C => {
+            Bit => {
                 opcycles += self.bit(&opcode.mode);
             }
             // ORA
-            0x09 | 0x05 | 0x15 | 0x0D | 0x1D | 0x19 | 0x01 | 0x11 => {
+            Ora => {
                 opcycles += self.ora(&opcode.mode);
             }
             // EOR - Exclusive OR
-            0x49 | 0x45 | 0x55 | 0x4D | 0x5D | 0x59 | 0x41 | 0x51 => {
+            Eor => {
                 opcycles += self.eor(&opcode.mode);
             }
 
             //DEC - Decrement Memory
-            0xC6 | 0xD6 | 0xCE | 0xDE => {
+            Dec => {
                 opcycles += self.dec_mem(&opcode.mode);
             }
             //DEX - Decrement X
-            0xCA => self.dex(),
+            Dex => self.dex(),
             //DEY - Decrement Y
-            0x88 => self.dey(),
+            Dey => self.dey(),
 
             // BCC - Branch if Carry Clear
-            0x90 => { opcycles += self.branch_if(!self.status.contains(CpuFlags::CARRY)); }
+            Bcc => { opcycles += self.branch_if(!self.status.contains(CpuFlags::CARRY)); }
             // BCS - Branch if Carry Set
-            0xB0 => { opcycles += self.branch_if(self.status.contains(CpuFlags::CARRY)); }
+            Bcs => { opcycles += self.branch_if(self.status.contains(CpuFlags::CARRY)); }
             // BEQ - Branch if Equal
-            0xF0 => { opcycles += self.branch_if(self.status.contains(CpuFlags::ZERO)); }
+            Beq => { opcycles += self.branch_if(self.status.contains(CpuFlags::ZERO)); }
             // BNE - Branch if Not Equal
-            0xD0 => { opcycles += self.branch_if(!self.status.contains(CpuFlags::ZERO)); }
+            Bne => { opcycles += self.branch_if(!self.status.contains(CpuFlags::ZERO)); }
             // BPL - Branch if Plus
-            0x10 => { opcycles += self.branch_if(!self.status.contains(CpuFlags::NEGATIVE)); }
+            Bpl => { opcycles += self.branch_if(!self.status.contains(CpuFlags::NEGATIVE)); }
             // BMI - Branch if Minus
-            0x30 => { opcycles += self.branch_if(self.status.contains(CpuFlags::NEGATIVE)); }
+            Bmi => { opcycles += self.branch_if(self.status.contains(CpuFlags::NEGATIVE)); }
             // BVC - Branch if Overflow Clear
-            0x50 => { opcycles += self.branch_if(!self.status.contains(CpuFlags::OVERFLOW)); }
+            Bvc => { opcycles += self.branch_if(!self.status.contains(CpuFlags::OVERFLOW)); }
             // BVS - Branch if Overflow Set
-            0x70 => { opcycles += self.branch_if(self.status.contains(CpuFlags::OVERFLOW)); }
+            Bvs => { opcycles += self.branch_if(self.status.contains(CpuFlags::OVERFLOW)); }
 
             //SET FLAGS
-            0x38 => self.sec(),
-            0xF8 => self.sed(),
-            0x78 => self.sei(),
+            Sec => self.sec(),
+            Sed => self.sed(),
+            Sei => self.sei(),
             
             //CLEAR FLAGS
-            0x18 => self.clc(),
-            0xD8 => self.cld(),
-            0x58 => self.cli(),
-            0xB8 => self.clv(),
+            Clc => self.clc(),
+            Cld => self.cld(),
+            Cli => self.cli(),
+            Clv => self.clv(),
 
             //LDX 
-            0xA2 | 0xA6 | 0xB6 | 0xAE | 0xBE => {
+            Ldx => {
                 opcycles += self.ldx(&opcode.mode);
             }
             //LDY
-            0xA0 | 0xA4 | 0xB4 | 0xAC | 0xBC => {
+            Ldy => {
                 opcycles += self.ldy(&opcode.mode);
             }
             //TAX
-            0xAA => self.tax(),
+            Tax => self.tax(),
             //TAY
-            0xA8 => self.tay(),
+            Tay => self.tay(),
 
             //INC
-            0xE6 | 0xF6 | 0xEE | 0xFE => {
+            Inc => {
                 opcycles += self.inc_mem(&opcode.mode);
             }
 
             //ROL ACCUMULATOR / ROtate Accumulator Left
-            0x2A => self.rol_accumulator(),
+            RolAccumulator => self.rol_accumulator(),
 
             //ROL - Rotate Left
-            0x26 | 0x36 | 0x2E | 0x3E => {
+            Rol => {
                 opcycles += self.rol(&opcode.mode);
             }
 
             //ROR ACCUMULATOR / Rotate Accumulator Right
-            0x6A => self.ror_accumulator(),
+            RorAccumulator => self.ror_accumulator(),
 
             //ROR - Rotate Right
-            0x66 | 0x76 | 0x6E | 0x7E => {
+            Ror => {
                 opcycles += self.ror(&opcode.mode);
             }
 
             //PLA
-            0x68 => self.pla(),
+            Pla => self.pla(),
             //PHA
-            0x48 => self.pha(self.register_a),
+            Pha => self.pha(self.register_a),
             //PLP
-            0x28 => self.plp(),
+            Plp => self.plp(),
             //PHP - Push Processor Status
-            0x08 => self.php(),
+            Php => self.php(),
 
             //RTI - Return from Interrupt
-            0x40 => self.rti(),
+            Rti => self.rti(),
 
             //RTS - Return from Subroutine
-            0x60 => self.rts(),
+            Rts => self.rts(),
 
             //TSX
-            0xBA => self.tsx(),
+            Tsx => self.tsx(),
             //TXS
-            0x9A => self.txs(),
+            Txs => self.txs(),
 
             //JSR
-            0x20 => self.jsr(),
+            Jsr => self.jsr(),
 
             //JMP
-            0x4C => self.jmp_abs(),
-            0x6C => self.jmp_indrect(),
+            JmpAbsolute => self.jmp_abs(),
+            JmpIndirect => self.jmp_indrect(),
 
             //INX
-            0xe8 => self.inx(),
+            Inx => self.inx(),
 
             //INY
-            0xC8 => self.iny(),
+            Iny => self.iny(),
 
             //TXA
-            0x8A => self.transfer_to_accumulator(self.register_x),
+            Txa => self.transfer_to_accumulator(self.register_x),
             //TYA
-            0x98 => self.transfer_to_accumulator(self.register_y),
+            Tya => self.transfer_to_accumulator(self.register_y),
 
-            //NOP - No Operation
-            0xEA => {
+            //NOP - No Operation (and the unofficial implied ones)
+            Nop => {
 
             }
 
             //BRK
-            0x00 => self.brk(),
+            Brk => self.brk(),
+
+            // ------------- UNOFFICIAL -------------
+
+            //NOP (reads the operand)
+            NopRead => {
+                opcycles += self.nop_read(&opcode.mode);
+            }
+
+            //LAX
+            Lax => {
+                opcycles += self.lax(&opcode.mode);
+            }
+            //SAX
+            Sax => self.sax(&opcode.mode),
+
+            //DCP
+            Dcp => self.dcp(&opcode.mode),
+            //ISC
+            Isb => self.isc(&opcode.mode),
+            //SLO
+            Slo => self.slo(&opcode.mode),
+            //RLA
+            Rla => self.rla(&opcode.mode),
+            //SRE
+            Sre => self.sre(&opcode.mode),
+            //RRA
+            Rra => self.rra(&opcode.mode),
+
+            //ANC
+            Anc => self.anc(&opcode.mode),
+            //ALR
+            Alr => self.alr(&opcode.mode),
+            //ARR
+            Arr => self.arr(&opcode.mode),
+            //AXS
+            Axs => self.axs(&opcode.mode),
+
+            //XAA
+            Xaa => self.xaa(&opcode.mode),
+            //LXA
+            Lxa => self.lxa(&opcode.mode),
+            //LAS
+            Las => {
+                opcycles += self.las(&opcode.mode);
+            }
+            //AHX
+            Ahx => self.store_and_high_byte(&opcode.mode, self.register_a & self.register_x),
+            //SHY
+            Shy => self.store_and_high_byte(&opcode.mode, self.register_y),
+            //SHX
+            Shx => self.store_and_high_byte(&opcode.mode, self.register_x),
+            //TAS
+            Tas => {
+                self.stack_pointer = self.register_a & self.register_x;
+                self.store_and_high_byte(&opcode.mode, self.stack_pointer);
+            }
 
-            _ => print_logs(LogType::Warning, format!("code: {}", self.last_opcode))
+            //JAM
+            Jam => {
+                self.jam();
+                return (true, 0);
+            }
         }
 
         self.cycles += opcycles as u64;
 
-        let tick_result =  self.bus.tick(opcycles);
+        let total_cycles = opcycles + interrupt_cycles;
+        let spent = self.bus.cycle_counter() - start_cycle;
+        debug_assert_eq!(spent, total_cycles as u64, "opcode {:02X} clocked the bus {} times in {} cycles",
+            self.last_opcode, spent, total_cycles);
 
-        if tick_result.nmi {
+        if self.bus.poll_interrupts().nmi {
             self.vblank = true;
         }
 
         //increments the program counter accordingly to how many cicles the opcode is specified at the hashmap
         if program_counter_state == self.program_counter {
-            self.program_counter += (opcode.len -1) as u16;
+            self.program_counter = self.program_counter.wrapping_add((opcode.len - 1) as u16);
         }
-        (false, opcycles)
+        (false, total_cycles as u16 + dma_cycles)
+    }
+
+    fn save_registers(&self, w: &mut StateWriter) {
+        w.write_u8(self.register_a);
+        w.write_u8(self.register_x);
+        w.write_u8(self.register_y);
+        w.write_u8(self.status.bits());
+        w.write_u16(self.program_counter);
+        w.write_u8(self.stack_pointer);
+        w.write_u64(self.cycles);
+        w.write_bool(self.vblank);
+        w.write_u8(self.last_opcode);
+        w.write_bool(self.is_halted);
+    }
+
+    fn load_registers(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.register_a = r.read_u8()?;
+        self.register_x = r.read_u8()?;
+        self.register_y = r.read_u8()?;
+        self.status = CpuFlags::from_bits_truncate(r.read_u8()?);
+        self.program_counter = r.read_u16()?;
+        self.stack_pointer = r.read_u8()?;
+        self.cycles = r.read_u64()?;
+        self.vblank = r.read_bool()?;
+        self.last_opcode = r.read_u8()?;
+        self.is_halted = r.read_bool()?;
+        Ok(())
     }
 
     pub fn format_cpu_status(status: u8) -> String {
@@ -1083,14 +1462,16 @@ mod test {
     #[allow(unused)]
     use crate::memory::mapper_base::*;
 
+    use crate::cpu::cpu_bus::{BusCycle, RamBus};
     use crate::memory::mappers::dummy_mapper::TestMapper;
+    use crate::memory::joypads::JoyPadButtons;
     use super::*;
 
-    impl CPU {
+    impl<B: CpuBus> CPU<B> {
         pub fn run_test(&mut self) {
             self.reset_interrupt();
             loop {
-                let opcode = self.bus.mem_read(self.program_counter);
+                let opcode = self.bus.peek(self.program_counter);
                 
                 if opcode == 0x00 { break; }
 
@@ -1103,8 +1484,7 @@ mod test {
     #[test]
     fn test_0xa9_lda_immediate() {
 
-        let mapper = TestMapper::new(vec![0xa9, 0x05, 0x00], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0xa9, 0x05, 0x00]));
 
         cpu.run_test();
         assert_eq!(cpu.register_a, 0x05);
@@ -1113,8 +1493,7 @@ mod test {
     }
     #[test]
     fn test_0xa9_lda_zero_flag() {
-        let mapper = TestMapper::new(vec![0xa9, 0x00, 0x00], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0xa9, 0x00, 0x00]));
 
         cpu.run_test();
         assert!(cpu.status.contains(CpuFlags::ZERO));
@@ -1122,8 +1501,7 @@ mod test {
   
     #[test]
     fn test_lda_from_memory() {
-        let mapper = TestMapper::new(vec![0xA5, 0x10, 0x00], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0xA5, 0x10, 0x00]));
 
         cpu.bus.mem_write(0x10, 0x55);
 
@@ -1133,8 +1511,7 @@ mod test {
     }
     #[test]
     fn test_ldx_from_memory() {
-        let mapper = TestMapper::new(vec![0xA6, 0x10, 0x00], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0xA6, 0x10, 0x00]));
 
         cpu.bus.mem_write(0x10, 0x55);
 
@@ -1145,8 +1522,7 @@ mod test {
 
     #[test]
     fn test_ldy_from_memory() {
-        let mapper = TestMapper::new(vec![0xA4, 0x10, 0x00], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0xA4, 0x10, 0x00]));
 
         cpu.bus.mem_write(0x10, 0x55);
 
@@ -1158,8 +1534,7 @@ mod test {
     // ------------------- TAX ------------------
     #[test]
     fn test_0xaa_tax() {
-        let mapper = TestMapper::new(vec![0xa9, 0x0a, 0xAA, 0x00], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0xa9, 0x0a, 0xAA, 0x00]));
 
         cpu.run_test(); //primeiro inserir LDA, no register A, o valor 0x0a(q é 10)
         //depois coloca esse valor no register x como comando TAX (0xAA), depois break
@@ -1168,8 +1543,7 @@ mod test {
     // ------------------- INX ------------------
     #[test]
     fn test_0xe8_inx() {
-        let mapper = TestMapper::new(vec![0xE8, 0x00], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0xE8, 0x00]));
 
         cpu.run_test();
 
@@ -1178,8 +1552,7 @@ mod test {
     // --------------- WRITE MEMORY --------------------
     #[test]
     fn test_write_mem() {
-        let mapper = TestMapper::new(vec![], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[]));
 
         cpu.bus.mem_write_u16(0x1fef, 0xef);
 
@@ -1189,8 +1562,7 @@ mod test {
     #[test]
     // -------------------- ADC ------------------------
     fn test_adc_from_immediate() {
-        let mapper = TestMapper::new(vec![0x69, 0x10, 0x69, 0x32], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0x69, 0x10, 0x69, 0x32]));
 
         //cpu.bus.mem_write(0x69, data);
         cpu.run_test();
@@ -1198,12 +1570,10 @@ mod test {
     }
     #[test]
     fn test_adc_from_memory() {
-        let mapper = TestMapper::new(vec![
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[
         0xa5, 0x10, // LDA ZeroPage, operando 0x10
         0x65, 0x20, // ADC ZeroPage, operando 0x20
-        0x00], 
-        Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        0x00]));
 
         cpu.bus.mem_write(0x10, 0xab); //carrega 0xab
         cpu.bus.mem_write(0x20, 0x05); //carrega 0x02
@@ -1214,8 +1584,7 @@ mod test {
     }
     #[test]
     fn test_adc_carry_flag() {
-        let mapper = TestMapper::new(vec![0xa5, 0x10, 0x65, 0x20, 0x00], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0xa5, 0x10, 0x65, 0x20, 0x00]));
 
         cpu.bus.mem_write(0x10, 0xff);
         cpu.bus.mem_write(0x20, 0x01);
@@ -1227,8 +1596,7 @@ mod test {
     }
     #[test]
     fn test_adc_carry_sum() {
-        let mapper = TestMapper::new(vec![0xa5, 0x10, 0x65, 0x20, 0x69, 0x50, 0x00], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0xa5, 0x10, 0x65, 0x20, 0x69, 0x50, 0x00]));
 
         cpu.bus.mem_write(0x10, 0xff);
         cpu.bus.mem_write(0x20, 0x01);
@@ -1250,8 +1618,7 @@ mod test {
         //const OVERFLOW          = 0b01000000;
         //const NEGATIVE          = 0b10000000;
 
-        let mapper = TestMapper::new(vec![], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[]));
 
         cpu.status = CpuFlags::from_bits_truncate(0b0100_1101);
 
@@ -1271,8 +1638,7 @@ mod test {
 
     #[test]
     fn test_set_flags() {
-        let mapper = TestMapper::new(vec![0x38, 0xF8, 0x78], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0x38, 0xF8, 0x78]));
 
         cpu.run_test();
 
@@ -1282,8 +1648,7 @@ mod test {
     }
     #[test]
     fn test_compare() {
-        let mapper = TestMapper::new(vec![0xa9, 0x2f, 0xC5, 0x10,], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0xa9, 0x2f, 0xC5, 0x10,]));
 
         cpu.bus.mem_write(0x10, 0x2f);
         cpu.run_test();
@@ -1294,8 +1659,7 @@ mod test {
     }
     #[test]
     fn test_and_instruction() {
-        let mapper = TestMapper::new(vec![0xa9, 0x1A, 0x25, 0x10,], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0xa9, 0x1A, 0x25, 0x10,]));
         
         cpu.bus.mem_write(0x10, 0x5C); //0x5C == 0b0101_1100
         cpu.run_test(); //0x1A == 0b0001_1010
@@ -1304,8 +1668,7 @@ mod test {
     }
     #[test]
     fn test_asl_instruction() {
-        let mapper = TestMapper::new(vec![0x06, 0x10], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0x06, 0x10]));
 
         cpu.bus.mem_write(0x10, 0b0001_0000);
         cpu.run_test();
@@ -1313,22 +1676,19 @@ mod test {
     }
     #[test]
     fn test_bcc_instruction() {
-        let mapper = TestMapper::new(vec![0x90, 0x03, 0x00, 0x00, 0x00, 0xa9, 0xff, 0x00 ], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0x90, 0x03, 0x00, 0x00, 0x00, 0xa9, 0xff, 0x00 ]));
 
         // |0x38 - sec | 0x90 - bcc | 0x18 - clc |
         cpu.run_test();
         assert_eq!(cpu.register_a, 0xff);
 
-        let mapper = TestMapper::new(vec![0x38, 0x90, 0x02,  0xa9, 0xab, 0x00,], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0x38, 0x90, 0x02,  0xa9, 0xab, 0x00,]));
         cpu.run_test();
         assert_eq!(cpu.register_a, 0xab);
     }
     #[test]
     fn test_bit_instruction() {
-        let mapper = TestMapper::new(vec![0xa9, 0xf8, 0x24, 0x20], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0xa9, 0xf8, 0x24, 0x20]));
 
         cpu.bus.mem_write(0x20, 0xf3);
         cpu.run_test();
@@ -1338,8 +1698,7 @@ mod test {
     }
     #[test]
     fn test_decrement_memory() {
-        let mapper = TestMapper::new(vec![0xc6, 0x50, 0x00], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0xc6, 0x50, 0x00]));
 
         cpu.bus.mem_write(0x50, 0x0f);
         cpu.run_test();
@@ -1347,8 +1706,7 @@ mod test {
     }
     #[test]
     fn test_decrement_register() {
-        let mapper = TestMapper::new(vec![0xA2, 0x0f, 0xCA, 0xA0, 0x09, 0x88, 0x00], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0xA2, 0x0f, 0xCA, 0xA0, 0x09, 0x88, 0x00]));
 
         cpu.run_test();
 
@@ -1357,8 +1715,7 @@ mod test {
     }
     #[test]
     fn test_increment_mem() {
-        let mapper = TestMapper::new(vec![0xE6, 0x10, 0x00], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0xE6, 0x10, 0x00]));
 
         cpu.bus.mem_write(0x10, 0x0e);
         cpu.run_test();
@@ -1366,8 +1723,7 @@ mod test {
     }
     #[test]
     fn test_jump_abs() {
-        let mapper = TestMapper::new(vec![0x4c, 0x05, 0x80, 0xa9, 0x10, 0xA2, 0x30, 0x00], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0x4c, 0x05, 0x80, 0xa9, 0x10, 0xA2, 0x30, 0x00]));
 
         cpu.run_test();
 
@@ -1376,8 +1732,7 @@ mod test {
     }
     #[test]
     fn test_pha() {
-        let mapper = TestMapper::new(vec![0xa9, 0xe0, 0x48], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0xa9, 0xe0, 0x48]));
         
         cpu.run_test();
         assert_eq!(cpu.bus.mem_read((STACK + cpu.stack_pointer as u16).wrapping_add(1)), 0xe0)
@@ -1385,8 +1740,7 @@ mod test {
     #[test]
     //it doenst purposefully panic anymore
     fn test_pla_at_empty_stack() {
-        let mapper = TestMapper::new(vec![0x68, 0x00], Mirroring::Horizontal); 
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0x68, 0x00]));
 
         cpu.stack_pointer = 0xFF;
 
@@ -1400,16 +1754,14 @@ mod test {
 
     #[test]
     fn test_pla_correctly() {
-        let mapper = TestMapper::new(vec![0xa9, 0xe0, 0x48, 0xa9, 0xd0, 0x48, 0xa9, 0x10, 0x68], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0xa9, 0xe0, 0x48, 0xa9, 0xd0, 0x48, 0xa9, 0x10, 0x68]));
 
         cpu.run_test();
         assert_eq!(cpu.register_a, 0xd0);
     }
     #[test]
     fn test_eor() {
-        let mapper = TestMapper::new(vec![0xa9, 0xA2, 0x45, 0x20], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0xa9, 0xA2, 0x45, 0x20]));
 
         cpu.bus.mem_write(0x20, 0xe0);
         cpu.run_test();
@@ -1417,10 +1769,9 @@ mod test {
     }
     #[test]
     fn test_jsr() {
-        let mapper = TestMapper::new(vec![
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[
             0x20, 0x34, 0x12, 0x00  // JSR $1234
-        ], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        ]));
 
         cpu.run_test();
         assert_eq!(cpu.program_counter, 0x1234);
@@ -1428,8 +1779,7 @@ mod test {
     }
     #[test]
     fn test_register_write() {
-        let mapper = TestMapper::new(vec![0xA2, 0xe0, 0x86, 0x20], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0xA2, 0xe0, 0x86, 0x20]));
         
         cpu.run_test();
         println!("registrador {}",cpu.register_x);
@@ -1437,8 +1787,7 @@ mod test {
     }
     #[test]
     fn test_lsr() {
-        let mapper = TestMapper::new(vec![0x46, 0x20], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0x46, 0x20]));
 
         cpu.bus.mem_write(0x20, 0xe1);
         cpu.run_test();
@@ -1447,8 +1796,7 @@ mod test {
     }
     #[test]
     fn test_ora_from_mem() {
-        let mapper = TestMapper::new(vec![0xa9, 0xa1, 0x05, 0x20], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0xa9, 0xa1, 0x05, 0x20]));
 
         cpu.bus.mem_write(0x20, 0xe0);
         cpu.run_test();
@@ -1456,24 +1804,21 @@ mod test {
     }
     #[test]
     fn test_ora_from_immidiate() {
-        let mapper = TestMapper::new(vec![0xa9, 0xa1, 0x09, 0xe0], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0xa9, 0xa1, 0x09, 0xe0]));
 
         cpu.run_test();
         assert_eq!(cpu.register_a, 0b1110_0001);
     }
     #[test]
     fn test_php() {
-        let mapper = TestMapper::new(vec![0x08], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0x08]));
 
         cpu.run_test();
         assert_eq!(cpu.stack_pop(), 0b0011_0100);
     }
     #[test]
     fn test_sbc() {
-        let mapper = TestMapper::new(vec![0x38, 0xa9, 0xe0, 0xe5, 0x20], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0x38, 0xa9, 0xe0, 0xe5, 0x20]));
 
         cpu.bus.mem_write(0x20, 0x02);
         cpu.run_test();
@@ -1481,8 +1826,7 @@ mod test {
     }
     #[test]
     fn test_rti() {
-        let mapper = TestMapper::new(vec![0x40], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0x40]));
 
         cpu.stack_push_u16(0x1234);
         cpu.stack_push(0b0010_0100);
@@ -1495,8 +1839,7 @@ mod test {
     }
     #[test]
     fn test_rol() {
-        let mapper = TestMapper::new(vec![0x26, 0x20], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0x26, 0x20]));
 
         cpu.bus.mem_write(0x20, 0xe0);
         cpu.run_test();
@@ -1504,18 +1847,243 @@ mod test {
     }
     #[test]
     fn test_txa() {
-        let mapper = TestMapper::new(vec![0xa2, 0xe0, 0x8a], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0xa2, 0xe0, 0x8a]));
 
         cpu.run_test();
         assert_eq!(cpu.register_a, 0xe0);
     }
     #[test]
     fn test_tsx() {
-        let mapper = TestMapper::new(vec![0xba], Mirroring::Horizontal);
-        let mut cpu = CPU::new(mapper);
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0xba]));
 
         cpu.run_test();
         assert_eq!(cpu.register_x, STACK_RESET);
     }
+
+    // ------------------- UNOFFICIAL ------------------
+    #[test]
+    fn test_lax() {
+        // LDA #$37, STA $10, LAX $10
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0xa9, 0x37, 0x85, 0x10, 0xa9, 0x00, 0xa7, 0x10]));
+
+        cpu.run_test();
+        assert_eq!(cpu.register_a, 0x37);
+        assert_eq!(cpu.register_x, 0x37);
+    }
+
+    #[test]
+    fn test_dcp() {
+        // LDA #$05, STA $10, DCP $10
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0xa9, 0x05, 0x85, 0x10, 0xc7, 0x10]));
+
+        cpu.run_test();
+        assert_eq!(cpu.bus.mem_read(0x10), 0x04);
+        assert!(cpu.status.contains(CpuFlags::CARRY));
+        assert!(!cpu.status.contains(CpuFlags::ZERO));
+    }
+
+    #[test]
+    fn test_axs() {
+        // LDA #$0F, LDX #$0C, AXS #$02
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0xa9, 0x0f, 0xa2, 0x0c, 0xcb, 0x02]));
+
+        cpu.run_test();
+        assert_eq!(cpu.register_x, 0x0a);
+        assert!(cpu.status.contains(CpuFlags::CARRY));
+    }
+
+    #[test]
+    fn test_jam_locks_the_cpu() {
+        // JAM, INX
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0x02, 0xe8]));
+        cpu.reset_interrupt();
+
+        assert_eq!(cpu.step(), (true, 0));
+        assert_eq!(cpu.step(), (true, 0));
+        assert_eq!(cpu.register_x, 0);
+
+        // only a reset brings it back
+        cpu.reset_interrupt();
+        cpu.program_counter = 0x8001;
+        assert!(!cpu.step().0);
+        assert_eq!(cpu.register_x, 1);
+    }
+
+    #[test]
+    fn test_program_counter_wraps_around() {
+        // NOP at $FFFF, then LDA $1234 at $FFFE with its operand split between $FFFF and $0000
+        let mut bus = RamBus::new();
+        bus.ram_mut()[0xFFFF] = 0xea;
+        let mut cpu = CPU::with_bus(bus);
+        cpu.program_counter = 0xFFFF;
+        assert!(!cpu.step().0);
+        assert_eq!(cpu.program_counter, 0x0000);
+
+        let ram = cpu.bus.ram_mut();
+        ram[0xFFFE] = 0xad;
+        ram[0xFFFF] = 0x34;
+        ram[0x0000] = 0x12;
+        ram[0x1234] = 0x42;
+        cpu.program_counter = 0xFFFE;
+        cpu.step();
+        assert_eq!(cpu.register_a, 0x42);
+        assert_eq!(cpu.program_counter, 0x0001);
+    }
+
+    // ------------------- CYCLES ------------------
+    #[test]
+    fn test_bus_is_clocked_every_cycle() {
+        // LDX #$01, LDA $80FF,X (page cross), INC $10,X, JSR $800A, NOP
+        let mut cpu = CPU::with_bus(RamBus::with_program(&[0xa2, 0x01, 0xbd, 0xff, 0x80, 0xf6, 0x10, 0x20, 0x0a, 0x80, 0xea]));
+        cpu.reset_interrupt();
+
+        for expected in [2, 5, 6, 6, 2] {
+            let before = cpu.bus.cycle_counter();
+            let (_, cycles) = cpu.step();
+            assert_eq!(cycles, expected);
+            assert_eq!(cpu.bus.cycle_counter() - before, expected as u64);
+        }
+        assert_eq!(cpu.program_counter, 0x800b);
+    }
+
+    #[test]
+    fn test_dummy_cycles_are_on_the_bus() {
+        // CLC, PHA, PLA, JSR $8010, BCC -16 (to $7FF8, crossing a page) and an RTS at $8010
+        let mut bus = RamBus::with_program(&[0x18, 0x48, 0x68, 0x20, 0x10, 0x80, 0x90, 0xf0]);
+        bus.ram_mut()[0x8010] = 0x60;
+        bus.record_cycles = true;
+        let mut cpu = CPU::with_bus(bus);
+        cpu.reset_interrupt();
+        cpu.bus.take_cycles();
+
+        use BusCycle::*;
+        let expected: [&[BusCycle]; 6] = [
+            &[Read(0x8000, 0x18), Read(0x8001, 0x48)],
+            &[Read(0x8001, 0x48), Read(0x8002, 0x68), Write(0x01FD, 0x00)],
+            &[Read(0x8002, 0x68), Read(0x8003, 0x20), Read(0x01FC, 0x00), Read(0x01FD, 0x00)],
+            &[Read(0x8003, 0x20), Read(0x8004, 0x10), Read(0x01FD, 0x00), Write(0x01FD, 0x80), Write(0x01FC, 0x05), Read(0x8005, 0x80)],
+            &[Read(0x8010, 0x60), Read(0x8011, 0x00), Read(0x01FB, 0x00), Read(0x01FC, 0x05), Read(0x01FD, 0x80), Read(0x8005, 0x80)],
+            &[Read(0x8006, 0x90), Read(0x8007, 0xf0), Read(0x8008, 0x00), Read(0x80F8, 0x00)],
+        ];
+        for cycles in expected {
+            cpu.step();
+            assert_eq!(cpu.bus.take_cycles(), cycles);
+        }
+        assert_eq!(cpu.program_counter, 0x7FF8);
+    }
+
+    #[test]
+    fn test_read_modify_write_writes_twice() {
+        // INC $10, DCP $10 (illegal), the unmodified value is written back before the new one
+        let mut bus = RamBus::with_program(&[0xe6, 0x10, 0xc7, 0x10]);
+        bus.ram_mut()[0x10] = 0x05;
+        bus.record_cycles = true;
+        let mut cpu = CPU::with_bus(bus);
+        cpu.reset_interrupt();
+        cpu.bus.take_cycles();
+
+        cpu.step();
+        assert_eq!(cpu.bus.take_cycles()[2..], [
+            BusCycle::Read(0x10, 0x05),
+            BusCycle::Write(0x10, 0x05),
+            BusCycle::Write(0x10, 0x06),
+        ]);
+        cpu.step();
+        assert_eq!(cpu.bus.take_cycles()[2..], [
+            BusCycle::Read(0x10, 0x06),
+            BusCycle::Write(0x10, 0x06),
+            BusCycle::Write(0x10, 0x05),
+        ]);
+    }
+
+    #[test]
+    fn test_interrupts_from_the_bus() {
+        // CLI, NOP, NOP, with a NOP on the NMI ($9000) and IRQ ($A000) handlers
+        let mut bus = RamBus::with_program(&[0x58, 0xea, 0xea]);
+        bus.ram_mut()[0x9000] = 0xea;
+        bus.ram_mut()[0xA000] = 0xea;
+        bus.ram_mut()[0xFFFA..0xFFFC].copy_from_slice(&[0x00, 0x90]);
+        bus.ram_mut()[0xFFFE..0x10000].copy_from_slice(&[0x00, 0xA0]);
+        let mut cpu = CPU::with_bus(bus);
+        cpu.reset_interrupt();
+        cpu.step();
+
+        cpu.bus.irq = true;
+        let (_, cycles) = cpu.step();
+        assert_eq!(cycles, 7 + 2);
+        assert_eq!(cpu.program_counter, 0xA001);
+        cpu.bus.irq = false;
+
+        // the NMI ignores the I flag set by the IRQ
+        cpu.bus.nmi = true;
+        cpu.step();
+        assert_eq!(cpu.program_counter, 0x9001);
+    }
+
+    #[test]
+    fn test_oam_dma_stalls_the_cpu() {
+        // LDA #$AB, STA $0205, LDA #$02, STA $4014, NOP
+        let mapper = TestMapper::boxed(vec![0xa9, 0xab, 0x8d, 0x05, 0x02, 0xa9, 0x02, 0x8d, 0x14, 0x40, 0xea], Mirroring::Horizontal);
+        let mut cpu = CPU::new(mapper);
+        cpu.reset_interrupt();
+
+        for _ in 0..4 {
+            cpu.step();
+        }
+        let odd_cycle = cpu.bus.cycle_counter() % 2 == 1;
+        let (_, cycles) = cpu.step();
+
+        assert_eq!(cycles, 2 + if odd_cycle { 514 } else { 513 });
+        assert_eq!(cpu.bus.ppu.oam[5], 0xAB);
+    }
+
+    #[test]
+    fn test_open_bus() {
+        // LDA #$00, LDA $4000 (write only), the last value on the bus was the operand high byte
+        let mapper = TestMapper::boxed(vec![0xa9, 0x00, 0xad, 0x00, 0x40], Mirroring::Horizontal);
+        let mut cpu = CPU::new(mapper);
+
+        cpu.run_test();
+        assert_eq!(cpu.register_a, 0x40);
+    }
+
+    #[test]
+    fn test_indexed_dummy_read() {
+        // LDA #$01, STA $4016, LDY #$26, LDA $40F0,Y (dummy reads $4016), LDA $4016
+        let mapper = TestMapper::boxed(vec![
+            0xa9, 0x01, 0x8d, 0x16, 0x40, 0xa0, 0x26, 0xb9, 0xf0, 0x40, 0xad, 0x16, 0x40
+        ], Mirroring::Horizontal);
+        let mut cpu = CPU::new(mapper);
+        cpu.bus.joypad_1.set_button(JoyPadButtons::B, true);
+
+        cpu.run_test();
+        // the dummy read already shifted out A, so the second bit (B) is read
+        assert_eq!(cpu.register_a & 0x01, 1);
+    }
+
+    // ------------------- SAVE STATES ------------------
+    #[test]
+    fn test_save_state_roundtrip() {
+        // LDA #$42, STA $10, LDX #$07
+        let mapper = TestMapper::boxed(vec![0xa9, 0x42, 0x85, 0x10, 0xa2, 0x07], Mirroring::Horizontal);
+        let mut cpu = CPU::new(mapper);
+        cpu.run_test();
+
+        let mut w = StateWriter::new();
+        cpu.save_state(&mut w);
+        let state = w.into_bytes();
+
+        cpu.reset_interrupt();
+        cpu.register_y = 0x99;
+        cpu.bus.mem_write(0x10, 0x00);
+
+        let mut r = StateReader::new(&state).unwrap();
+        cpu.load_state(&mut r).unwrap();
+
+        assert_eq!(cpu.register_a, 0x42);
+        assert_eq!(cpu.register_x, 0x07);
+        assert_eq!(cpu.register_y, 0x00);
+        assert_eq!(cpu.bus.mem_read(0x10), 0x42);
+        assert_eq!(cpu.program_counter, 0x8006);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,153 @@
+/// Interrupt lines sampled by the CPU between instructions
+pub struct TickResult {
+    pub nmi: bool,
+    pub irq: bool,
+}
+
+/// Everything the 6502 core needs from the system it is plugged into
+///
+/// The CPU only talks to the rest of the machine through this trait, [`crate::memory::bus::BUS`]
+/// is the NES implementation and [`RamBus`] is a plain 64 KB of RAM to test the CPU on its own.
+///
+/// Every read and write is one CPU cycle, the implementation clocks whatever runs along the CPU
+/// (the PPU and the APU on the NES) before the access happens.
+pub trait CpuBus {
+    /// Read cycle
+    fn mem_read(&mut self, addr: u16) -> u8;
+
+    /// Write cycle
+    fn mem_write(&mut self, addr: u16, val: u8);
+
+    /// Reads without side effects, for the debuggers and the logs
+    fn peek(&self, addr: u16) -> u8;
+
+    /// Internal cycles of the CPU, the ones without a memory access
+    fn tick(&mut self, cycles: u8);
+
+    /// Takes the pending NMI (edge triggered) and samples the IRQ line (level triggered)
+    fn poll_interrupts(&mut self) -> TickResult;
+
+    /// Total of CPU cycles clocked through the bus
+    fn cycle_counter(&self) -> u64;
+
+    /// Runs the DMA transfers waiting for the CPU to be halted, returns how many cycles they stole
+    fn run_pending_dma(&mut self) -> u16 {
+        0
+    }
+
+    /// The reset button was pressed, for what's connected to the reset line besides the CPU
+    fn reset(&mut self) {}
+
+    fn mem_read_u16(&mut self, pos: u16) -> u16 {
+        let lo = self.mem_read(pos) as u16;
+        let hi = self.mem_read(pos.wrapping_add(1)) as u16;
+        (hi << 8) | lo
+    }
+
+    fn mem_write_u16(&mut self, pos: u16, data: u16) {
+        self.mem_write(pos, data as u8);
+        self.mem_write(pos.wrapping_add(1), (data >> 8) as u8);
+    }
+}
+
+/// One CPU cycle as seen from the bus, recorded by [`RamBus`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusCycle {
+    Read(u16, u8),
+    Write(u16, u8),
+    /// [`CpuBus::tick`], a cycle the CPU spends without going through the bus
+    /// (the real 6502 does a dummy read there, of an address that depends on the instruction)
+    Internal,
+}
+
+/// 64 KB of RAM and nothing else, the interrupt lines are driven by hand
+pub struct RamBus {
+    ram: Box<[u8]>,
+    cycle_counter: u64,
+    /// Every cycle is pushed to `cycles` while it's set
+    pub record_cycles: bool,
+    cycles: Vec<BusCycle>,
+    /// Set to send an NMI, it's taken by the CPU like the one of the PPU
+    pub nmi: bool,
+    pub irq: bool,
+}
+
+impl RamBus {
+    pub fn new() -> Self {
+        Self {
+            ram: vec![0; 0x10000].into_boxed_slice(),
+            cycle_counter: 0,
+            record_cycles: false,
+            cycles: Vec::new(),
+            nmi: false,
+            irq: false,
+        }
+    }
+
+    /// Loads `program` at $8000 and points the reset vector to it
+    pub fn with_program(program: &[u8]) -> Self {
+        let mut bus = Self::new();
+        bus.ram[0x8000..0x8000 + program.len()].copy_from_slice(program);
+        bus.ram[0xFFFC] = 0x00;
+        bus.ram[0xFFFD] = 0x80;
+        bus
+    }
+
+    pub fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    /// The cycles recorded since the last call (see `record_cycles`)
+    pub fn take_cycles(&mut self) -> Vec<BusCycle> {
+        std::mem::take(&mut self.cycles)
+    }
+
+    fn record(&mut self, cycle: BusCycle) {
+        if self.record_cycles {
+            self.cycles.push(cycle);
+        }
+    }
+}
+
+impl Default for RamBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CpuBus for RamBus {
+    fn mem_read(&mut self, addr: u16) -> u8 {
+        self.cycle_counter += 1;
+        let val = self.ram[addr as usize];
+        self.record(BusCycle::Read(addr, val));
+        val
+    }
+
+    fn mem_write(&mut self, addr: u16, val: u8) {
+        self.cycle_counter += 1;
+        self.ram[addr as usize] = val;
+        self.record(BusCycle::Write(addr, val));
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        self.ram[addr as usize]
+    }
+
+    fn tick(&mut self, cycles: u8) {
+        self.cycle_counter += cycles as u64;
+        for _ in 0..cycles {
+            self.record(BusCycle::Internal);
+        }
+    }
+
+    fn poll_interrupts(&mut self) -> TickResult {
+        TickResult {
+            nmi: std::mem::take(&mut self.nmi),
+            irq: self.irq,
+        }
+    }
+
+    fn cycle_counter(&self) -> u64 {
+        self.cycle_counter
+    }
+}
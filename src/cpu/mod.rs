@@ -1,2 +1,3 @@
 pub mod cpu;
+pub mod cpu_bus;
 mod opcodes;
\ No newline at end of file
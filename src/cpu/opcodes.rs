@@ -1,271 +1,430 @@
 use crate::cpu::cpu::AddressingMode;
-use std::{collections::HashMap, sync::{Arc, OnceLock}};
+use self::Instruction::*;
+
+/// What the CPU does for an opcode, the same instruction can have several opcodes (one per addressing mode)
+///
+/// The accumulator versions of the shifts, the two JMPs and the unofficial NOPs that read their operand
+/// have their own entry since they run different code. Unofficial opcodes use the names of
+/// https://www.nesdev.org/wiki/CPU_unofficial_opcodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    Adc, Ahx, Alr, Anc, And, Arr, Asl, AslAccumulator,
+    Axs, Bcc, Bcs, Beq, Bit, Bmi, Bne, Bpl,
+    Brk, Bvc, Bvs, Clc, Cld, Cli, Clv, Cmp,
+    Cpx, Cpy, Dcp, Dec, Dex, Dey, Eor, Inc,
+    Inx, Iny, Isb, Jam, JmpAbsolute, JmpIndirect, Jsr, Las,
+    Lax, Lda, Ldx, Ldy, Lsr, LsrAccumulator, Lxa, Nop,
+    NopRead, Ora, Pha, Php, Pla, Plp, Rla, Rol,
+    RolAccumulator, Ror, RorAccumulator, Rra, Rti, Rts, Sax, Sbc,
+    Sec, Sed, Sei, Shx, Shy, Slo, Sre, Sta,
+    Stx, Sty, Tas, Tax, Tay, Tsx, Txa, Txs,
+    Tya, Xaa,
+}
 
+#[derive(Clone, Copy)]
 #[allow(dead_code)]
 pub struct OpCode {
     pub code: u8,
     pub mnemonic: &'static str,
+    pub instruction: Instruction,
     pub len:u8,
     pub cycles: u8,
     pub mode: AddressingMode,
 }
 impl OpCode {
-    fn new(code: u8, mnemonic: &'static str, len: u8, cycles: u8, mode: AddressingMode) -> Self{
+    const fn new(code: u8, mnemonic: &'static str, instruction: Instruction, len: u8, cycles: u8, mode: AddressingMode) -> Self{
         OpCode {
-            code: code,
-            mnemonic: mnemonic,
-            len: len,
-            cycles: cycles,
-            mode: mode,
+            code,
+            mnemonic,
+            instruction,
+            len,
+            cycles,
+            mode,
         }
     }
 }
 
-pub static CPU_OPS_CODES: OnceLock<Arc<[OpCode]>> = OnceLock::new();
-pub static OPCODES_MAP: OnceLock<HashMap<u8, &'static OpCode>> = OnceLock::new();
-
-//lazy_static macro needed to inicialize values that need heap alocated memory as static
-//such as a vector of "opcode::new()"
-pub fn cpu_ops_codes() -> &'static Arc<[OpCode]> {
-    CPU_OPS_CODES.get_or_init(|| {
-        let opcodes = vec![
-            OpCode::new(0x00, "BRK", 1, 7, AddressingMode::NoneAddressing),
-            OpCode::new(0xaa, "TAX", 1, 2, AddressingMode::NoneAddressing),
-            OpCode::new(0xe8, "INX", 1, 2, AddressingMode::NoneAddressing),
-
-            OpCode::new(0xa9, "LDA", 2, 2, AddressingMode::Immediate),
-            OpCode::new(0xa5, "LDA", 2, 3, AddressingMode::ZeroPage),
-            OpCode::new(0xb5, "LDA", 2, 4, AddressingMode::ZeroPage_X),
-            OpCode::new(0xad, "LDA", 3, 4, AddressingMode::Absolute),
-            OpCode::new(0xbd, "LDA", 3, 4, AddressingMode::Absolute_X),     // +1 if page crossed
-            OpCode::new(0xb9, "LDA", 3, 4, AddressingMode::Absolute_Y),     // +1 if page crossed
-            OpCode::new(0xa1, "LDA", 2, 6, AddressingMode::Indirect_X),
-            OpCode::new(0xb1, "LDA", 2, 5, AddressingMode::Indirect_Y),     // +1 if page crossed
-
-            OpCode::new(0x85, "STA", 2, 3, AddressingMode::ZeroPage),
-            OpCode::new(0x95, "STA", 2, 4, AddressingMode::ZeroPage_X),
-            OpCode::new(0x8d, "STA", 3, 4, AddressingMode::Absolute),
-            OpCode::new(0x9d, "STA", 3, 5, AddressingMode::Absolute_X),
-            OpCode::new(0x99, "STA", 3, 5, AddressingMode::Absolute_Y),
-            OpCode::new(0x81, "STA", 2, 6, AddressingMode::Indirect_X),
-            OpCode::new(0x91, "STA", 2, 6, AddressingMode::Indirect_Y),
-
-            OpCode::new(0x69, "ADC", 2, 2, AddressingMode::Immediate),
-            OpCode::new(0x65, "ADC", 2, 3, AddressingMode::ZeroPage),
-            OpCode::new(0x75, "ADC", 2, 4, AddressingMode::ZeroPage_X),
-            OpCode::new(0x6D, "ADC", 3, 4, AddressingMode::Absolute),
-            OpCode::new(0x7D, "ADC", 3, 4, AddressingMode::Absolute_X), // +1 if page crossed
-            OpCode::new(0x79, "ADC", 3, 4, AddressingMode::Absolute_Y), // +1 if page crossed
-            OpCode::new(0x61, "ADC", 2, 6, AddressingMode::Indirect_X),
-            OpCode::new(0x71, "ADC", 2, 5, AddressingMode::Indirect_Y),
-
-            OpCode::new(0xB0, "BCS", 2, 2, AddressingMode::NoneAddressing), // +1 if page crossed
-            OpCode::new(0x90, "BCC", 2, 2, AddressingMode::NoneAddressing), // +1 if page crossed
-            OpCode::new(0xF0, "BEQ", 2, 2, AddressingMode::NoneAddressing),
-            OpCode::new(0x30, "BMI", 2, 2, AddressingMode::NoneAddressing), // +1 if page crossed
-            OpCode::new(0xD0, "BNE", 2, 2, AddressingMode::NoneAddressing), // +1 if page crossed
-            OpCode::new(0x10, "BPL", 2, 2, AddressingMode::NoneAddressing), // +1 if page crossed
-            OpCode::new(0x50, "BVC", 2, 2, AddressingMode::NoneAddressing), // +1 if page crossed
-            OpCode::new(0x70, "BVS", 2, 2, AddressingMode::NoneAddressing), // +1 if page crossed
-
-            OpCode::new(0x24, "BIT",2 , 3, AddressingMode::ZeroPage), 
-            OpCode::new(0x2c, "BIT",3 , 4, AddressingMode::Absolute), 
-            
-            // AND - Logical AND
-            OpCode::new(0x29, "AND", 2, 2, AddressingMode::Immediate),
-            OpCode::new(0x25, "AND", 2, 3, AddressingMode::ZeroPage),
-            OpCode::new(0x35, "AND", 2, 4, AddressingMode::ZeroPage_X),
-            OpCode::new(0x2D, "AND", 3, 4, AddressingMode::Absolute),
-            OpCode::new(0x3D, "AND", 3, 4, AddressingMode::Absolute_X), // +1 if page crossed
-            OpCode::new(0x39, "AND", 3, 4, AddressingMode::Absolute_Y), // +1 if page crossed
-            OpCode::new(0x21, "AND", 2, 6, AddressingMode::Indirect_X),
-            OpCode::new(0x31, "AND", 2, 5, AddressingMode::Indirect_Y), // +1 if page crossed
-
-            // ASL - Arithmetic Shift Left
-            OpCode::new(0x0A, "ASL", 1, 2, AddressingMode::NoneAddressing), // Accumulator
-            OpCode::new(0x06, "ASL", 2, 5, AddressingMode::ZeroPage),
-            OpCode::new(0x16, "ASL", 2, 6, AddressingMode::ZeroPage_X),
-            OpCode::new(0x0E, "ASL", 3, 6, AddressingMode::Absolute),
-            OpCode::new(0x1E, "ASL", 3, 7, AddressingMode::Absolute_X),
-
-            // CLC - Clear Carry Flag
-            OpCode::new(0x18, "CLC", 1, 2, AddressingMode::NoneAddressing),
-            // CLD - Clear Decimal Mode
-            OpCode::new(0xD8, "CLD", 1, 2, AddressingMode::NoneAddressing),
-            // CLI - Clear Interrupt Disable
-            OpCode::new(0x58, "CLI", 1, 2, AddressingMode::NoneAddressing),
-            // CLV - Clear Overflow Flag
-            OpCode::new(0xB8, "CLV", 1, 2, AddressingMode::NoneAddressing),
-
-            // CMP - Compare Accumulator
-            OpCode::new(0xC9, "CMP", 2, 2, AddressingMode::Immediate),
-            OpCode::new(0xC5, "CMP", 2, 3, AddressingMode::ZeroPage),
-            OpCode::new(0xD5, "CMP", 2, 4, AddressingMode::ZeroPage_X),
-            OpCode::new(0xCD, "CMP", 3, 4, AddressingMode::Absolute),
-            OpCode::new(0xDD, "CMP", 3, 4, AddressingMode::Absolute_X), // +1 if page crossed
-            OpCode::new(0xD9, "CMP", 3, 4, AddressingMode::Absolute_Y), // +1 if page crossed
-            OpCode::new(0xC1, "CMP", 2, 6, AddressingMode::Indirect_X),
-            OpCode::new(0xD1, "CMP", 2, 5, AddressingMode::Indirect_Y), // +1 if page crossed
-
-            // CPX - Compare X Register
-            OpCode::new(0xE0, "CPX", 2, 2, AddressingMode::Immediate),
-            OpCode::new(0xE4, "CPX", 2, 3, AddressingMode::ZeroPage),
-            OpCode::new(0xEC, "CPX", 3, 4, AddressingMode::Absolute),
-
-            // CPY - Compare Y Register
-            OpCode::new(0xC0, "CPY", 2, 2, AddressingMode::Immediate),
-            OpCode::new(0xC4, "CPY", 2, 3, AddressingMode::ZeroPage),
-            OpCode::new(0xCC, "CPY", 3, 4, AddressingMode::Absolute),
-
-            // DEC - Decrement Memory
-            OpCode::new(0xC6, "DEC", 2, 5, AddressingMode::ZeroPage),
-            OpCode::new(0xD6, "DEC", 2, 6, AddressingMode::ZeroPage_X),
-            OpCode::new(0xCE, "DEC", 3, 6, AddressingMode::Absolute),
-            OpCode::new(0xDE, "DEC", 3, 7, AddressingMode::Absolute_X),
-
-            // DEX - Decrement X Register
-            OpCode::new(0xCA, "DEX", 1, 2, AddressingMode::NoneAddressing),
-            // DEY - Decrement Y Register
-            OpCode::new(0x88, "DEY", 1, 2, AddressingMode::NoneAddressing),
-
-            // EOR - Exclusive OR
-            OpCode::new(0x49, "EOR", 2, 2, AddressingMode::Immediate),
-            OpCode::new(0x45, "EOR", 2, 3, AddressingMode::ZeroPage),
-            OpCode::new(0x55, "EOR", 2, 4, AddressingMode::ZeroPage_X),
-            OpCode::new(0x4D, "EOR", 3, 4, AddressingMode::Absolute),
-            OpCode::new(0x5D, "EOR", 3, 4, AddressingMode::Absolute_X), // +1 if page crossed
-            OpCode::new(0x59, "EOR", 3, 4, AddressingMode::Absolute_Y), // +1 if page crossed
-            OpCode::new(0x41, "EOR", 2, 6, AddressingMode::Indirect_X),
-            OpCode::new(0x51, "EOR", 2, 5, AddressingMode::Indirect_Y), // +1 if page crossed
-
-            // INC - Increment Memory
-            OpCode::new(0xE6, "INC", 2, 5, AddressingMode::ZeroPage),
-            OpCode::new(0xF6, "INC", 2, 6, AddressingMode::ZeroPage_X),
-            OpCode::new(0xEE, "INC", 3, 6, AddressingMode::Absolute),
-            OpCode::new(0xFE, "INC", 3, 7, AddressingMode::Absolute_X),
-
-            // INY - Increment Y Register
-            OpCode::new(0xC8, "INY", 1, 2, AddressingMode::NoneAddressing),
-
-            // JMP - Jump
-            OpCode::new(0x4C, "JMP", 3, 3, AddressingMode::Absolute),
-            OpCode::new(0x6C, "JMP", 3, 5, AddressingMode::NoneAddressing), //AddressingMode:Indirect with 6502 bug
-
-            // JSR - Jump to Subroutine
-            OpCode::new(0x20, "JSR", 3, 6, AddressingMode::Absolute),
-
-            // LDX - Load X Register
-            OpCode::new(0xA2, "LDX", 2, 2, AddressingMode::Immediate),
-            OpCode::new(0xA6, "LDX", 2, 3, AddressingMode::ZeroPage),
-            OpCode::new(0xB6, "LDX", 2, 4, AddressingMode::ZeroPage_Y),
-            OpCode::new(0xAE, "LDX", 3, 4, AddressingMode::Absolute),
-            OpCode::new(0xBE, "LDX", 3, 4, AddressingMode::Absolute_Y), // +1 if page crossed
-
-            // LDY - Load Y Register
-            OpCode::new(0xA0, "LDY", 2, 2, AddressingMode::Immediate),
-            OpCode::new(0xA4, "LDY", 2, 3, AddressingMode::ZeroPage),
-            OpCode::new(0xB4, "LDY", 2, 4, AddressingMode::ZeroPage_X),
-            OpCode::new(0xAC, "LDY", 3, 4, AddressingMode::Absolute),
-            OpCode::new(0xBC, "LDY", 3, 4, AddressingMode::Absolute_X), // +1 if page crossed
-
-            // LSR - Logical Shift Right
-            OpCode::new(0x4A, "LSR", 1, 2, AddressingMode::NoneAddressing), // Accumulator
-            OpCode::new(0x46, "LSR", 2, 5, AddressingMode::ZeroPage),
-            OpCode::new(0x56, "LSR", 2, 6, AddressingMode::ZeroPage_X),
-            OpCode::new(0x4E, "LSR", 3, 6, AddressingMode::Absolute),
-            OpCode::new(0x5E, "LSR", 3, 7, AddressingMode::Absolute_X),
-
-            // NOP - No Operation
-            OpCode::new(0xEA, "NOP", 1, 2, AddressingMode::NoneAddressing),
-
-            // ORA - Logical Inclusive OR
-            OpCode::new(0x09, "ORA", 2, 2, AddressingMode::Immediate),
-            OpCode::new(0x05, "ORA", 2, 3, AddressingMode::ZeroPage),
-            OpCode::new(0x15, "ORA", 2, 4, AddressingMode::ZeroPage_X),
-            OpCode::new(0x0D, "ORA", 3, 4, AddressingMode::Absolute),
-            OpCode::new(0x1D, "ORA", 3, 4, AddressingMode::Absolute_X), // +1 if page crossed
-            OpCode::new(0x19, "ORA", 3, 4, AddressingMode::Absolute_Y), // +1 if page crossed
-            OpCode::new(0x01, "ORA", 2, 6, AddressingMode::Indirect_X),
-            OpCode::new(0x11, "ORA", 2, 5, AddressingMode::Indirect_Y), // +1 if page crossed
-
-            // PHA - Push Accumulator
-            OpCode::new(0x48, "PHA", 1, 3, AddressingMode::NoneAddressing),
-            // PHP - Push Processor Status
-            OpCode::new(0x08, "PHP", 1, 3, AddressingMode::NoneAddressing),
-
-            // PLA - Pull Accumulator
-            OpCode::new(0x68, "PLA", 1, 4, AddressingMode::NoneAddressing),
-            // PLP - Pull Processor Status
-            OpCode::new(0x28, "PLP", 1, 4, AddressingMode::NoneAddressing),
-
-            // ROL - Rotate Left
-            OpCode::new(0x2A, "ROL", 1, 2, AddressingMode::NoneAddressing), // Accumulator
-            OpCode::new(0x26, "ROL", 2, 5, AddressingMode::ZeroPage),
-            OpCode::new(0x36, "ROL", 2, 6, AddressingMode::ZeroPage_X),
-            OpCode::new(0x2E, "ROL", 3, 6, AddressingMode::Absolute),
-            OpCode::new(0x3E, "ROL", 3, 7, AddressingMode::Absolute_X),
-
-            // ROR - Rotate Right
-            OpCode::new(0x6A, "ROR", 1, 2, AddressingMode::NoneAddressing), // Accumulator
-            OpCode::new(0x66, "ROR", 2, 5, AddressingMode::ZeroPage),
-            OpCode::new(0x76, "ROR", 2, 6, AddressingMode::ZeroPage_X),
-            OpCode::new(0x6E, "ROR", 3, 6, AddressingMode::Absolute),
-            OpCode::new(0x7E, "ROR", 3, 7, AddressingMode::Absolute_X),
-
-            // RTI - Return from Interrupt
-            OpCode::new(0x40, "RTI", 1, 6, AddressingMode::NoneAddressing),
-            // RTS - Return from Subroutine
-            OpCode::new(0x60, "RTS", 1, 6, AddressingMode::NoneAddressing),
-
-            // SBC - Subtract with Carry
-            OpCode::new(0xE9, "SBC", 2, 2, AddressingMode::Immediate),
-            OpCode::new(0xE5, "SBC", 2, 3, AddressingMode::ZeroPage),
-            OpCode::new(0xF5, "SBC", 2, 4, AddressingMode::ZeroPage_X),
-            OpCode::new(0xED, "SBC", 3, 4, AddressingMode::Absolute),
-            OpCode::new(0xFD, "SBC", 3, 4, AddressingMode::Absolute_X), // +1 if page crossed
-            OpCode::new(0xF9, "SBC", 3, 4, AddressingMode::Absolute_Y), // +1 if page crossed
-            OpCode::new(0xE1, "SBC", 2, 6, AddressingMode::Indirect_X),
-            OpCode::new(0xF1, "SBC", 2, 5, AddressingMode::Indirect_Y), // +1 if page crossed
-
-            // SEC - Set Carry Flag
-            OpCode::new(0x38, "SEC", 1, 2, AddressingMode::NoneAddressing),
-            // SED - Set Decimal Flag
-            OpCode::new(0xF8, "SED", 1, 2, AddressingMode::NoneAddressing),
-            // SEI - Set Interrupt Disable
-            OpCode::new(0x78, "SEI", 1, 2, AddressingMode::NoneAddressing),
-
-            // STX - Store X Register
-            OpCode::new(0x86, "STX", 2, 3, AddressingMode::ZeroPage),
-            OpCode::new(0x96, "STX", 2, 4, AddressingMode::ZeroPage_Y),
-            OpCode::new(0x8E, "STX", 3, 4, AddressingMode::Absolute),
-
-            // STY - Store Y Register
-            OpCode::new(0x84, "STY", 2, 3, AddressingMode::ZeroPage),
-            OpCode::new(0x94, "STY", 2, 4, AddressingMode::ZeroPage_X),
-            OpCode::new(0x8C, "STY", 3, 4, AddressingMode::Absolute),
-
-            // TXA - Transfer X to Accumulator (já tinha TAX)
-            OpCode::new(0x8A, "TXA", 1, 2, AddressingMode::NoneAddressing),
-            // TSX - Transfer Stack Pointer to X
-            OpCode::new(0xBA, "TSX", 1, 2, AddressingMode::NoneAddressing),
-            // TXS - Transfer X to Stack Pointer
-            OpCode::new(0x9A, "TXS", 1, 2, AddressingMode::NoneAddressing),
-            // TYA - Transfer Y to Accumulator
-            OpCode::new(0x98, "TYA", 1, 2, AddressingMode::NoneAddressing),
-            // TAY - Transfer Accumulator to Y
-            OpCode::new(0xA8, "TAY", 1, 2, AddressingMode::NoneAddressing),
-
-        ];
-        opcodes.into_boxed_slice().into()
-    })
+/// Decoding table of the CPU, indexed by the opcode
+///
+/// Built at compile time from [`OPCODE_LIST`] (grouped by instruction, easier to read), the build fails
+/// if an opcode is missing or defined twice.
+pub static OPCODES: [OpCode; 256] = index_by_code(OPCODE_LIST);
+
+const fn index_by_code(list: [OpCode; 256]) -> [OpCode; 256] {
+    let mut table = list;
+    let mut defined = [false; 256];
+
+    let mut i = 0;
+    while i < list.len() {
+        let code = list[i].code as usize;
+        assert!(!defined[code], "an opcode is defined twice");
+        defined[code] = true;
+        table[code] = list[i];
+        i += 1;
+    }
+    table
 }
 
-pub fn opcodes_map() -> &'static HashMap<u8, &'static OpCode> {
-    OPCODES_MAP.get_or_init(|| {
-        let mut map = HashMap::new();
-        for cpline in cpu_ops_codes().iter() {
-            map.insert(cpline.code, cpline);
-        }
-        map
-    })
-}
\ No newline at end of file
+const OPCODE_LIST: [OpCode; 256] = [
+    OpCode::new(0x00, "BRK", Brk, 1, 7, AddressingMode::NoneAddressing),
+    OpCode::new(0xaa, "TAX", Tax, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0xe8, "INX", Inx, 1, 2, AddressingMode::NoneAddressing),
+
+    OpCode::new(0xa9, "LDA", Lda, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0xa5, "LDA", Lda, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0xb5, "LDA", Lda, 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0xad, "LDA", Lda, 3, 4, AddressingMode::Absolute),
+    OpCode::new(0xbd, "LDA", Lda, 3, 4, AddressingMode::Absolute_X),     // +1 if page crossed
+    OpCode::new(0xb9, "LDA", Lda, 3, 4, AddressingMode::Absolute_Y),     // +1 if page crossed
+    OpCode::new(0xa1, "LDA", Lda, 2, 6, AddressingMode::Indirect_X),
+    OpCode::new(0xb1, "LDA", Lda, 2, 5, AddressingMode::Indirect_Y),     // +1 if page crossed
+
+    OpCode::new(0x85, "STA", Sta, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x95, "STA", Sta, 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0x8d, "STA", Sta, 3, 4, AddressingMode::Absolute),
+    OpCode::new(0x9d, "STA", Sta, 3, 5, AddressingMode::Absolute_X),
+    OpCode::new(0x99, "STA", Sta, 3, 5, AddressingMode::Absolute_Y),
+    OpCode::new(0x81, "STA", Sta, 2, 6, AddressingMode::Indirect_X),
+    OpCode::new(0x91, "STA", Sta, 2, 6, AddressingMode::Indirect_Y),
+
+    OpCode::new(0x69, "ADC", Adc, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0x65, "ADC", Adc, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x75, "ADC", Adc, 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0x6D, "ADC", Adc, 3, 4, AddressingMode::Absolute),
+    OpCode::new(0x7D, "ADC", Adc, 3, 4, AddressingMode::Absolute_X), // +1 if page crossed
+    OpCode::new(0x79, "ADC", Adc, 3, 4, AddressingMode::Absolute_Y), // +1 if page crossed
+    OpCode::new(0x61, "ADC", Adc, 2, 6, AddressingMode::Indirect_X),
+    OpCode::new(0x71, "ADC", Adc, 2, 5, AddressingMode::Indirect_Y),
+
+    OpCode::new(0xB0, "BCS", Bcs, 2, 2, AddressingMode::NoneAddressing), // +1 if page crossed
+    OpCode::new(0x90, "BCC", Bcc, 2, 2, AddressingMode::NoneAddressing), // +1 if page crossed
+    OpCode::new(0xF0, "BEQ", Beq, 2, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0x30, "BMI", Bmi, 2, 2, AddressingMode::NoneAddressing), // +1 if page crossed
+    OpCode::new(0xD0, "BNE", Bne, 2, 2, AddressingMode::NoneAddressing), // +1 if page crossed
+    OpCode::new(0x10, "BPL", Bpl, 2, 2, AddressingMode::NoneAddressing), // +1 if page crossed
+    OpCode::new(0x50, "BVC", Bvc, 2, 2, AddressingMode::NoneAddressing), // +1 if page crossed
+    OpCode::new(0x70, "BVS", Bvs, 2, 2, AddressingMode::NoneAddressing), // +1 if page crossed
+
+    OpCode::new(0x24, "BIT", Bit,2 , 3, AddressingMode::ZeroPage), 
+    OpCode::new(0x2c, "BIT", Bit,3 , 4, AddressingMode::Absolute), 
+    
+    // AND - Logical AND
+    OpCode::new(0x29, "AND", And, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0x25, "AND", And, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x35, "AND", And, 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0x2D, "AND", And, 3, 4, AddressingMode::Absolute),
+    OpCode::new(0x3D, "AND", And, 3, 4, AddressingMode::Absolute_X), // +1 if page crossed
+    OpCode::new(0x39, "AND", And, 3, 4, AddressingMode::Absolute_Y), // +1 if page crossed
+    OpCode::new(0x21, "AND", And, 2, 6, AddressingMode::Indirect_X),
+    OpCode::new(0x31, "AND", And, 2, 5, AddressingMode::Indirect_Y), // +1 if page crossed
+
+    // ASL - Arithmetic Shift Left
+    OpCode::new(0x0A, "ASL", AslAccumulator, 1, 2, AddressingMode::NoneAddressing), // Accumulator
+    OpCode::new(0x06, "ASL", Asl, 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0x16, "ASL", Asl, 2, 6, AddressingMode::ZeroPage_X),
+    OpCode::new(0x0E, "ASL", Asl, 3, 6, AddressingMode::Absolute),
+    OpCode::new(0x1E, "ASL", Asl, 3, 7, AddressingMode::Absolute_X),
+
+    // CLC - Clear Carry Flag
+    OpCode::new(0x18, "CLC", Clc, 1, 2, AddressingMode::NoneAddressing),
+    // CLD - Clear Decimal Mode
+    OpCode::new(0xD8, "CLD", Cld, 1, 2, AddressingMode::NoneAddressing),
+    // CLI - Clear Interrupt Disable
+    OpCode::new(0x58, "CLI", Cli, 1, 2, AddressingMode::NoneAddressing),
+    // CLV - Clear Overflow Flag
+    OpCode::new(0xB8, "CLV", Clv, 1, 2, AddressingMode::NoneAddressing),
+
+    // CMP - Compare Accumulator
+    OpCode::new(0xC9, "CMP", Cmp, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0xC5, "CMP", Cmp, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0xD5, "CMP", Cmp, 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0xCD, "CMP", Cmp, 3, 4, AddressingMode::Absolute),
+    OpCode::new(0xDD, "CMP", Cmp, 3, 4, AddressingMode::Absolute_X), // +1 if page crossed
+    OpCode::new(0xD9, "CMP", Cmp, 3, 4, AddressingMode::Absolute_Y), // +1 if page crossed
+    OpCode::new(0xC1, "CMP", Cmp, 2, 6, AddressingMode::Indirect_X),
+    OpCode::new(0xD1, "CMP", Cmp, 2, 5, AddressingMode::Indirect_Y), // +1 if page crossed
+
+    // CPX - Compare X Register
+    OpCode::new(0xE0, "CPX", Cpx, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0xE4, "CPX", Cpx, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0xEC, "CPX", Cpx, 3, 4, AddressingMode::Absolute),
+
+    // CPY - Compare Y Register
+    OpCode::new(0xC0, "CPY", Cpy, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0xC4, "CPY", Cpy, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0xCC, "CPY", Cpy, 3, 4, AddressingMode::Absolute),
+
+    // DEC - Decrement Memory
+    OpCode::new(0xC6, "DEC", Dec, 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0xD6, "DEC", Dec, 2, 6, AddressingMode::ZeroPage_X),
+    OpCode::new(0xCE, "DEC", Dec, 3, 6, AddressingMode::Absolute),
+    OpCode::new(0xDE, "DEC", Dec, 3, 7, AddressingMode::Absolute_X),
+
+    // DEX - Decrement X Register
+    OpCode::new(0xCA, "DEX", Dex, 1, 2, AddressingMode::NoneAddressing),
+    // DEY - Decrement Y Register
+    OpCode::new(0x88, "DEY", Dey, 1, 2, AddressingMode::NoneAddressing),
+
+    // EOR - Exclusive OR
+    OpCode::new(0x49, "EOR", Eor, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0x45, "EOR", Eor, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x55, "EOR", Eor, 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0x4D, "EOR", Eor, 3, 4, AddressingMode::Absolute),
+    OpCode::new(0x5D, "EOR", Eor, 3, 4, AddressingMode::Absolute_X), // +1 if page crossed
+    OpCode::new(0x59, "EOR", Eor, 3, 4, AddressingMode::Absolute_Y), // +1 if page crossed
+    OpCode::new(0x41, "EOR", Eor, 2, 6, AddressingMode::Indirect_X),
+    OpCode::new(0x51, "EOR", Eor, 2, 5, AddressingMode::Indirect_Y), // +1 if page crossed
+
+    // INC - Increment Memory
+    OpCode::new(0xE6, "INC", Inc, 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0xF6, "INC", Inc, 2, 6, AddressingMode::ZeroPage_X),
+    OpCode::new(0xEE, "INC", Inc, 3, 6, AddressingMode::Absolute),
+    OpCode::new(0xFE, "INC", Inc, 3, 7, AddressingMode::Absolute_X),
+
+    // INY - Increment Y Register
+    OpCode::new(0xC8, "INY", Iny, 1, 2, AddressingMode::NoneAddressing),
+
+    // JMP - Jump
+    OpCode::new(0x4C, "JMP", JmpAbsolute, 3, 3, AddressingMode::Absolute),
+    OpCode::new(0x6C, "JMP", JmpIndirect, 3, 5, AddressingMode::NoneAddressing), //AddressingMode:Indirect with 6502 bug
+
+    // JSR - Jump to Subroutine
+    OpCode::new(0x20, "JSR", Jsr, 3, 6, AddressingMode::Absolute),
+
+    // LDX - Load X Register
+    OpCode::new(0xA2, "LDX", Ldx, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0xA6, "LDX", Ldx, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0xB6, "LDX", Ldx, 2, 4, AddressingMode::ZeroPage_Y),
+    OpCode::new(0xAE, "LDX", Ldx, 3, 4, AddressingMode::Absolute),
+    OpCode::new(0xBE, "LDX", Ldx, 3, 4, AddressingMode::Absolute_Y), // +1 if page crossed
+
+    // LDY - Load Y Register
+    OpCode::new(0xA0, "LDY", Ldy, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0xA4, "LDY", Ldy, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0xB4, "LDY", Ldy, 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0xAC, "LDY", Ldy, 3, 4, AddressingMode::Absolute),
+    OpCode::new(0xBC, "LDY", Ldy, 3, 4, AddressingMode::Absolute_X), // +1 if page crossed
+
+    // LSR - Logical Shift Right
+    OpCode::new(0x4A, "LSR", LsrAccumulator, 1, 2, AddressingMode::NoneAddressing), // Accumulator
+    OpCode::new(0x46, "LSR", Lsr, 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0x56, "LSR", Lsr, 2, 6, AddressingMode::ZeroPage_X),
+    OpCode::new(0x4E, "LSR", Lsr, 3, 6, AddressingMode::Absolute),
+    OpCode::new(0x5E, "LSR", Lsr, 3, 7, AddressingMode::Absolute_X),
+
+    // NOP - No Operation
+    OpCode::new(0xEA, "NOP", Nop, 1, 2, AddressingMode::NoneAddressing),
+
+    // ORA - Logical Inclusive OR
+    OpCode::new(0x09, "ORA", Ora, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0x05, "ORA", Ora, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x15, "ORA", Ora, 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0x0D, "ORA", Ora, 3, 4, AddressingMode::Absolute),
+    OpCode::new(0x1D, "ORA", Ora, 3, 4, AddressingMode::Absolute_X), // +1 if page crossed
+    OpCode::new(0x19, "ORA", Ora, 3, 4, AddressingMode::Absolute_Y), // +1 if page crossed
+    OpCode::new(0x01, "ORA", Ora, 2, 6, AddressingMode::Indirect_X),
+    OpCode::new(0x11, "ORA", Ora, 2, 5, AddressingMode::Indirect_Y), // +1 if page crossed
+
+    // PHA - Push Accumulator
+    OpCode::new(0x48, "PHA", Pha, 1, 3, AddressingMode::NoneAddressing),
+    // PHP - Push Processor Status
+    OpCode::new(0x08, "PHP", Php, 1, 3, AddressingMode::NoneAddressing),
+
+    // PLA - Pull Accumulator
+    OpCode::new(0x68, "PLA", Pla, 1, 4, AddressingMode::NoneAddressing),
+    // PLP - Pull Processor Status
+    OpCode::new(0x28, "PLP", Plp, 1, 4, AddressingMode::NoneAddressing),
+
+    // ROL - Rotate Left
+    OpCode::new(0x2A, "ROL", RolAccumulator, 1, 2, AddressingMode::NoneAddressing), // Accumulator
+    OpCode::new(0x26, "ROL", Rol, 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0x36, "ROL", Rol, 2, 6, AddressingMode::ZeroPage_X),
+    OpCode::new(0x2E, "ROL", Rol, 3, 6, AddressingMode::Absolute),
+    OpCode::new(0x3E, "ROL", Rol, 3, 7, AddressingMode::Absolute_X),
+
+    // ROR - Rotate Right
+    OpCode::new(0x6A, "ROR", RorAccumulator, 1, 2, AddressingMode::NoneAddressing), // Accumulator
+    OpCode::new(0x66, "ROR", Ror, 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0x76, "ROR", Ror, 2, 6, AddressingMode::ZeroPage_X),
+    OpCode::new(0x6E, "ROR", Ror, 3, 6, AddressingMode::Absolute),
+    OpCode::new(0x7E, "ROR", Ror, 3, 7, AddressingMode::Absolute_X),
+
+    // RTI - Return from Interrupt
+    OpCode::new(0x40, "RTI", Rti, 1, 6, AddressingMode::NoneAddressing),
+    // RTS - Return from Subroutine
+    OpCode::new(0x60, "RTS", Rts, 1, 6, AddressingMode::NoneAddressing),
+
+    // SBC - Subtract with Carry
+    OpCode::new(0xE9, "SBC", Sbc, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0xE5, "SBC", Sbc, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0xF5, "SBC", Sbc, 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0xED, "SBC", Sbc, 3, 4, AddressingMode::Absolute),
+    OpCode::new(0xFD, "SBC", Sbc, 3, 4, AddressingMode::Absolute_X), // +1 if page crossed
+    OpCode::new(0xF9, "SBC", Sbc, 3, 4, AddressingMode::Absolute_Y), // +1 if page crossed
+    OpCode::new(0xE1, "SBC", Sbc, 2, 6, AddressingMode::Indirect_X),
+    OpCode::new(0xF1, "SBC", Sbc, 2, 5, AddressingMode::Indirect_Y), // +1 if page crossed
+
+    // SEC - Set Carry Flag
+    OpCode::new(0x38, "SEC", Sec, 1, 2, AddressingMode::NoneAddressing),
+    // SED - Set Decimal Flag
+    OpCode::new(0xF8, "SED", Sed, 1, 2, AddressingMode::NoneAddressing),
+    // SEI - Set Interrupt Disable
+    OpCode::new(0x78, "SEI", Sei, 1, 2, AddressingMode::NoneAddressing),
+
+    // STX - Store X Register
+    OpCode::new(0x86, "STX", Stx, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x96, "STX", Stx, 2, 4, AddressingMode::ZeroPage_Y),
+    OpCode::new(0x8E, "STX", Stx, 3, 4, AddressingMode::Absolute),
+
+    // STY - Store Y Register
+    OpCode::new(0x84, "STY", Sty, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x94, "STY", Sty, 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0x8C, "STY", Sty, 3, 4, AddressingMode::Absolute),
+
+    // TXA - Transfer X to Accumulator (já tinha TAX)
+    OpCode::new(0x8A, "TXA", Txa, 1, 2, AddressingMode::NoneAddressing),
+    // TSX - Transfer Stack Pointer to X
+    OpCode::new(0xBA, "TSX", Tsx, 1, 2, AddressingMode::NoneAddressing),
+    // TXS - Transfer X to Stack Pointer
+    OpCode::new(0x9A, "TXS", Txs, 1, 2, AddressingMode::NoneAddressing),
+    // TYA - Transfer Y to Accumulator
+    OpCode::new(0x98, "TYA", Tya, 1, 2, AddressingMode::NoneAddressing),
+    // TAY - Transfer Accumulator to Y
+    OpCode::new(0xA8, "TAY", Tay, 1, 2, AddressingMode::NoneAddressing),
+
+    // ------------------------ UNOFFICIAL OPCODES ------------------------
+    // https://www.nesdev.org/wiki/CPU_unofficial_opcodes
+    // (marked with '*' just like the nestest.log does)
+
+    // NOP - implied, immediate, zero page and absolute variants (the last ones still read the operand)
+    OpCode::new(0x1A, "*NOP", Nop, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0x3A, "*NOP", Nop, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0x5A, "*NOP", Nop, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0x7A, "*NOP", Nop, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0xDA, "*NOP", Nop, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0xFA, "*NOP", Nop, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0x80, "*NOP", NopRead, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0x82, "*NOP", NopRead, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0x89, "*NOP", NopRead, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0xC2, "*NOP", NopRead, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0xE2, "*NOP", NopRead, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0x04, "*NOP", NopRead, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x44, "*NOP", NopRead, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x64, "*NOP", NopRead, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x14, "*NOP", NopRead, 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0x34, "*NOP", NopRead, 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0x54, "*NOP", NopRead, 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0x74, "*NOP", NopRead, 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0xD4, "*NOP", NopRead, 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0xF4, "*NOP", NopRead, 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0x0C, "*NOP", NopRead, 3, 4, AddressingMode::Absolute),
+    OpCode::new(0x1C, "*NOP", NopRead, 3, 4, AddressingMode::Absolute_X), // +1 if page crossed
+    OpCode::new(0x3C, "*NOP", NopRead, 3, 4, AddressingMode::Absolute_X), // +1 if page crossed
+    OpCode::new(0x5C, "*NOP", NopRead, 3, 4, AddressingMode::Absolute_X), // +1 if page crossed
+    OpCode::new(0x7C, "*NOP", NopRead, 3, 4, AddressingMode::Absolute_X), // +1 if page crossed
+    OpCode::new(0xDC, "*NOP", NopRead, 3, 4, AddressingMode::Absolute_X), // +1 if page crossed
+    OpCode::new(0xFC, "*NOP", NopRead, 3, 4, AddressingMode::Absolute_X), // +1 if page crossed
+
+    // LAX - LDA + LDX
+    OpCode::new(0xA7, "*LAX", Lax, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0xB7, "*LAX", Lax, 2, 4, AddressingMode::ZeroPage_Y),
+    OpCode::new(0xAF, "*LAX", Lax, 3, 4, AddressingMode::Absolute),
+    OpCode::new(0xBF, "*LAX", Lax, 3, 4, AddressingMode::Absolute_Y), // +1 if page crossed
+    OpCode::new(0xA3, "*LAX", Lax, 2, 6, AddressingMode::Indirect_X),
+    OpCode::new(0xB3, "*LAX", Lax, 2, 5, AddressingMode::Indirect_Y), // +1 if page crossed
+
+    // SAX - stores A & X
+    OpCode::new(0x87, "*SAX", Sax, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x97, "*SAX", Sax, 2, 4, AddressingMode::ZeroPage_Y),
+    OpCode::new(0x8F, "*SAX", Sax, 3, 4, AddressingMode::Absolute),
+    OpCode::new(0x83, "*SAX", Sax, 2, 6, AddressingMode::Indirect_X),
+
+    // SBC - same as the official $E9
+    OpCode::new(0xEB, "*SBC", Sbc, 2, 2, AddressingMode::Immediate),
+
+    // DCP - DEC + CMP
+    OpCode::new(0xC7, "*DCP", Dcp, 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0xD7, "*DCP", Dcp, 2, 6, AddressingMode::ZeroPage_X),
+    OpCode::new(0xCF, "*DCP", Dcp, 3, 6, AddressingMode::Absolute),
+    OpCode::new(0xDF, "*DCP", Dcp, 3, 7, AddressingMode::Absolute_X),
+    OpCode::new(0xDB, "*DCP", Dcp, 3, 7, AddressingMode::Absolute_Y),
+    OpCode::new(0xC3, "*DCP", Dcp, 2, 8, AddressingMode::Indirect_X),
+    OpCode::new(0xD3, "*DCP", Dcp, 2, 8, AddressingMode::Indirect_Y),
+
+    // ISC (ISB) - INC + SBC
+    OpCode::new(0xE7, "*ISB", Isb, 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0xF7, "*ISB", Isb, 2, 6, AddressingMode::ZeroPage_X),
+    OpCode::new(0xEF, "*ISB", Isb, 3, 6, AddressingMode::Absolute),
+    OpCode::new(0xFF, "*ISB", Isb, 3, 7, AddressingMode::Absolute_X),
+    OpCode::new(0xFB, "*ISB", Isb, 3, 7, AddressingMode::Absolute_Y),
+    OpCode::new(0xE3, "*ISB", Isb, 2, 8, AddressingMode::Indirect_X),
+    OpCode::new(0xF3, "*ISB", Isb, 2, 8, AddressingMode::Indirect_Y),
+
+    // SLO - ASL + ORA
+    OpCode::new(0x07, "*SLO", Slo, 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0x17, "*SLO", Slo, 2, 6, AddressingMode::ZeroPage_X),
+    OpCode::new(0x0F, "*SLO", Slo, 3, 6, AddressingMode::Absolute),
+    OpCode::new(0x1F, "*SLO", Slo, 3, 7, AddressingMode::Absolute_X),
+    OpCode::new(0x1B, "*SLO", Slo, 3, 7, AddressingMode::Absolute_Y),
+    OpCode::new(0x03, "*SLO", Slo, 2, 8, AddressingMode::Indirect_X),
+    OpCode::new(0x13, "*SLO", Slo, 2, 8, AddressingMode::Indirect_Y),
+
+    // RLA - ROL + AND
+    OpCode::new(0x27, "*RLA", Rla, 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0x37, "*RLA", Rla, 2, 6, AddressingMode::ZeroPage_X),
+    OpCode::new(0x2F, "*RLA", Rla, 3, 6, AddressingMode::Absolute),
+    OpCode::new(0x3F, "*RLA", Rla, 3, 7, AddressingMode::Absolute_X),
+    OpCode::new(0x3B, "*RLA", Rla, 3, 7, AddressingMode::Absolute_Y),
+    OpCode::new(0x23, "*RLA", Rla, 2, 8, AddressingMode::Indirect_X),
+    OpCode::new(0x33, "*RLA", Rla, 2, 8, AddressingMode::Indirect_Y),
+
+    // SRE - LSR + EOR
+    OpCode::new(0x47, "*SRE", Sre, 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0x57, "*SRE", Sre, 2, 6, AddressingMode::ZeroPage_X),
+    OpCode::new(0x4F, "*SRE", Sre, 3, 6, AddressingMode::Absolute),
+    OpCode::new(0x5F, "*SRE", Sre, 3, 7, AddressingMode::Absolute_X),
+    OpCode::new(0x5B, "*SRE", Sre, 3, 7, AddressingMode::Absolute_Y),
+    OpCode::new(0x43, "*SRE", Sre, 2, 8, AddressingMode::Indirect_X),
+    OpCode::new(0x53, "*SRE", Sre, 2, 8, AddressingMode::Indirect_Y),
+
+    // RRA - ROR + ADC
+    OpCode::new(0x67, "*RRA", Rra, 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0x77, "*RRA", Rra, 2, 6, AddressingMode::ZeroPage_X),
+    OpCode::new(0x6F, "*RRA", Rra, 3, 6, AddressingMode::Absolute),
+    OpCode::new(0x7F, "*RRA", Rra, 3, 7, AddressingMode::Absolute_X),
+    OpCode::new(0x7B, "*RRA", Rra, 3, 7, AddressingMode::Absolute_Y),
+    OpCode::new(0x63, "*RRA", Rra, 2, 8, AddressingMode::Indirect_X),
+    OpCode::new(0x73, "*RRA", Rra, 2, 8, AddressingMode::Indirect_Y),
+
+    // immediate only combined operations
+    OpCode::new(0x0B, "*ANC", Anc, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0x2B, "*ANC", Anc, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0x4B, "*ALR", Alr, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0x6B, "*ARR", Arr, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0xCB, "*AXS", Axs, 2, 2, AddressingMode::Immediate),
+
+    // "unstable" opcodes, their result depends on the chip (and even on temperature),
+    // these follow the behavior most emulators agree on
+    OpCode::new(0x8B, "*XAA", Xaa, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0xAB, "*LXA", Lxa, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0x9F, "*AHX", Ahx, 3, 5, AddressingMode::Absolute_Y),
+    OpCode::new(0x93, "*AHX", Ahx, 2, 6, AddressingMode::Indirect_Y),
+    OpCode::new(0x9C, "*SHY", Shy, 3, 5, AddressingMode::Absolute_X),
+    OpCode::new(0x9E, "*SHX", Shx, 3, 5, AddressingMode::Absolute_Y),
+    OpCode::new(0x9B, "*TAS", Tas, 3, 5, AddressingMode::Absolute_Y),
+    OpCode::new(0xBB, "*LAS", Las, 3, 4, AddressingMode::Absolute_Y), // +1 if page crossed
+
+    // JAM (KIL) - locks the CPU until a reset
+    OpCode::new(0x02, "*JAM", Jam, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0x12, "*JAM", Jam, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0x22, "*JAM", Jam, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0x32, "*JAM", Jam, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0x42, "*JAM", Jam, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0x52, "*JAM", Jam, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0x62, "*JAM", Jam, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0x72, "*JAM", Jam, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0x92, "*JAM", Jam, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0xB2, "*JAM", Jam, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0xD2, "*JAM", Jam, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0xF2, "*JAM", Jam, 1, 2, AddressingMode::NoneAddressing),
+];
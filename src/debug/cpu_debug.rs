@@ -127,9 +127,9 @@ pub fn log_state_nestest(
         let pc = cpu.program_counter;
         
         let line = format!(
-            "{:04X} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X}",
+            "{:04X} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CYC:{}",
             pc, cpu.register_a, cpu.register_x, cpu.register_y, 
-            cpu.status.bits(), cpu.stack_pointer
+            cpu.status.bits(), cpu.stack_pointer, cpu.cycles
         );
 
         if guard.buffer.len() >= size {
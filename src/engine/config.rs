@@ -1,20 +1,45 @@
 use std::{collections::HashMap, path::PathBuf, sync::atomic::Ordering};
 
 use serde::{Serialize, Deserialize};
-use crate::{engine::console::{LogType, print_logs}, ppu::palettes::*};
+use crate::{engine::{console::{LogType, print_logs}, region::Region}, ppu::palettes::*};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(default)]
 pub struct EmulatorConfig {
     pub volume: f32,
+    /// Output device picked in the settings, `None` uses the default one of the system
+    pub audio_device: Option<String>,
+    /// Audio kept buffered ahead of the device, in milliseconds
+    pub audio_latency_ms: u32,
+    pub pacing: PacingMode,
     pub hide_overscan: bool,
     pub terminal_types: (bool, bool, bool),
     pub multiply_resolution: i32,
     pub allow_opposite_directions: bool,
     pub palette: PaletteTheme,
     pub custom_palettes: HashMap<String, Vec<NESColor>>,
-    
+
+    /// Memory the rewind snapshots may use, in megabytes (0 disables rewinding)
+    pub rewind_budget_mb: u32,
+    /// Frames between two rewind snapshots
+    pub rewind_interval: u32,
+
+    /// Forces the timing of a TV system, `None` uses the one in the rom header
+    pub region: Option<Region>,
 }
+/// What decides when the next frame is emulated
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PacingMode {
+    /// Sleeps to the frame rate of the region, the audio is resampled slightly faster or slower
+    /// to keep its buffer half full (dynamic rate control)
+    #[default]
+    Video,
+    /// Emulates a frame every time the audio device has played enough of the buffer, so the speed
+    /// follows the clock of the sound card and the pitch is exact (some frames get shown twice or skipped).
+    /// Works like `Video` without an audio device, or while paused
+    Audio,
+}
+
 impl EmulatorConfig {
     pub fn load() -> Self {
         let path = Self::get_config_path();
@@ -51,12 +76,18 @@ impl Default for EmulatorConfig {
     fn default() -> Self {
         Self {
             volume: 10.0,
+            audio_device: None,
+            audio_latency_ms: 45,
+            pacing: PacingMode::Video,
             hide_overscan: true,
             terminal_types: (true, true, false),
             multiply_resolution: 2,
             allow_opposite_directions: true,
             custom_palettes: HashMap::new(),
             palette: PaletteTheme::DefaultNtsc,
+            rewind_budget_mb: 32,
+            rewind_interval: 4,
+            region: None,
         }
     }
 }
\ No newline at end of file
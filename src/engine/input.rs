@@ -1,6 +1,7 @@
 use crate::memory::joypads::{JoyPad, JoyPadButtons};
 use crate::engine::config::EmulatorConfig;
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct ControllerState {
     pub a: bool, pub b: bool,
     pub up: bool, pub down: bool,
@@ -1,36 +1,57 @@
-use std::path::{Path, PathBuf};
+use std::{fs, path::PathBuf};
 
 use crate::{
     cpu::cpu::CPU,
-    engine::stats::PerfomanceStats,
-    memory,
-    apu::audio::AudioOutput,
+    engine::{
+        console::{LogType, print_logs},
+        region::Region,
+        rewind::RewindBuffer,
+        save_state::{self, SaveStateError, StateReader, StateWriter},
+        stats::PerfomanceStats,
+    },
+    memory::{self, rom_header::{RomHeader, RomLoadError}},
+    apu::audio::AudioSink,
 };
 
 pub struct EmulatorInstance {
     pub cpu: CPU,
     pub is_paused: bool,
     pub is_halted: bool,
+    rom_path: PathBuf,
+    /// The region it asks for is used unless the user picks one
+    header: RomHeader,
+    /// Disabled until [`RewindBuffer::set_limits`] gives it a memory budget
+    pub rewind: RewindBuffer,
     #[allow(unused)]
     stats: PerfomanceStats, //TODO impl performance visualization with the new frontend
 }
 
 impl EmulatorInstance {
-    pub fn new(game_path: PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
-        let mapper = memory::bus::load_rom_from_file(Path::new(&game_path))?;
+    pub fn new(game_path: PathBuf) -> Result<Self, RomLoadError> {
+        let rom_data = fs::read(&game_path)?;
+        Self::from_bytes(&rom_data, game_path)
+    }
+
+    /// Boots a rom that is already in memory, `rom_path` is only used for the battery save and the save states
+    pub fn from_bytes(rom_data: &[u8], rom_path: PathBuf) -> Result<Self, RomLoadError> {
+        let (mapper, header) = memory::bus::load_rom(rom_data, &rom_path)?;
         let mut cpu = CPU::new(mapper);
+        cpu.bus.set_region(header.region());
         cpu.reset_interrupt();
 
         Ok(Self {
             cpu,
             is_paused: false,
             is_halted: false,
+            rom_path,
+            header,
+            rewind: RewindBuffer::new(0, 1),
             stats: PerfomanceStats::new(),
         })
     }
 
     #[cfg(feature = "debug_log")]
-    pub fn run_frame_with_debug_logger(&mut self, audio: &mut Option<(AudioOutput, u32)>, mut logger: impl FnMut(&mut CPU)) {
+    pub fn run_frame_with_debug_logger(&mut self, audio: &mut impl AudioSink, mut logger: impl FnMut(&mut CPU)) {
         self.cpu.bus.ppu.frame_complete = false;
 
         if self.is_paused { return; }
@@ -38,20 +59,24 @@ impl EmulatorInstance {
 
         while !self.cpu.bus.ppu.frame_complete {
             #[cfg(feature = "debug_log")]
-            let (halted, cycles) = self.cpu.step_with_callback(Some(|cpu: &mut CPU| logger(cpu)));
+            let (halted, _) = self.cpu.step_with_callback(Some(|cpu: &mut CPU| {
+                // the logs show the PPU position
+                cpu.bus.sync_ppu();
+                logger(cpu)
+            }));
             
             if halted { 
                 self.is_halted = true; 
                 break;
             };
 
-            if let Some(audio) = audio {
-                self.cpu.bus.sync_audio(cycles, audio);
-            }
+            self.cpu.bus.sync_audio(audio);
         }
+        self.cpu.bus.sync_ppu();
+        self.record_rewind_snapshot();
     }
 
-    pub fn run_frame(&mut self, audio: &mut Option<(AudioOutput, u32)>) {
+    pub fn run_frame(&mut self, audio: &mut impl AudioSink) {
         self.cpu.bus.ppu.frame_complete = false;
 
         if self.is_paused { return; }
@@ -59,22 +84,115 @@ impl EmulatorInstance {
 
         while !self.cpu.bus.ppu.frame_complete {
 
-            let (halted, cycles) = self.cpu.step();
+            let (halted, _) = self.cpu.step();
             
             if halted { 
                 self.is_halted = true; 
                 break;
             };
 
-            if let Some(audio) = audio {
-                self.cpu.bus.sync_audio(cycles, audio);
+            self.cpu.bus.sync_audio(audio);
+        }
+        self.cpu.bus.sync_ppu();
+        self.record_rewind_snapshot();
+    }
+
+    /// Presses the reset button of the console (the cartridge and the RAM are kept)
+    pub fn soft_reset(&mut self) {
+        self.cpu.soft_reset();
+        self.is_halted = false;
+    }
+
+    /// Goes back to the previous rewind snapshot, returns false when there is nothing left to rewind
+    pub fn rewind_frame(&mut self) -> bool {
+        let Some(snapshot) = self.rewind.pop() else { return false };
+
+        if let Err(e) = self.load_state(&snapshot) {
+            print_logs(LogType::Warning, format!("Corrupted rewind snapshot, clearing the rewind buffer ({})", e));
+            self.rewind.clear();
+            return false;
+        }
+
+        // snapshots don't include the picture, so the frame right after it is emulated again (muted)
+        // to have something to show
+        self.cpu.bus.ppu.frame_complete = false;
+        while !self.cpu.bus.ppu.frame_complete {
+            let (halted, _) = self.cpu.step();
+            if halted {
+                self.is_halted = true;
+                break;
             }
         }
+        self.cpu.bus.sync_ppu();
+        self.cpu.bus.apu.discard_samples();
+        true
+    }
+
+    pub fn header(&self) -> &RomHeader {
+        &self.header
+    }
+
+    pub fn region(&self) -> Region {
+        self.cpu.bus.region()
+    }
+
+    /// `None` goes back to the region of the rom header
+    pub fn set_region(&mut self, region: Option<Region>) {
+        let region = region.unwrap_or(self.header.region());
+        if region != self.cpu.bus.region() {
+            print_logs(LogType::Info, format!("Switching region to {:?}", region));
+            self.cpu.bus.set_region(region);
+        }
+    }
+
+    fn record_rewind_snapshot(&mut self) {
+        if self.rewind.frame_finished() {
+            let snapshot = self.save_state();
+            self.rewind.push(snapshot);
+        }
     }
 
     pub fn frame_buffer(&self) -> &[u8] {
         &self.cpu.bus.ppu.frame_buffer
     }
+
+    /// Snapshot of the whole console (CPU, BUS, PPU, APU and the cartridge)
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut w = StateWriter::new();
+        self.cpu.save_state(&mut w);
+        w.write_bool(self.is_halted);
+        w.into_bytes()
+    }
+
+    /// Restores a snapshot made by [`EmulatorInstance::save_state`],
+    /// if it turns out to be invalid halfway through, the previous state is put back
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), SaveStateError> {
+        let mut r = StateReader::new(data)?;
+        let backup = self.save_state();
+
+        let result = self.cpu.load_state(&mut r)
+            .and_then(|_| r.read_bool())
+            .map(|is_halted| self.is_halted = is_halted);
+
+        if result.is_err() {
+            let mut r = StateReader::new(&backup).expect("backup state is always valid");
+            self.cpu.load_state(&mut r).expect("backup state is always valid");
+        }
+        result
+    }
+
+    pub fn save_state_to_slot(&self, slot: u8) -> Result<PathBuf, SaveStateError> {
+        let path = save_state::slot_path(&self.rom_path, slot);
+        fs::write(&path, self.save_state())?;
+        Ok(path)
+    }
+
+    pub fn load_state_from_slot(&mut self, slot: u8) -> Result<PathBuf, SaveStateError> {
+        let path = save_state::slot_path(&self.rom_path, slot);
+        let data = fs::read(&path)?;
+        self.load_state(&data)?;
+        Ok(path)
+    }
 }
 
 
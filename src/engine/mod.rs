@@ -3,4 +3,8 @@ pub mod instance;
 pub mod stats;
 pub mod config;
 pub mod state;
-pub mod console;
\ No newline at end of file
+pub mod console;
+pub mod save_state;
+pub mod rewind;
+pub mod region;
+pub mod movie;
\ No newline at end of file
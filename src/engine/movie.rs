@@ -0,0 +1,110 @@
+use std::fmt::Display;
+
+use crate::memory::joypads::JoyPadButtons;
+
+/// Controller input recorded frame by frame, played back by the command line runner
+///
+/// Uses the input lines of the FCEUX `.fm2` format, so movies recorded there can be used directly:
+/// ```text
+/// |0|RLDUTSBA|........||
+/// ```
+/// One line per frame: the command field, then one field per controller with the buttons in the
+/// order Right, Left, Down, Up, sTart, Select, B, A. A `.` or a space is a released button, anything
+/// else is pressed. Lines that don't start with `|` (the fm2 header, comments) are skipped, and so is
+/// the command field (resets are not supported).
+///
+/// https://fceux.com/web/help/fm2.html
+#[derive(Debug, Default, Clone)]
+pub struct InputMovie {
+    frames: Vec<[JoyPadButtons; 2]>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct MovieParseError {
+    /// 1-based, like a text editor
+    pub line: usize,
+    pub reason: &'static str,
+}
+
+impl Display for MovieParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid movie at line {}: {}", self.line, self.reason)
+    }
+}
+
+impl std::error::Error for MovieParseError {}
+
+/// Bit of each character of a controller field, in the fm2 order
+const BUTTON_ORDER: [JoyPadButtons; 8] = [
+    JoyPadButtons::RIGHT,
+    JoyPadButtons::LEFT,
+    JoyPadButtons::DOWN,
+    JoyPadButtons::UP,
+    JoyPadButtons::START,
+    JoyPadButtons::SELECT,
+    JoyPadButtons::B,
+    JoyPadButtons::A,
+];
+
+impl InputMovie {
+    pub fn parse(text: &str) -> Result<Self, MovieParseError> {
+        let mut frames = Vec::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let Some(fields) = line.trim_end().strip_prefix('|') else { continue };
+            let error = |reason| MovieParseError { line: i + 1, reason };
+
+            let mut fields = fields.split('|');
+            let _commands = fields.next();
+
+            let mut frame = [JoyPadButtons::empty(); 2];
+            for buttons in frame.iter_mut() {
+                let field = fields.next().unwrap_or("");
+                if field.is_empty() { continue }
+                if field.chars().count() != 8 {
+                    return Err(error("a controller field must have 8 buttons"));
+                }
+
+                for (c, button) in field.chars().zip(BUTTON_ORDER) {
+                    if c != '.' && c != ' ' {
+                        buttons.insert(button);
+                    }
+                }
+            }
+            frames.push(frame);
+        }
+
+        Ok(Self { frames })
+    }
+
+    /// Buttons held on `frame` by the 2 controllers, nothing is held after the movie ends
+    pub fn frame(&self, frame: usize) -> [JoyPadButtons; 2] {
+        self.frames.get(frame).copied().unwrap_or([JoyPadButtons::empty(); 2])
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_fm2_input_lines() {
+        let error = InputMovie::parse("version 3\nromFilename test\n|0|........|........||\n|0|....T..A|R.......||\n|0|.L..||\n").unwrap_err();
+        assert_eq!(error, MovieParseError { line: 5, reason: "a controller field must have 8 buttons" });
+
+        let movie = InputMovie::parse("version 3\n|0|........|........||\n|0|....T..A|R.......||\n|1|||\n").unwrap();
+        assert_eq!(movie.len(), 3);
+        assert_eq!(movie.frame(0), [JoyPadButtons::empty(); 2]);
+        assert_eq!(movie.frame(1), [JoyPadButtons::START | JoyPadButtons::A, JoyPadButtons::RIGHT]);
+        assert_eq!(movie.frame(2), [JoyPadButtons::empty(); 2]);
+        assert_eq!(movie.frame(10), [JoyPadButtons::empty(); 2]);
+    }
+}
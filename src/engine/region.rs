@@ -0,0 +1,107 @@
+use serde::{Serialize, Deserialize};
+
+/// TV system of the console, every timing that isn't the same on all of them lives here
+///
+/// ```text
+///          CPU clock      PPU dots/CPU cycle  scanlines  vblank lines   frame rate
+/// NTSC     1.789773 MHz   3                   262        241-260        60.0988 Hz
+/// PAL      1.662607 MHz   3.2                 312        241-310        50.0070 Hz
+/// Dendy    1.773448 MHz   3                   312        291-310        50.0070 Hz
+/// ```
+/// The Dendy (famiclone) runs the PAL picture with the NTSC APU tables,
+/// its vblank starts 51 scanlines after the picture instead of 1.
+///
+/// for more info:
+/// https://www.nesdev.org/wiki/Cycle_reference_chart
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Region {
+    #[default]
+    Ntsc,
+    Pal,
+    Dendy,
+}
+
+const NTSC_NOISE_PERIODS: [u16; 16] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068
+];
+const PAL_NOISE_PERIODS: [u16; 16] = [
+    4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778
+];
+
+const NTSC_DMC_PERIODS: [u16; 16] = [
+    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54
+];
+const PAL_DMC_PERIODS: [u16; 16] = [
+    398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118, 98, 78, 66, 50
+];
+/// See [`Region::frame_counter_steps`]
+const NTSC_FRAME_COUNTER_STEPS: [usize; 5] = [7457, 14913, 22371, 29828, 37281];
+const PAL_FRAME_COUNTER_STEPS: [usize; 5] = [8313, 16627, 24939, 33252, 41565];
+
+impl Region {
+    /// CPU cycles per second
+    pub fn cpu_frequency(self) -> f64 {
+        match self {
+            Region::Ntsc  => 1_789_773.0,
+            Region::Pal   => 1_662_607.0,
+            Region::Dendy => 1_773_448.0,
+        }
+    }
+
+    /// PPU dots run for every CPU cycle, as a fraction (dots, cpu cycles)
+    pub fn ppu_clock_ratio(self) -> (u16, u16) {
+        match self {
+            Region::Ntsc | Region::Dendy => (3, 1),
+            Region::Pal                  => (16, 5),
+        }
+    }
+
+    /// Number of the last scanline of the frame (the pre-render line comes right after it, as -1)
+    pub fn last_scanline(self) -> i16 {
+        match self {
+            Region::Ntsc                => 260,
+            Region::Pal | Region::Dendy => 310,
+        }
+    }
+
+    /// Scanline where the vblank flag is set (and the NMI is sent)
+    pub fn vblank_scanline(self) -> i16 {
+        match self {
+            Region::Ntsc | Region::Pal => 241,
+            Region::Dendy              => 291,
+        }
+    }
+
+    pub fn frame_rate(self) -> f64 {
+        match self {
+            Region::Ntsc                => 60.0988,
+            Region::Pal | Region::Dendy => 50.0070,
+        }
+    }
+
+    /// Noise channel periods, in CPU cycles
+    pub fn noise_periods(self) -> &'static [u16; 16] {
+        match self {
+            Region::Ntsc | Region::Dendy => &NTSC_NOISE_PERIODS,
+            Region::Pal                  => &PAL_NOISE_PERIODS,
+        }
+    }
+
+    /// DMC channel periods, in CPU cycles
+    pub fn dmc_periods(self) -> &'static [u16; 16] {
+        match self {
+            Region::Ntsc | Region::Dendy => &NTSC_DMC_PERIODS,
+            Region::Pal                  => &PAL_DMC_PERIODS,
+        }
+    }
+
+    /// CPU cycles (since the frame counter was reset) of the steps of the APU frame counter:
+    /// the first three quarter frames, the frame IRQ of the 4-step mode
+    /// (the last step comes 1 cycle later) and the last step of the 5-step mode
+    pub fn frame_counter_steps(self) -> &'static [usize; 5] {
+        match self {
+            Region::Ntsc | Region::Dendy => &NTSC_FRAME_COUNTER_STEPS,
+            Region::Pal                  => &PAL_FRAME_COUNTER_STEPS,
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use std::collections::VecDeque;
+
+/// Ring buffer of whole-machine snapshots used to play the game backwards.
+///
+/// Only the newest snapshot is kept as is, every older one is stored as the difference
+/// (XOR, then run-length encoded) against the snapshot that came right after it.
+/// Two consecutive frames share almost all of their RAM/VRAM, so the deltas are mostly zeros
+/// and a few hundred bytes are usually enough for each of them.
+///
+/// ```text
+///  oldest                                          newest
+///  [delta 0] [delta 1] ... [delta n-1]  <-- xor --  [full snapshot]
+/// ```
+/// When the memory budget is exceeded the oldest deltas are thrown away.
+pub struct RewindBuffer {
+    newest: Option<Vec<u8>>,
+    deltas: VecDeque<Vec<u8>>,
+    deltas_size: usize,
+
+    budget: usize,
+    interval: u32,
+    frames_since_snapshot: u32,
+}
+
+impl RewindBuffer {
+    /// `budget` in bytes (0 disables rewinding), `interval` in frames between two snapshots
+    pub fn new(budget: usize, interval: u32) -> Self {
+        Self {
+            newest: None,
+            deltas: VecDeque::new(),
+            deltas_size: 0,
+
+            budget,
+            interval: interval.max(1),
+            frames_since_snapshot: 0,
+        }
+    }
+
+    pub fn set_limits(&mut self, budget: usize, interval: u32) {
+        self.budget = budget;
+        self.interval = interval.max(1);
+        if budget == 0 {
+            self.clear();
+        }
+        self.trim();
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.budget > 0
+    }
+
+    /// Counts one emulated frame, returns true every `interval` frames (when a snapshot should be taken)
+    pub fn frame_finished(&mut self) -> bool {
+        if !self.is_enabled() {
+            return false;
+        }
+        self.frames_since_snapshot += 1;
+        if self.frames_since_snapshot >= self.interval {
+            self.frames_since_snapshot = 0;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn push(&mut self, snapshot: Vec<u8>) {
+        if let Some(newest) = self.newest.take() {
+            // every snapshot of the same game has the same size, if it doesn't the chain is useless
+            if newest.len() == snapshot.len() {
+                let delta = compress_delta(&newest, &snapshot);
+                self.deltas_size += delta.len();
+                self.deltas.push_back(delta);
+            } else {
+                self.deltas.clear();
+                self.deltas_size = 0;
+            }
+        }
+        self.newest = Some(snapshot);
+        self.trim();
+    }
+
+    /// Takes the newest snapshot out of the buffer, the one before it becomes the newest
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        let newest = self.newest.take()?;
+
+        if let Some(delta) = self.deltas.pop_back() {
+            self.deltas_size -= delta.len();
+            let mut previous = newest.clone();
+            apply_delta(&mut previous, &delta);
+            self.newest = Some(previous);
+        }
+        self.frames_since_snapshot = 0;
+        Some(newest)
+    }
+
+    pub fn clear(&mut self) {
+        self.newest = None;
+        self.deltas.clear();
+        self.deltas_size = 0;
+        self.frames_since_snapshot = 0;
+    }
+
+    /// Amount of snapshots that can still be rewound
+    pub fn len(&self) -> usize {
+        self.newest.as_ref().map_or(0, |_| self.deltas.len() + 1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.newest.is_none()
+    }
+
+    /// Memory currently used by the snapshots, in bytes
+    pub fn memory_usage(&self) -> usize {
+        self.newest.as_ref().map_or(0, Vec::len) + self.deltas_size
+    }
+
+    fn trim(&mut self) {
+        while self.memory_usage() > self.budget {
+            match self.deltas.pop_front() {
+                Some(delta) => self.deltas_size -= delta.len(),
+                None => {
+                    // not even a single snapshot fits in the budget
+                    self.newest = None;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Encodes `old XOR new` as a sequence of `[zero run][literal length][literal bytes]`,
+/// both lengths as LEB128 varints
+fn compress_delta(old: &[u8], new: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+
+    while i < old.len() {
+        let zeros_start = i;
+        while i < old.len() && old[i] == new[i] {
+            i += 1;
+        }
+        let literal_start = i;
+        while i < old.len() && old[i] != new[i] {
+            i += 1;
+        }
+
+        write_varint(&mut out, literal_start - zeros_start);
+        write_varint(&mut out, i - literal_start);
+        out.extend(old[literal_start..i].iter().zip(&new[literal_start..i]).map(|(a, b)| a ^ b));
+    }
+    out
+}
+
+fn apply_delta(data: &mut [u8], delta: &[u8]) {
+    let mut pos = 0;
+    let mut i = 0;
+
+    while i < delta.len() {
+        pos += read_varint(delta, &mut i);
+        let literal_len = read_varint(delta, &mut i);
+
+        for (byte, xor) in data[pos..pos + literal_len].iter_mut().zip(&delta[i..i + literal_len]) {
+            *byte ^= xor;
+        }
+        pos += literal_len;
+        i += literal_len;
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut val: usize) {
+    loop {
+        let byte = (val & 0x7F) as u8;
+        val >>= 7;
+        if val == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn read_varint(data: &[u8], i: &mut usize) -> usize {
+    let mut val = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[*i];
+        *i += 1;
+        val |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return val;
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(seed: u8) -> Vec<u8> {
+        let mut data = vec![0u8; 4096];
+        data[10] = seed;
+        data[2000..2000 + seed as usize].fill(seed);
+        data[4095] = seed.wrapping_mul(3);
+        data
+    }
+
+    #[test]
+    fn delta_roundtrip() {
+        let old = snapshot(3);
+        let new = snapshot(200);
+
+        let delta = compress_delta(&old, &new);
+        assert!(delta.len() < old.len());
+
+        let mut restored = new.clone();
+        apply_delta(&mut restored, &delta);
+        assert_eq!(restored, old);
+    }
+
+    #[test]
+    fn pops_snapshots_newest_first() {
+        let mut rewind = RewindBuffer::new(usize::MAX, 1);
+        for seed in 0..10 {
+            rewind.push(snapshot(seed));
+        }
+        assert_eq!(rewind.len(), 10);
+
+        for seed in (0..10).rev() {
+            assert_eq!(rewind.pop().unwrap(), snapshot(seed));
+        }
+        assert!(rewind.pop().is_none());
+    }
+
+    #[test]
+    fn budget_drops_the_oldest_snapshots() {
+        let budget = 4096 + 200;
+        let mut rewind = RewindBuffer::new(budget, 1);
+        for seed in 0..50 {
+            rewind.push(snapshot(seed));
+            assert!(rewind.memory_usage() <= budget);
+        }
+
+        assert!(rewind.len() < 50);
+        assert_eq!(rewind.pop().unwrap(), snapshot(49));
+    }
+
+    #[test]
+    fn snapshot_interval() {
+        let mut rewind = RewindBuffer::new(usize::MAX, 3);
+        let taken: Vec<bool> = (0..6).map(|_| rewind.frame_finished()).collect();
+        assert_eq!(taken, [false, false, true, false, false, true]);
+
+        rewind.set_limits(0, 3);
+        assert!(!rewind.frame_finished());
+    }
+}
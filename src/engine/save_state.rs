@@ -0,0 +1,243 @@
+use std::{fmt::Display, path::{Path, PathBuf}};
+
+use crate::memory::game_save;
+
+/// Identifies a SelectNES save state file ("SelectNES State")
+const SAVE_STATE_MAGIC: [u8; 4] = *b"SNST";
+
+/// Bumped every time the layout of any component's state changes,
+/// older snapshots are refused instead of being loaded into the wrong fields.
+pub const SAVE_STATE_VERSION: u16 = 9;
+
+/// Number of save state slots available for each game (selected with the number keys)
+pub const SAVE_STATE_SLOTS: u8 = 10;
+
+#[derive(Debug)]
+pub enum SaveStateError {
+    Io(std::io::Error),
+    BadMagic,
+    UnsupportedVersion(u16),
+    UnexpectedEof,
+    /// A block had a different size than the component expected (e.g. a CHR RAM of another size)
+    SizeMismatch { expected: usize, found: usize },
+    /// The snapshot was made with a different mapper than the one currently loaded
+    MapperMismatch { expected: u16, found: u16 },
+    InvalidValue(&'static str),
+}
+
+impl Display for SaveStateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "I/O error: {}", e),
+            Self::BadMagic => write!(f, "not a SelectNES save state"),
+            Self::UnsupportedVersion(v) => write!(f, "save state version {} is not supported (current: {})", v, SAVE_STATE_VERSION),
+            Self::UnexpectedEof => write!(f, "save state is truncated"),
+            Self::SizeMismatch { expected, found } => write!(f, "block size mismatch (expected {} bytes, found {})", expected, found),
+            Self::MapperMismatch { expected, found } => write!(f, "save state belongs to mapper {} but mapper {} is loaded", found, expected),
+            Self::InvalidValue(field) => write!(f, "invalid value for '{}'", field),
+        }
+    }
+}
+
+impl std::error::Error for SaveStateError {}
+
+impl From<std::io::Error> for SaveStateError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Little-endian binary writer used by every component to dump its internal state.
+///
+/// The format has no field names, so the order of `write_*` calls in `save_state`
+/// must match the order of `read_*` calls in `load_state` exactly.
+pub struct StateWriter {
+    buf: Vec<u8>,
+}
+
+impl Default for StateWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        let mut w = Self { buf: Vec::with_capacity(0x4000) };
+        w.buf.extend_from_slice(&SAVE_STATE_MAGIC);
+        w.write_u16(SAVE_STATE_VERSION);
+        w
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+
+    pub fn write_u8(&mut self, val: u8) {
+        self.buf.push(val);
+    }
+    pub fn write_bool(&mut self, val: bool) {
+        self.buf.push(val as u8);
+    }
+    pub fn write_u16(&mut self, val: u16) {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+    }
+    pub fn write_i16(&mut self, val: i16) {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+    }
+    pub fn write_u32(&mut self, val: u32) {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+    }
+    pub fn write_u64(&mut self, val: u64) {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+    }
+    pub fn write_f64(&mut self, val: f64) {
+        self.write_u64(val.to_bits());
+    }
+    /// Writes a length prefixed block of bytes (RAM, OAM, palettes...)
+    pub fn write_bytes(&mut self, data: &[u8]) {
+        self.write_u32(data.len() as u32);
+        self.buf.extend_from_slice(data);
+    }
+}
+
+/// Counterpart of [`StateWriter`], every read fails with [`SaveStateError::UnexpectedEof`]
+/// instead of panicking when the snapshot is truncated.
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    /// Validates the magic and version of the snapshot and returns a reader positioned right after them
+    pub fn new(data: &'a [u8]) -> Result<Self, SaveStateError> {
+        let mut r = Self { data, pos: 0 };
+        if r.take(4)? != SAVE_STATE_MAGIC {
+            return Err(SaveStateError::BadMagic);
+        }
+        let version = r.read_u16()?;
+        if version != SAVE_STATE_VERSION {
+            return Err(SaveStateError::UnsupportedVersion(version));
+        }
+        Ok(r)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SaveStateError> {
+        let end = self.pos.checked_add(len).ok_or(SaveStateError::UnexpectedEof)?;
+        let slice = self.data.get(self.pos..end).ok_or(SaveStateError::UnexpectedEof)?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, SaveStateError> {
+        Ok(self.take(1)?[0])
+    }
+    pub fn read_bool(&mut self) -> Result<bool, SaveStateError> {
+        Ok(self.read_u8()? != 0)
+    }
+    pub fn read_u16(&mut self) -> Result<u16, SaveStateError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+    pub fn read_i16(&mut self) -> Result<i16, SaveStateError> {
+        Ok(i16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+    pub fn read_u32(&mut self) -> Result<u32, SaveStateError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+    pub fn read_u64(&mut self) -> Result<u64, SaveStateError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+    pub fn read_f64(&mut self) -> Result<f64, SaveStateError> {
+        Ok(f64::from_bits(self.read_u64()?))
+    }
+    /// Reads a block written by [`StateWriter::write_bytes`] into `dest`,
+    /// the block must have exactly the same size as `dest`
+    pub fn read_bytes_into(&mut self, dest: &mut [u8]) -> Result<(), SaveStateError> {
+        let len = self.read_u32()? as usize;
+        if len != dest.len() {
+            return Err(SaveStateError::SizeMismatch { expected: dest.len(), found: len });
+        }
+        dest.copy_from_slice(self.take(len)?);
+        Ok(())
+    }
+
+    /// Checks the tag written by a mapper's `save_state` against the loaded mapper
+    pub fn expect_mapper(&mut self, mapper_id: u16) -> Result<(), SaveStateError> {
+        let found = self.read_u16()?;
+        if found != mapper_id {
+            return Err(SaveStateError::MapperMismatch { expected: mapper_id, found });
+        }
+        Ok(())
+    }
+}
+
+/// `.saves/<game name>.ss<slot>`, right next to the battery saves of the same game
+pub fn slot_path(rom_path: &Path, slot: u8) -> PathBuf {
+    let game_name = rom_path.file_stem().unwrap_or_default();
+
+    let mut path = game_save::saves_directory();
+    path.push(game_name);
+    path.set_extension(format!("ss{}", slot));
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn primitives_roundtrip() {
+        let mut w = StateWriter::new();
+        w.write_u8(0xAB);
+        w.write_bool(true);
+        w.write_u16(0xBEEF);
+        w.write_i16(-1);
+        w.write_u32(0xDEAD_BEEF);
+        w.write_u64(u64::MAX - 1);
+        w.write_f64(0.25);
+        w.write_bytes(&[1, 2, 3]);
+        let data = w.into_bytes();
+
+        let mut r = StateReader::new(&data).unwrap();
+        assert_eq!(r.read_u8().unwrap(), 0xAB);
+        assert!(r.read_bool().unwrap());
+        assert_eq!(r.read_u16().unwrap(), 0xBEEF);
+        assert_eq!(r.read_i16().unwrap(), -1);
+        assert_eq!(r.read_u32().unwrap(), 0xDEAD_BEEF);
+        assert_eq!(r.read_u64().unwrap(), u64::MAX - 1);
+        assert_eq!(r.read_f64().unwrap(), 0.25);
+        let mut block = [0u8; 3];
+        r.read_bytes_into(&mut block).unwrap();
+        assert_eq!(block, [1, 2, 3]);
+    }
+
+    #[test]
+    fn truncated_state_is_an_error() {
+        let mut w = StateWriter::new();
+        w.write_u32(7);
+        let data = w.into_bytes();
+
+        let mut r = StateReader::new(&data[..data.len() - 1]).unwrap();
+        assert!(matches!(r.read_u32(), Err(SaveStateError::UnexpectedEof)));
+    }
+
+    #[test]
+    fn rejects_other_files_and_versions() {
+        assert!(matches!(StateReader::new(b"NES\x1A\x01\x00"), Err(SaveStateError::BadMagic)));
+
+        let mut data = StateWriter::new().into_bytes();
+        data[4] = 0xFF;
+        assert!(matches!(StateReader::new(&data), Err(SaveStateError::UnsupportedVersion(_))));
+    }
+
+    #[test]
+    fn block_size_must_match() {
+        let mut w = StateWriter::new();
+        w.write_bytes(&[0; 4]);
+        let data = w.into_bytes();
+
+        let mut r = StateReader::new(&data).unwrap();
+        let mut dest = [0u8; 8];
+        assert!(matches!(r.read_bytes_into(&mut dest), Err(SaveStateError::SizeMismatch { expected: 8, found: 4 })));
+    }
+}
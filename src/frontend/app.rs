@@ -11,12 +11,13 @@ use winit::{
 };
 
 use crate::{
-    apu::audio::AudioOutput, 
     engine::{
-        config::EmulatorConfig, console::*, input::*, instance::EmulatorInstance
+        config::EmulatorConfig, console::*, input::*, instance::EmulatorInstance,
+        save_state::SAVE_STATE_SLOTS,
     }, 
     frontend::{
         dock_state::{NesTabViewer, Tab}, 
+        emu_thread::{EmuCommand, EmulatorThread},
         glstate::GLState, 
         nes_texture::NesTexture, 
         panels::{
@@ -26,7 +27,7 @@ use crate::{
     }
 };
 
-use std::{path::PathBuf, sync::Arc, time::{Duration, Instant}};
+use std::{path::PathBuf, sync::Arc};
 
 pub struct App {
     window: Option<Arc<Window>>,
@@ -34,15 +35,22 @@ pub struct App {
     egui_glow: Option<EguiGlow>,      // egui rendering
     dock_state: DockState<Tab>,
 
-    nes: Option<EmulatorInstance>,
+    /// Started with the window, it also asks for the redraws (once per emulated frame)
+    emulator: Option<EmulatorThread>,
     nes_texture: Option<NesTexture>,
     rom_path: Option<PathBuf>,
+    /// Save state slot used by F5 (save) and F7 (load), selected with the number keys
+    state_slot: u8,
+    /// Why the last rom couldn't be loaded, shown in a window until the user closes it
+    rom_error: Option<String>,
 
-    audio: Option<(AudioOutput, u32)>,
     input_state: ControllerState,
 
     config: EmulatorConfig,
-    instant: Instant,
+    /// Last settings sent to the emulation thread
+    sent_config: EmulatorConfig,
+    /// Whether the emulation thread was told that a debug panel is visible
+    watching_debug_view: bool,
 }
 impl App {
     pub fn new() -> Self {
@@ -52,22 +60,44 @@ impl App {
             egui_glow: None,
             dock_state: create_initial_dock_state(),
 
-            nes: None,
+            emulator: None,
             nes_texture: None,
             rom_path: None,
+            state_slot: 0,
+            rom_error: None,
 
-            audio: AudioOutput::new(44100),
-            input_state: ControllerState {
-                a: false, b: false,
-                up: false, down: false,
-                left: false, right: false,
-                start: false, select: false,
-            },
+            input_state: ControllerState::default(),
 
             config: EmulatorConfig::load(),
-            instant: Instant::now(),
+            sent_config: EmulatorConfig::default(),
+            watching_debug_view: false,
         }
     }
+
+    fn send(&self, command: EmuCommand) {
+        if let Some(emulator) = &self.emulator {
+            emulator.send(command);
+        }
+    }
+
+    /// Loads the rom here so a bad file can be reported right away, the game then runs on the emulation thread
+    fn load_rom(&mut self, path: PathBuf) {
+        match EmulatorInstance::new(path.clone()) {
+            Ok(emu) => {
+                self.rom_path = Some(path);
+                self.send(EmuCommand::Load(Box::new(emu)));
+            }
+            Err(e) => {
+                print_logs(LogType::Warning, format!("Failed to load ROM: {}", e));
+                self.rom_error = Some(format!("{}:\n{}", path.display(), e));
+            }
+        }
+    }
+
+    fn select_state_slot(&mut self, slot: u8) {
+        self.state_slot = slot % SAVE_STATE_SLOTS;
+        print_logs(LogType::Info, format!("Save state slot {} selected", self.state_slot));
+    }
 }
 
 impl ApplicationHandler for App {
@@ -98,6 +128,11 @@ impl ApplicationHandler for App {
         let egui_glow = EguiGlow::new(event_loop, Arc::clone(&gl_state.gl), None, None, true);
         
         
+        let redraw_window = Arc::clone(&window);
+        self.emulator = Some(EmulatorThread::spawn(self.config.clone(), move || redraw_window.request_redraw()));
+        self.sent_config = self.config.clone();
+        self.watching_debug_view = false;
+
         self.window = Some(window);
         self.gl_state = Some(gl_state);
         self.egui_glow = Some(egui_glow);
@@ -113,6 +148,7 @@ impl ApplicationHandler for App {
         if let WindowEvent::KeyboardInput { event, .. } = &event {
             if let PhysicalKey::Code(keycode) = event.physical_key {
                 let is_pressed = event.state == ElementState::Pressed;
+                let previous_input = self.input_state;
                 
                 match keycode {
                     KeyCode::KeyZ => self.input_state.a = is_pressed,
@@ -123,8 +159,30 @@ impl ApplicationHandler for App {
                     KeyCode::ArrowDown => self.input_state.down = is_pressed,
                     KeyCode::ArrowLeft => self.input_state.left = is_pressed,
                     KeyCode::ArrowRight => self.input_state.right = is_pressed,
+                    KeyCode::KeyR if !event.repeat => self.send(EmuCommand::Rewind(is_pressed)),
                     _ => {}
                 }
+                if self.input_state != previous_input {
+                    self.send(EmuCommand::Input(self.input_state));
+                }
+
+                if is_pressed && !event.repeat {
+                    match keycode {
+                        KeyCode::F5 => self.send(EmuCommand::SaveState(self.state_slot)),
+                        KeyCode::F7 => self.send(EmuCommand::LoadState(self.state_slot)),
+                        KeyCode::Digit0 => self.select_state_slot(0),
+                        KeyCode::Digit1 => self.select_state_slot(1),
+                        KeyCode::Digit2 => self.select_state_slot(2),
+                        KeyCode::Digit3 => self.select_state_slot(3),
+                        KeyCode::Digit4 => self.select_state_slot(4),
+                        KeyCode::Digit5 => self.select_state_slot(5),
+                        KeyCode::Digit6 => self.select_state_slot(6),
+                        KeyCode::Digit7 => self.select_state_slot(7),
+                        KeyCode::Digit8 => self.select_state_slot(8),
+                        KeyCode::Digit9 => self.select_state_slot(9),
+                        _ => {}
+                    }
+                }
             }
         }
 
@@ -149,15 +207,6 @@ impl ApplicationHandler for App {
                 }
             }
             WindowEvent::RedrawRequested => {
-                let target_frame_time = Duration::from_secs_f64(1.0 / 60.0988);
-                let elapsed = self.instant.elapsed();
-
-                if elapsed < target_frame_time {
-                    std::thread::sleep(target_frame_time - elapsed);
-                }
-                
-                self.instant = Instant::now();
-
                 let gl_state = self.gl_state.as_ref().unwrap();
                 let egui_glow = self.egui_glow.as_mut().unwrap();
                 let window = self.window.as_ref().unwrap();
@@ -168,30 +217,24 @@ impl ApplicationHandler for App {
                     gl.clear(glow::COLOR_BUFFER_BIT);
                 }
 
-                if self.nes_texture.is_none() && !self.nes.is_none() {
-                    self.nes_texture = Some(NesTexture::new(gl, egui_glow));
-                }
-
-                if let Some(emu) = &mut self.nes {
-
-                    //TODO: probably optimize this
-                    emu.cpu.bus.ppu.color_palette = self.config.palette.clone();
-                    emu.cpu.bus.apu.volume = self.config.volume / 100.0;
-
-                    apply_input(&mut emu.cpu.bus.joypad_1, &self.input_state, &self.config);
-
-                    emu.run_frame(&mut self.audio);
-                    let texture = self.nes_texture.as_ref().unwrap();
-                    texture.update(gl, emu.frame_buffer());
+                let emulator = self.emulator.as_mut().unwrap();
+                if let Some(pixels) = emulator.take_frame() {
+                    let texture = self.nes_texture.get_or_insert_with(|| NesTexture::new(gl, egui_glow));
+                    texture.update(gl, pixels);
                 }
+                let debug_view = emulator.debug_view();
 
                 let mut open_rom_requested = false;
                 let mut pause_requested = false; 
                 let mut reset_requested = false;
+                let mut save_state_requested = false;
+                let mut load_state_requested = false;
+                let state_slot = self.state_slot;
                 let dock = &mut self.dock_state;
-                let nes_ref = self.nes.as_ref();
+                let rom_error = &mut self.rom_error;
 
                 let texture_opt = self.nes_texture.as_ref().map(|nt| nt.egui_texture_id);
+                let mut debug_view_shown = false;
 
                 let _repaint_after = egui_glow.run(window, |ctx| { 
                     egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
@@ -212,6 +255,15 @@ impl ApplicationHandler for App {
                                     pause_requested = true; 
                                     ui.close_menu();
                                 }
+                                ui.separator();
+                                if ui.button(format!("Save State (slot {})    F5", state_slot)).clicked() {
+                                    save_state_requested = true;
+                                    ui.close_menu();
+                                }
+                                if ui.button(format!("Load State (slot {})    F7", state_slot)).clicked() {
+                                    load_state_requested = true;
+                                    ui.close_menu();
+                                }
                             });
                             ui.menu_button("View", |ui| {
                                 if ui.button("NES Screen").clicked() {
@@ -260,57 +312,75 @@ impl ApplicationHandler for App {
                         });
                     });
                     
-                    DockArea::new(dock)
-                    .style(Style::from_egui(ctx.style().as_ref()))
-                    .show(ctx, &mut NesTabViewer {
+                    let mut tab_viewer = NesTabViewer {
                         nes_texture: texture_opt,
-                        emulator: nes_ref,
+                        emulator: debug_view.as_ref(),
                         config: &mut self.config,
                         pattern_viewer: &mut pattern_viewer::PatternTableViewer::new(),
                         nametable_viewer: &mut palette_viewer::PaletteViewer::new(),
-                    });
+                        debug_view_shown: false,
+                    };
+                    DockArea::new(dock)
+                    .style(Style::from_egui(ctx.style().as_ref()))
+                    .show(ctx, &mut tab_viewer);
+                    debug_view_shown = tab_viewer.debug_view_shown;
+
+                    if let Some(message) = rom_error.clone() {
+                        egui::Window::new("Failed to load ROM")
+                            .collapsible(false)
+                            .resizable(false)
+                            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                            .show(ctx, |ui| {
+                                ui.label(message);
+                                if ui.button("OK").clicked() {
+                                    *rom_error = None;
+                                }
+                            });
+                    }
                 });
 
+                egui_glow.paint(window);
+                gl_state.swap_buffers();
+
                 if open_rom_requested {
                     if let Some(path) = crate::frontend::panels::open_rom::open_rom_dialog() {
-                        match crate::engine::instance::EmulatorInstance::new(path.clone()) {
-                            Ok(emu) => {
-                                self.rom_path = Some(path);
-                                self.nes = Some(emu);
-                            }
-                            Err(e) => {
-                                print_logs(LogType::Warning, format!("Failed to load ROM: {}", e));
-                            }
-                        }
+                        self.load_rom(path);
                     }
                 }
 
                 if pause_requested {
-                    if let Some(emu) = &mut self.nes {
-                        emu.is_paused = !emu.is_paused;
-                        
-                    }
+                    self.send(EmuCommand::TogglePause);
+                }
+
+                if save_state_requested {
+                    self.send(EmuCommand::SaveState(self.state_slot));
+                }
+
+                if load_state_requested {
+                    self.send(EmuCommand::LoadState(self.state_slot));
                 }
 
                 if reset_requested {
-                    if let Some(_) = &mut self.nes {
-                        match crate::engine::instance::EmulatorInstance::new(self.rom_path.clone().unwrap()) {
-                            Ok(emu) => {
-                                self.nes = Some(emu);
-                            }
-                            Err(e) => {
-                                eprintln!("Failed to load ROM: {}", e);
-                            }
-                        }
+                    if let Some(path) = self.rom_path.clone() {
+                        self.load_rom(path);
                     }
                 }
 
-                egui_glow.paint(window);
-                gl_state.swap_buffers();
-                window.request_redraw();
+                if self.config != self.sent_config {
+                    self.sent_config = self.config.clone();
+                    self.send(EmuCommand::Configure(Box::new(self.config.clone())));
+                }
+
+                if debug_view_shown != self.watching_debug_view {
+                    self.watching_debug_view = debug_view_shown;
+                    self.send(EmuCommand::WatchDebugView(debug_view_shown));
+                }
+                // the next redraw is requested by the emulation thread at the end of its next frame
             }
             WindowEvent::CloseRequested => {
                 self.config.save();
+                // stops the emulation thread
+                self.emulator = None;
                 event_loop.exit();
             }
             _ => { }
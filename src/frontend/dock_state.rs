@@ -1,6 +1,6 @@
 use egui_dock::{TabViewer};
 use crate::engine::config::EmulatorConfig;
-use crate::engine::instance::EmulatorInstance;
+use crate::frontend::emu_thread::DebugView;
 
 use crate::frontend::panels::app_terminal::render_terminal;
 use crate::frontend::panels::settings_panel::render_settings;
@@ -23,11 +23,15 @@ pub enum Tab {
 
 pub struct NesTabViewer<'a> {
     pub nes_texture: Option<egui::TextureId>,
-    pub emulator: Option<&'a EmulatorInstance>,
+    /// `None` while no game is running
+    pub emulator: Option<&'a DebugView>,
     pub config: &'a mut EmulatorConfig,
 
     pub pattern_viewer: &'a mut pattern_viewer::PatternTableViewer,
     pub nametable_viewer: &'a mut palette_viewer::PaletteViewer,
+
+    /// Set when a panel that shows the [`DebugView`] was drawn
+    pub debug_view_shown: bool,
 }
 
 impl TabViewer for NesTabViewer<'_> {
@@ -89,6 +93,7 @@ impl TabViewer for NesTabViewer<'_> {
                 }
             }
             Tab::CpuViewer => {
+                self.debug_view_shown = true;
                 if let Some(emu) = self.emulator {
                     render_cpu_viewer(ui, emu);
                 } else {
@@ -97,6 +102,7 @@ impl TabViewer for NesTabViewer<'_> {
                 
             }
             Tab::PpuViewer => {
+                self.debug_view_shown = true;
                 if let Some(emu) = self.emulator {
                     self.pattern_viewer.render(ui, emu);
                     self.nametable_viewer.render(ui, emu);
@@ -105,6 +111,7 @@ impl TabViewer for NesTabViewer<'_> {
                 }
             }
             Tab::MemoryEditor => {
+                self.debug_view_shown = true;
                 if let Some(emu) = self.emulator {
                     MemViewer::render_memory_viewer(ui, emu, 0x00, 0x07FF);
                 } else {
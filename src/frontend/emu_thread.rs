@@ -0,0 +1,342 @@
+use std::{
+    sync::{mpsc::{self, Receiver, Sender, TryRecvError}, Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    apu::audio::AudioOutput,
+    engine::{
+        config::{EmulatorConfig, PacingMode},
+        console::{LogType, print_logs},
+        input::{apply_input, ControllerState},
+        instance::EmulatorInstance,
+        region::Region,
+    },
+    ppu::{palettes::NESColor, ppu::{SCREEN_HEIGHT, SCREEN_WIDTH}},
+};
+
+const FRAME_BUFFER_LEN: usize = SCREEN_WIDTH * SCREEN_HEIGHT * 4;
+
+/// Everything the window can ask the emulation thread to do
+pub enum EmuCommand {
+    /// Starts a game, replacing the current one (the reset button sends a freshly loaded instance too)
+    Load(Box<EmulatorInstance>),
+    TogglePause,
+    /// Buttons held on the keyboard, for the first controller
+    Input(ControllerState),
+    /// True while the rewind key is held
+    Rewind(bool),
+    SaveState(u8),
+    LoadState(u8),
+    /// The settings changed (palette, volume, rewind budget, region...)
+    Configure(Box<EmulatorConfig>),
+    /// True while a debug panel is visible, the [`DebugView`] is only captured then
+    WatchDebugView(bool),
+}
+
+/// What the debug panels show, copied from the console after every frame while one of them is visible
+#[derive(Clone)]
+pub struct DebugView {
+    /// $0000-$07FF
+    pub ram: Box<[u8; 0x0800]>,
+    /// Pattern tables as the PPU sees them ($0000-$1FFF)
+    pub chr: Box<[u8; 0x2000]>,
+    /// $3F00-$3F1F
+    pub palette_ram: [u8; 0x20],
+    pub colors: [NESColor; 64],
+}
+
+impl DebugView {
+    fn capture(emu: &EmulatorInstance) -> Self {
+        let ppubus = &emu.cpu.bus.ppu.ppubus;
+        let mapper = &*emu.cpu.bus.mapper;
+
+        let mut chr = Box::new([0; 0x2000]);
+        for (addr, byte) in chr.iter_mut().enumerate() {
+            *byte = ppubus.peek(addr as u16, mapper);
+        }
+
+        Self {
+            ram: Box::new(*emu.cpu.bus.ram()),
+            chr,
+            palette_ram: std::array::from_fn(|i| ppubus.peek(0x3F00 + i as u16, mapper)),
+            colors: *emu.cpu.bus.ppu.color_palette.get_collors(),
+        }
+    }
+}
+
+/// Last finished frame, the emulation thread swaps its buffer in and the window swaps it out
+struct FrameSlot {
+    pixels: Box<[u8]>,
+    /// Counts the frames put in the slot, so the window only uploads new ones
+    frame_number: u64,
+    debug: Option<DebugView>,
+}
+
+/// Runs the [`EmulatorInstance`] on its own thread, paced to the frame rate of its region
+///
+/// The window only talks to it through [`EmuCommand`]s and picks up finished frames with
+/// [`EmulatorThread::take_frame`], so rendering egui or dragging the window never delays the emulation
+/// (nor the audio, the stream is fed from the emulation thread).
+///
+/// Frames are double-buffered: the thread renders into its own buffer and swaps it with the shared
+/// one when the frame ends, the window swaps that one with its own buffer before uploading it,
+/// so neither side holds the lock while copying pixels around.
+pub struct EmulatorThread {
+    commands: Option<Sender<EmuCommand>>,
+    slot: Arc<Mutex<FrameSlot>>,
+    front: Box<[u8]>,
+    last_frame: u64,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl EmulatorThread {
+    /// `on_frame` is called from the emulation thread once per frame (to ask the window for a redraw),
+    /// also while there is no game or it is paused
+    pub fn spawn(config: EmulatorConfig, on_frame: impl Fn() + Send + 'static) -> Self {
+        let (commands, receiver) = mpsc::channel();
+        let slot = Arc::new(Mutex::new(FrameSlot {
+            pixels: vec![0; FRAME_BUFFER_LEN].into_boxed_slice(),
+            frame_number: 0,
+            debug: None,
+        }));
+
+        let thread_slot = Arc::clone(&slot);
+        let handle = thread::Builder::new()
+            .name("emulation".into())
+            .spawn(move || emulation_loop(receiver, thread_slot, config, on_frame))
+            .expect("failed to spawn the emulation thread");
+
+        Self {
+            commands: Some(commands),
+            slot,
+            front: vec![0; FRAME_BUFFER_LEN].into_boxed_slice(),
+            last_frame: 0,
+            handle: Some(handle),
+        }
+    }
+
+    pub fn send(&self, command: EmuCommand) {
+        if let Some(commands) = &self.commands {
+            // only fails if the thread is gone, which already got logged by the panic hook
+            let _ = commands.send(command);
+        }
+    }
+
+    /// The latest frame, `None` if there is nothing new since the last call
+    pub fn take_frame(&mut self) -> Option<&[u8]> {
+        let mut slot = self.slot.lock().unwrap();
+        if slot.frame_number == self.last_frame {
+            return None;
+        }
+        self.last_frame = slot.frame_number;
+        std::mem::swap(&mut slot.pixels, &mut self.front);
+        drop(slot);
+
+        Some(&self.front)
+    }
+
+    /// State of the console at the end of the last frame it was captured on
+    /// (see [`EmuCommand::WatchDebugView`]), `None` until then
+    pub fn debug_view(&self) -> Option<DebugView> {
+        self.slot.lock().unwrap().debug.clone()
+    }
+}
+
+impl Drop for EmulatorThread {
+    fn drop(&mut self) {
+        // closing the channel stops the thread
+        self.commands = None;
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+struct EmulationState {
+    nes: Option<EmulatorInstance>,
+    config: EmulatorConfig,
+    audio: Option<AudioOutput>,
+    /// Device and latency `audio` was opened with
+    audio_settings: (Option<String>, u32),
+    input: ControllerState,
+    rewinding: bool,
+    watching_debug_view: bool,
+}
+
+impl EmulationState {
+    fn apply_config(&mut self) {
+        let audio_settings = (self.config.audio_device.clone(), self.config.audio_latency_ms);
+        if audio_settings != self.audio_settings {
+            // the old stream has to be closed before opening the device again
+            self.audio = None;
+            self.audio = AudioOutput::new(audio_settings.0.as_deref(), audio_settings.1);
+            self.audio_settings = audio_settings;
+        }
+        if let Some(audio) = &mut self.audio {
+            // the audio pacing already keeps the buffer level, the rate stays exact
+            audio.dynamic_rate = self.config.pacing == PacingMode::Video;
+        }
+
+        let Some(emu) = &mut self.nes else { return };
+        emu.cpu.bus.ppu.color_palette = self.config.palette.clone();
+        emu.cpu.bus.apu.volume = self.config.volume / 100.0;
+        emu.rewind.set_limits(self.config.rewind_budget_mb as usize * 1024 * 1024, self.config.rewind_interval);
+        emu.set_region(self.config.region);
+    }
+
+    fn handle(&mut self, command: EmuCommand) {
+        match command {
+            EmuCommand::Load(emu) => {
+                self.nes = Some(*emu);
+                self.apply_config();
+            }
+            EmuCommand::TogglePause => {
+                if let Some(emu) = &mut self.nes {
+                    emu.is_paused = !emu.is_paused;
+                }
+            }
+            EmuCommand::Input(input) => self.input = input,
+            EmuCommand::Rewind(rewinding) => self.rewinding = rewinding,
+            EmuCommand::SaveState(slot) => {
+                let Some(emu) = &self.nes else { return };
+                match emu.save_state_to_slot(slot) {
+                    Ok(path) => print_logs(LogType::Info, format!("State saved to slot {} ({})", slot, path.display())),
+                    Err(e) => print_logs(LogType::Warning, format!("Failed to save state on slot {}: {}", slot, e)),
+                }
+            }
+            EmuCommand::LoadState(slot) => {
+                let Some(emu) = &mut self.nes else { return };
+                match emu.load_state_from_slot(slot) {
+                    Ok(_) => print_logs(LogType::Info, format!("State loaded from slot {}", slot)),
+                    Err(e) => print_logs(LogType::Warning, format!("Failed to load state from slot {}: {}", slot, e)),
+                }
+            }
+            EmuCommand::Configure(config) => {
+                self.config = *config;
+                self.apply_config();
+            }
+            EmuCommand::WatchDebugView(watching) => self.watching_debug_view = watching,
+        }
+    }
+}
+
+fn emulation_loop(
+    commands: Receiver<EmuCommand>,
+    slot: Arc<Mutex<FrameSlot>>,
+    config: EmulatorConfig,
+    on_frame: impl Fn(),
+) {
+    // the stream can't leave the thread that created it on every platform
+    let audio = AudioOutput::new(config.audio_device.as_deref(), config.audio_latency_ms);
+    let mut state = EmulationState {
+        audio_settings: (config.audio_device.clone(), config.audio_latency_ms),
+        nes: None,
+        config,
+        audio,
+        input: ControllerState::default(),
+        rewinding: false,
+        watching_debug_view: false,
+    };
+    state.apply_config();
+    let mut back = vec![0; FRAME_BUFFER_LEN].into_boxed_slice();
+    let mut next_frame = Instant::now();
+
+    loop {
+        loop {
+            match commands.try_recv() {
+                Ok(command) => state.handle(command),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return,
+            }
+        }
+
+        if let Some(emu) = &mut state.nes {
+            apply_input(&mut emu.cpu.bus.joypad_1, &state.input, &state.config);
+
+            if state.rewinding {
+                // the picture just stays frozen once there is nothing left to rewind
+                emu.rewind_frame();
+            } else {
+                emu.run_frame(&mut state.audio);
+            }
+            back.copy_from_slice(emu.frame_buffer());
+            // copying the CHR and the palettes every frame isn't free, nobody looks at them most of the time
+            let debug = state.watching_debug_view.then(|| DebugView::capture(emu));
+
+            let mut slot = slot.lock().unwrap();
+            std::mem::swap(&mut slot.pixels, &mut back);
+            slot.frame_number += 1;
+            if debug.is_some() {
+                slot.debug = debug;
+            }
+        }
+        on_frame();
+
+        let region = state.nes.as_ref().map_or(Region::Ntsc, |emu| emu.region());
+        let frame_time = Duration::from_secs_f64(1.0 / region.frame_rate());
+
+        // the audio pacing needs frames that make sound, otherwise the buffer never fills up
+        let playing = state.nes.as_ref().is_some_and(|emu| !emu.is_paused && !emu.is_halted) && !state.rewinding;
+        if let Some(audio) = &state.audio {
+            if state.config.pacing == PacingMode::Audio && playing {
+                // a device that stops pulling samples can't stall the emulation for more than 2 frames
+                audio.wait_for_room(frame_time * 2);
+                next_frame = Instant::now();
+                continue;
+            }
+        }
+
+        // sleeps until a fixed schedule instead of a fixed time, so a slow frame doesn't delay the next ones,
+        // unless it's so late that catching up would mean running frames back to back
+        next_frame += frame_time;
+
+        let now = Instant::now();
+        if next_frame > now {
+            thread::sleep(next_frame - now);
+        } else if now - next_frame > frame_time {
+            next_frame = now;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{path::PathBuf, sync::atomic::{AtomicU32, Ordering}};
+
+    #[test]
+    fn frames_come_back_from_the_thread() {
+        let frames = Arc::new(AtomicU32::new(0));
+        let counter = Arc::clone(&frames);
+        let mut emulator = EmulatorThread::spawn(EmulatorConfig::default(), move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+        assert!(emulator.take_frame().is_none());
+        assert!(emulator.debug_view().is_none());
+
+        let rom = std::fs::read("tests/nestest/nestest.nes").unwrap();
+        let nes = EmulatorInstance::from_bytes(&rom, PathBuf::from("nestest.nes")).unwrap();
+        emulator.send(EmuCommand::Load(Box::new(nes)));
+
+        let start = Instant::now();
+        while frames.load(Ordering::Relaxed) < 3 {
+            assert!(start.elapsed() < Duration::from_secs(10), "no frame after 10 seconds");
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(emulator.take_frame().map(|pixels| pixels.len()), Some(FRAME_BUFFER_LEN));
+        assert!(emulator.take_frame().is_none());
+        // no panel asked for it
+        assert!(emulator.debug_view().is_none());
+
+        emulator.send(EmuCommand::WatchDebugView(true));
+        while emulator.debug_view().is_none() {
+            assert!(start.elapsed() < Duration::from_secs(10), "no debug view after 10 seconds");
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        // dropping it stops and joins the thread
+        drop(emulator);
+    }
+}
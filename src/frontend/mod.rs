@@ -2,5 +2,6 @@ pub mod app;
 pub mod glstate;
 pub mod dock_state;
 pub mod nes_texture;
+pub mod emu_thread;
 
 pub mod panels;
\ No newline at end of file
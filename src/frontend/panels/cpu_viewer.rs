@@ -1,8 +1,8 @@
 use egui::Color32;
 
-use crate::engine::instance::EmulatorInstance;
+use crate::frontend::emu_thread::DebugView;
 
-pub fn render_cpu_viewer<'a>(ui: &mut egui_dock::egui::Ui, _emulator_instance: &'a EmulatorInstance) {
+pub fn render_cpu_viewer<'a>(ui: &mut egui_dock::egui::Ui, _emulator: &'a DebugView) {
     ui.vertical_centered(|ui| {
         ui.label(egui::RichText::new("Not implemented yet")
         .size(20.0)
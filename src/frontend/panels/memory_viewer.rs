@@ -1,4 +1,4 @@
-use crate::{engine::instance::EmulatorInstance};
+use crate::frontend::emu_thread::DebugView;
 
 pub struct MemViewer {
     memory: Box<[u8]>
@@ -9,12 +9,12 @@ impl MemViewer {
             memory: vec![0u8; 0x07FF].into_boxed_slice()
         }
     }
-    pub fn get_mem(&mut self, emu: &EmulatorInstance) {
-        for i in 0..=0x7FF {
-            self.memory[i] = emu.cpu.bus.peek(i as u16)
+    pub fn get_mem(&mut self, emu: &DebugView) {
+        for i in 0..0x7FF {
+            self.memory[i] = emu.ram[i]
         }
     }
-    pub fn render_memory_viewer(ui: &mut egui::Ui, emu: &EmulatorInstance, start_addr: u16, end_addr: u16) {
+    pub fn render_memory_viewer(ui: &mut egui::Ui, emu: &DebugView, start_addr: u16, end_addr: u16) {
         ui.group(|ui| {
             ui.vertical_centered(|ui| {
                 ui.label(
@@ -32,7 +32,7 @@ impl MemViewer {
                     let mut row_text = format!("{:04X}:", addr);
                     
                     for i in 0..16 {
-                        let val = emu.cpu.bus.peek(addr + i);
+                        let val = emu.ram[(addr + i) as usize & 0x07FF];
                         row_text.push_str(&format!(" {:02X}", val));
                     }
                     ui.label(egui::RichText::new(row_text).monospace());
@@ -1,5 +1,5 @@
 use egui::{Color32, ColorImage, TextureHandle, TextureOptions};
-use crate::engine::instance::EmulatorInstance;
+use crate::frontend::emu_thread::DebugView;
 
 pub struct PaletteViewer {
     texture: Option<TextureHandle>,
@@ -10,7 +10,7 @@ impl PaletteViewer {
         Self { texture: None }
     }
 
-    pub fn render(&mut self, ui: &mut egui::Ui, emu: &EmulatorInstance) {
+    pub fn render(&mut self, ui: &mut egui::Ui, emu: &DebugView) {
         let image = self.generate_palette_image(emu);
 
         let texture = self.texture.get_or_insert_with(|| {
@@ -37,15 +37,15 @@ impl PaletteViewer {
         });
     }
 
-    fn generate_palette_image(&self, emu: &EmulatorInstance) -> ColorImage {
+    fn generate_palette_image(&self, emu: &DebugView) -> ColorImage {
         let mut image = ColorImage::new([256, 64], Color32::BLACK);
-        let rgb = emu.cpu.bus.ppu.color_palette.get_collors();
+        let rgb = &emu.colors;
 
         for is_sprite in 0..2 {
             for palette_idx in 0..4 {
                 for color_idx in 0..4 {
                     let addr = 0x3F00 + (is_sprite * 16) + (palette_idx * 4) + color_idx;
-                    let nes_color_idx = emu.cpu.bus.ppu.ppubus.peek(addr) & 0x3F;
+                    let nes_color_idx = emu.palette_ram[addr as usize & 0x1F] & 0x3F;
                     
                     let color = Color32::from_rgb(
                         rgb[nes_color_idx as usize].r,
@@ -1,5 +1,5 @@
 use egui::{Color32, ColorImage, TextureHandle, TextureOptions};
-use crate::engine::instance::EmulatorInstance;
+use crate::frontend::emu_thread::DebugView;
 
 pub struct PatternTableViewer {
     texture: Option<TextureHandle>,
@@ -10,7 +10,7 @@ impl PatternTableViewer {
         Self { texture: None }
     }
 
-    pub fn render(&mut self, ui: &mut egui::Ui, emu: &EmulatorInstance) {
+    pub fn render(&mut self, ui: &mut egui::Ui, emu: &DebugView) {
         
         let image = self.generate_pattern_image(emu);
 
@@ -41,7 +41,7 @@ impl PatternTableViewer {
 
     }
 
-    fn generate_pattern_image(&self, emu: &EmulatorInstance) -> ColorImage {
+    fn generate_pattern_image(&self, emu: &DebugView) -> ColorImage {
         let mut image = ColorImage::new([256, 128], Color32::BLACK);
 
         for side in 0..2 {
@@ -52,8 +52,8 @@ impl PatternTableViewer {
                     for row in 0..8 {
 
                         let addr = (side * 0x1000) + offset + row;
-                        let tile_lsb = emu.cpu.bus.ppu.ppubus.peek(addr);
-                        let tile_msb = emu.cpu.bus.ppu.ppubus.peek(addr + 8);
+                        let tile_lsb = emu.chr[addr as usize];
+                        let tile_msb = emu.chr[addr as usize + 8];
 
                         for col in 0..8 {
                             let pixel_x = (side * 128) + (tile_x * 8) + col;
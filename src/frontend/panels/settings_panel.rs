@@ -1,4 +1,4 @@
-use crate::{engine::{config::EmulatorConfig, console::{LogType, print_logs}}, ppu::palettes::PaletteTheme};
+use crate::{apu::audio::AudioOutput, engine::{config::{EmulatorConfig, PacingMode}, console::{LogType, print_logs}, region::Region}, ppu::palettes::PaletteTheme};
 
 pub fn render_settings(settings: &mut EmulatorConfig, ui: &mut egui_dock::egui::Ui) {
     change_palette(settings, ui);
@@ -8,8 +8,74 @@ pub fn render_settings(settings: &mut EmulatorConfig, ui: &mut egui_dock::egui::
     ui.checkbox(&mut settings.hide_overscan, "Hide hide_overscan");
     ui.separator();
 
-    ui.menu_button("Audio", |ui| {
-         ui.add(egui::Slider::new(&mut settings.volume, 0.0_f32..=200.0_f32))
+    // asking the system for the devices takes a while, they're listed once each time the menu opens
+    let device_names_id = egui::Id::new("audio_device_names");
+    let audio_menu = ui.menu_button("Audio", |ui| {
+        ui.add(egui::Slider::new(&mut settings.volume, 0.0_f32..=200.0_f32).text("Volume"));
+
+        let device_names: Vec<String> = match ui.data(|data| data.get_temp(device_names_id)) {
+            Some(names) => names,
+            None => {
+                let names = AudioOutput::device_names();
+                ui.data_mut(|data| data.insert_temp(device_names_id, names.clone()));
+                names
+            }
+        };
+        let selected_device = settings.audio_device.clone().unwrap_or_else(|| "System default".to_string());
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_label("Device")
+            .selected_text(selected_device)
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut settings.audio_device, None, "System default");
+                for name in device_names {
+                    ui.selectable_value(&mut settings.audio_device, Some(name.clone()), name);
+                }
+            });
+            if ui.button("Refresh").clicked() {
+                ui.data_mut(|data| data.remove::<Vec<String>>(device_names_id));
+            }
+        });
+
+        // every new latency reopens the stream, so it's only applied once the slider is let go
+        let latency_id = egui::Id::new("audio_latency_drag");
+        let mut latency = ui.data(|data| data.get_temp(latency_id)).unwrap_or(settings.audio_latency_ms);
+        let latency_slider = ui.add(egui::Slider::new(&mut latency, 10..=250).text("Latency (ms)"));
+        if latency_slider.dragged() {
+            ui.data_mut(|data| data.insert_temp(latency_id, latency));
+        } else {
+            ui.data_mut(|data| data.remove::<u32>(latency_id));
+            settings.audio_latency_ms = latency;
+        }
+
+        egui::ComboBox::from_label("Pacing")
+        .selected_text(format!("{:?}", settings.pacing))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut settings.pacing, PacingMode::Video, "Video (frame rate)")
+                .on_hover_text("Steady frame rate, the audio rate is adjusted to keep up");
+            ui.selectable_value(&mut settings.pacing, PacingMode::Audio, "Audio (sound card)")
+                .on_hover_text("Exact audio pitch, a frame is emulated whenever the sound card needs more samples");
+        });
+    });
+    if audio_menu.inner.is_none() {
+        ui.data_mut(|data| data.remove::<Vec<String>>(device_names_id));
+    }
+
+    ui.menu_button("Rewind", |ui| {
+        ui.add(egui::Slider::new(&mut settings.rewind_budget_mb, 0..=512).text("Memory (MB)"));
+        ui.add(egui::Slider::new(&mut settings.rewind_interval, 1..=60).text("Snapshot every N frames"));
+    });
+
+    let selected_region = match settings.region {
+        Some(region) => format!("{:?}", region),
+        None => "Auto (ROM header)".to_string(),
+    };
+    egui::ComboBox::from_label("Region")
+    .selected_text(selected_region)
+    .show_ui(ui, |ui| {
+        ui.selectable_value(&mut settings.region, None, "Auto (ROM header)");
+        ui.selectable_value(&mut settings.region, Some(Region::Ntsc), "NTSC");
+        ui.selectable_value(&mut settings.region, Some(Region::Pal), "PAL");
+        ui.selectable_value(&mut settings.region, Some(Region::Dendy), "Dendy");
     });
 }
 
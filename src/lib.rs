@@ -6,6 +6,7 @@ pub mod memory;
 pub mod ppu;
 pub mod apu;
 pub mod engine;
+#[cfg(feature = "gui")]
 pub mod frontend;
 
 #[cfg(feature = "debug_log")]
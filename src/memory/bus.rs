@@ -1,26 +1,20 @@
 use std::path::Path;
 
 use crate::engine::console::{self, LogType};
-use crate::memory::{mappers, mapper_base::*};
+use crate::engine::region::Region;
+use crate::memory::rom_header::{RomHeader, RomLoadError};
+use crate::engine::save_state::{SaveStateError, StateReader, StateWriter};
+use crate::memory::{mappers, mapper_base::*, dma::Dma};
+use crate::cpu::cpu_bus::{CpuBus, TickResult};
 
 use crate::{
     apu::apu::APU,
-    apu::audio::AudioOutput,
+    apu::audio::AudioSink,
     memory::game_save::GameSave,
     ppu::ppu::PPU,
     memory::joypads::JoyPad,
 };
 
-use ringbuf::traits::{Observer as _, Producer as _};
-use ringbuf::wrap;
-
-use std::rc::Rc;
-use std::cell::RefCell;
-
-pub struct TickResult {
-    pub nmi: bool,
-    pub irq: bool,
-}
 
 pub struct BUS {
 
@@ -38,22 +32,50 @@ pub struct BUS {
     ///Unmapped. Available for cartridge use.
     ///[$6000–$7FFF | Usually cartridge RAM, when present]
     ///[$8000–$FFFF | Usually cartridge ROM and mapper registers]
-    pub mapper: Rc<RefCell<dyn Mapper>>,
+    ///
+    ///The PPU reaches the cartridge too (CHR and nametables), it's lent to the PPU on every access
+    pub mapper: Box<dyn Mapper>,
     pub ppu: PPU,
     pub apu: APU,
+    pub dma: Dma,
+
+    /// Last value that went through the data bus, undriven addresses (and undriven bits) read it back
+    /// 
+    /// [https://www.nesdev.org/wiki/Open_bus_behavior]
+    open_bus: u8,
+
+    region: Region,
+    /// PPU dots owed to the PPU, the PAL PPU runs 16 dots every 5 CPU cycles
+    ppu_dot_remainder: u16,
+    /// Dots the PPU is behind the CPU, it only catches up when something could tell the difference
+    /// (see [`BUS::sync_ppu`])
+    ppu_pending_dots: u32,
+    /// The PPU has to catch up once this many dots are pending, 0 keeps it in lockstep with the CPU
+    ppu_deadline: u32,
+
+    /// Total of CPU cycles clocked through the bus, the CPU uses it to know how many cycles
+    /// an instruction actually spent on memory accesses
+    cycle_counter: u64,
 }
 
 impl BUS {
     
-    pub fn new(mapper: Rc<RefCell<dyn Mapper>>) -> Self {
+    pub fn new(mapper: Box<dyn Mapper>) -> Self {
         BUS {
             cpu_memory: [0; 0x0800],
             apu_and_io_functionality: [0; 0x08],
             joypad_1: JoyPad::new(),
             joypad_2: JoyPad::new(),
-            mapper: Rc::clone(&mapper),
-            ppu: PPU::new(mapper),
+            mapper,
+            ppu: PPU::new(),
             apu: APU::default(),
+            dma: Dma::default(),
+            open_bus: 0,
+            region: Region::Ntsc,
+            ppu_dot_remainder: 0,
+            ppu_pending_dots: 0,
+            ppu_deadline: 0,
+            cycle_counter: 0,
         }
     }
 
@@ -64,12 +86,10 @@ impl BUS {
                 self.cpu_memory[addr as usize]
             }
             0x2000..=0x3FFF => {
-                self.ppu.peek(addr)
-            }
-            0x4000..=0x4014 => {
-                0
+                self.ppu.peek(addr, &*self.mapper)
             }
-            0x4015 => self.apu.read_status(),
+            0x4000..=0x4014 => self.open_bus,
+            0x4015 => self.apu.peek_status(),
             0x4016 => {
                 self.joypad_1.peek()
             }
@@ -81,49 +101,73 @@ impl BUS {
                 self.apu_and_io_functionality[addr as usize]
             }
             0x4020..=0xFFFF => {
-                self.mapper.borrow().read(addr)
-                //self.unmapped[addr as usize]
+                let mapper = &self.mapper;
+                if mapper.is_mapped(addr) { mapper.read(addr) } else { self.open_bus }
             }
         }
     }
 
+    /// CPU read, the PPU and the APU are clocked by one CPU cycle **before** the access,
+    /// so reads like $2002 see exactly the dot where they happen
     #[inline(always)]
     pub fn mem_read(&mut self, addr: u16) -> u8 {
+        self.tick(1);
+        self.open_bus = self.read(addr);
+        self.open_bus
+    }
+
+    ///Returns true if the cpu should trigger an NMI
+    /// 
+    ///Same as [`BUS::mem_read`], the rest of the console is clocked before the write happens
+    #[inline(always)]
+    pub fn mem_write(&mut self, addr: u16, val: u8) -> bool {
+        self.tick(1);
+        self.open_bus = val;
+        self.write(addr, val)
+    }
+
+    #[inline]
+    pub fn open_bus(&self) -> u8 {
+        self.open_bus
+    }
+
+    #[inline(always)]
+    fn read(&mut self, addr: u16) -> u8 {
         match addr {
             0x0000..=0x1FFF => {
                 let addr = addr & 0x07FF;
                 self.cpu_memory[addr as usize]
             }
             0x2000..=0x3FFF => {
+                self.sync_ppu();
                 let addr: u8 = (addr & 0x07) as u8;
-                self.ppu.read_registers(addr)
-            }
-            0x4000..=0x4014 => {
-                0
+                self.ppu.read_registers(addr, &mut *self.mapper)
             }
-            0x4015 => self.apu.read_status(),
+            // write only registers, nothing drives the bus
+            0x4000..=0x4014 => self.open_bus,
+            // bit 5 isn't driven
+            0x4015 => self.apu.read_status() | (self.open_bus & 0x20),
+            // the controllers only drive the low bits, the rest is usually $40 (the high byte of the address)
             0x4016 => {
-                self.joypad_1.read()
+                self.joypad_1.read() | (self.open_bus & 0xE0)
             }
             0x4017 => {
-                self.joypad_2.read()
+                self.joypad_2.read() | (self.open_bus & 0xE0)
             }
             0x4018..=0x401F => {
                 let addr = addr - 0x4018;
                 self.apu_and_io_functionality[addr as usize]
             }
             0x4020..=0xFFFF => {
-                self.mapper.borrow().read(addr)
-                //self.unmapped[addr as usize]
+                let mapper = &self.mapper;
+                if mapper.is_mapped(addr) { mapper.read(addr) } else { self.open_bus }
             }
         }
     }
 
-    ///Returns true if the cpu should trigger an NMI
-    /// 
     ///It should trigger an NMI if the ppu writes at ppuctrl AND NMI was just enabled AND the PPU is already in vblank
     #[inline(always)]
-    pub fn mem_write(&mut self, addr: u16, val: u8) -> bool {
+    fn write(&mut self, addr: u16, val: u8) -> bool {
         match addr {
             //cpu ram
             0x0000..=0x1FFF => {
@@ -133,23 +177,19 @@ impl BUS {
             }
             //ppu registers
             0x2000..=0x3FFF => {
+                self.sync_ppu();
                 let addr = addr & 0x0007;
-                if self.ppu.write_registers(addr, val) {
-                    return true
-                }
-                false
+                let nmi = self.ppu.write_registers(addr, val, &mut *self.mapper);
+                self.update_ppu_deadline();
+                nmi
             }
             // dma, controls and audio
             0x4000..=0x4017 => {
 
                 if addr == 0x4014 {
                     // val é a página — ex: 0x02 significa $0200-$02FF
-                    let page_start = (val as u16) << 8;
-                    let mut page = [0u8; 256];
-                    for i in 0..256u16 {
-                        page[i as usize] = self.mem_read(page_start + i);
-                    }
-                    self.ppu.oam_dma_write(&page);
+                    // the copy only starts on the next cpu read (see `run_pending_dma`)
+                    self.dma.start_oam(val);
                     return false;
                 }
 
@@ -170,8 +210,11 @@ impl BUS {
             }
             //cartridge
             0x4020..=0xFFFF => {
+                // the banks, the mirroring and the IRQ counter can change what the PPU does next
+                self.sync_ppu();
                 //passing it's real address(without subtraction) to the mapper to take care of it
-                self.mapper.borrow_mut().write(addr, val);
+                self.mapper.write(addr, val);
+                self.update_ppu_deadline();
                 false
             }
         }
@@ -179,7 +222,7 @@ impl BUS {
 
     pub fn mem_read_u16(&mut self, pos: u16) -> u16 {
         let lo = self.mem_read(pos) as u16;
-        let hi = self.mem_read(pos + 1) as u16;
+        let hi = self.mem_read(pos.wrapping_add(1)) as u16;
         return (hi << 8) | (lo as u16);
     }
 
@@ -187,15 +230,126 @@ impl BUS {
         let hi = (data >> 8) as u8;
         let lo = (data & 0xff) as u8; 
         self.mem_write(pos, lo);
-        self.mem_write(pos + 1, hi);
+        self.mem_write(pos.wrapping_add(1), hi);
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
     }
 
-    pub fn tick(&mut self, cycles: u8) -> TickResult {
-        self.ppu.tick(cycles as u16 * 3);
+    pub fn set_region(&mut self, region: Region) {
+        self.sync_ppu();
+        self.region = region;
+        self.ppu_dot_remainder = 0;
+        self.ppu.region = region;
+        self.apu.set_region(region);
+        self.update_ppu_deadline();
+    }
+
+    /// Runs the PPU (3 dots per cycle, 3.2 on PAL) and the APU for `cycles` CPU cycles,
+    /// used directly by the CPU for its internal cycles (the ones without a memory access)
+    ///
+    /// The dots are only counted here, the PPU runs them in [`BUS::sync_ppu`]
+    #[inline(always)]
+    pub fn tick(&mut self, cycles: u8) {
+        let (dots, per_cycles) = self.region.ppu_clock_ratio();
         for _ in 0..cycles {
-            self.apu.step();
+            self.ppu_dot_remainder += dots;
+            self.ppu_pending_dots += (self.ppu_dot_remainder / per_cycles) as u32;
+            self.ppu_dot_remainder %= per_cycles;
+            self.apu.step(&mut self.dma);
         }
+        self.cycle_counter += cycles as u64;
 
+        if self.ppu_pending_dots >= self.ppu_deadline {
+            self.sync_ppu();
+        }
+    }
+
+    /// Runs the PPU up to the current cycle of the CPU
+    ///
+    /// The PPU is lazy: the CPU only sees it through the registers ($2000-$3FFF), the NMI, the mapper IRQ
+    /// and the end of the frame, so the dots pile up in [`BUS::tick`] and run in one go right before
+    /// any of those. Anything that looks at `ppu` directly (the debuggers, the trace logs) should call it first.
+    pub fn sync_ppu(&mut self) {
+        if self.ppu_pending_dots > 0 {
+            self.ppu.tick(self.ppu_pending_dots, &mut *self.mapper);
+            self.ppu_pending_dots = 0;
+        }
+        self.update_ppu_deadline();
+    }
+
+    /// When the PPU has to catch up next: at the NMI or the end of the frame, or on every cycle
+    /// while the mapper counts PPU events to raise its IRQ
+    fn update_ppu_deadline(&mut self) {
+        self.ppu_deadline = if self.mapper.may_raise_irq() {
+            0
+        } else {
+            self.ppu.dots_until_next_event().saturating_sub(self.ppu_pending_dots)
+        };
+    }
+
+    /// Runs the DMA transfers requested since the last call, the CPU calls it before every instruction
+    /// since that's where it would be halted (its next read cycle).
+    ///
+    /// Returns how many cycles were stolen from the CPU:
+    /// - OAM DMA: 1 halt cycle, 1 alignment cycle if it started on an odd (put) cycle,
+    ///   then 256 reads and 256 writes, 513 or 514 cycles in total
+    /// - DMC DMA: halt, dummy cycle, alignment and the read, usually 4 cycles,
+    ///   or 2 when it happens in the middle of an OAM DMA
+    pub fn run_pending_dma(&mut self) -> u16 {
+        if !self.dma.is_pending() {
+            return 0;
+        }
+        let start = self.cycle_counter;
+
+        // the dma only reads on even (get) cycles, which is where the alignment cycles come from
+        if let Some(page) = self.dma.take_oam_page() {
+            self.tick(1);
+            if self.cycle_counter.is_multiple_of(2) {
+                self.tick(1);
+            }
+
+            let page_start = (page as u16) << 8;
+            let mut oam = [0u8; 256];
+            for i in 0..256u16 {
+                if let Some(addr) = self.dma.take_dmc_address() {
+                    // the dmc read takes one get cycle and the oam dma needs one more to realign
+                    let sample = self.mem_read(addr);
+                    self.dma.finish_dmc(sample);
+                    self.tick(1);
+                }
+                oam[i as usize] = self.mem_read(page_start + i);
+                self.tick(1);
+            }
+            self.sync_ppu();
+            self.ppu.oam_dma_write(&oam);
+        }
+
+        if let Some(addr) = self.dma.take_dmc_address() {
+            self.tick(2);
+            if self.cycle_counter.is_multiple_of(2) {
+                self.tick(1);
+            }
+            let sample = self.mem_read(addr);
+            self.dma.finish_dmc(sample);
+        }
+
+        (self.cycle_counter - start) as u16
+    }
+
+    /// The 2 KB of work RAM ($0000-$07FF)
+    pub fn ram(&self) -> &[u8; 0x0800] {
+        &self.cpu_memory
+    }
+
+    #[inline]
+    pub fn cycle_counter(&self) -> u64 {
+        self.cycle_counter
+    }
+
+    /// Takes the NMI latched by the PPU (if any) and samples the IRQ lines of the mapper and the APU
+    pub fn poll_interrupts(&mut self) -> TickResult {
         let mut tick_result = TickResult {
             nmi: false,
             irq: false,
@@ -206,19 +360,44 @@ impl BUS {
             tick_result.nmi = true;
         }
 
-        tick_result.irq = self.mapper.borrow().irq_pending();
+        tick_result.irq = self.mapper.irq_pending() || self.apu.irq_pending();
 
         tick_result
     }
 
-    pub fn sync_audio(&mut self, cycles: u8, audio: &mut (AudioOutput, u32)) {
-        let capacity = audio.0.producer.capacity().get() as f64;
-        let len = audio.0.producer.occupied_len() as f64;
-        let fullness = len / capacity;
+    pub fn sync_audio(&mut self, audio: &mut impl AudioSink) {
+        self.apu.flush_samples(audio);
+    }
 
-        if let Some(sample) = self.apu.tick(cycles, audio.1, fullness) {
-            let _ = audio.0.producer.try_push(sample);
-        }
+    ///Writes the work RAM, the controllers, the PPU, the APU, the DMA unit and the cartridge, in this order
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_bytes(&self.cpu_memory);
+        w.write_bytes(&self.apu_and_io_functionality);
+        w.write_u8(self.open_bus);
+        w.write_u16(self.ppu_dot_remainder);
+        w.write_u32(self.ppu_pending_dots);
+        self.joypad_1.save_state(w);
+        self.joypad_2.save_state(w);
+        self.ppu.save_state(w);
+        self.apu.save_state(w);
+        self.dma.save_state(w);
+        self.mapper.save_state(w);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        r.read_bytes_into(&mut self.cpu_memory)?;
+        r.read_bytes_into(&mut self.apu_and_io_functionality)?;
+        self.open_bus = r.read_u8()?;
+        self.ppu_dot_remainder = r.read_u16()? % self.region.ppu_clock_ratio().1;
+        self.ppu_pending_dots = r.read_u32()?;
+        self.joypad_1.load_state(r)?;
+        self.joypad_2.load_state(r)?;
+        self.ppu.load_state(r)?;
+        self.apu.load_state(r)?;
+        self.dma.load_state(r)?;
+        self.mapper.load_state(r)?;
+        self.update_ppu_deadline();
+        Ok(())
     }
     
     //pub fn load(&mut self, program: Vec<u8>) {
@@ -227,127 +406,145 @@ impl BUS {
     //}
 }
 
-/// fn to reduce code repetition
-fn wrap_in_pointers<T>(mapper: T) ->  Rc<RefCell<dyn Mapper>>
+impl CpuBus for BUS {
+    #[inline(always)]
+    fn mem_read(&mut self, addr: u16) -> u8 {
+        BUS::mem_read(self, addr)
+    }
+
+    #[inline(always)]
+    fn mem_write(&mut self, addr: u16, val: u8) {
+        BUS::mem_write(self, addr, val);
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        BUS::peek(self, addr)
+    }
+
+    #[inline(always)]
+    fn tick(&mut self, cycles: u8) {
+        BUS::tick(self, cycles)
+    }
+
+    fn poll_interrupts(&mut self) -> TickResult {
+        BUS::poll_interrupts(self)
+    }
+
+    fn cycle_counter(&self) -> u64 {
+        BUS::cycle_counter(self)
+    }
+
+    fn run_pending_dma(&mut self) -> u16 {
+        BUS::run_pending_dma(self)
+    }
+
+    /// The APU channels are silenced and PPUCTRL/PPUMASK are cleared
+    fn reset(&mut self) {
+        self.apu.reset();
+        self.sync_ppu();
+        self.ppu.write_registers(0x2000, 0, &mut *self.mapper);
+        self.ppu.write_registers(0x2001, 0, &mut *self.mapper);
+        self.update_ppu_deadline();
+    }
+
+    fn mem_read_u16(&mut self, pos: u16) -> u16 {
+        BUS::mem_read_u16(self, pos)
+    }
+
+    fn mem_write_u16(&mut self, pos: u16, data: u16) {
+        BUS::mem_write_u16(self, pos, data)
+    }
+}
+
+/// Boxes the mapper for the bus, adding the extra VRAM of the four-screen boards
+fn box_mapper<T>(mapper: T, header: &RomHeader) -> Box<dyn Mapper>
 where T: Mapper + 'static {
-    Rc::new(
-        RefCell::new(
-            mapper
-        )
-    )
+    if header.four_screen {
+        return Box::new(mappers::FourScreenVram::new(mapper));
+    }
+    Box::new(mapper)
 }
 
-const NES_SIGNATURE: [u8; 4] = [0x4E, 0x45, 0x53, 0x1A];
+/// Loads an iNES / NES 2.0 ROM file and returns the appropriate "mapper" for the cartridge,
+/// see [`load_rom`].
+pub fn load_rom_from_file(path: &Path) -> Result<(Box<dyn Mapper>, RomHeader), RomLoadError> {
+    //reads the entire content of a file into a vector of bytes(which is excatly what i need)
+    let rom_data = std::fs::read(path)?;
+    load_rom(&rom_data, path)
+}
 
-/// Loads an iNES ROM file and returns the appropriate "mapper" for the cartridge.
+/// Builds the appropriate "mapper" for a rom already in memory.
 /// 
 /// The "Mappers" in this codebase are a customized 'struct/data format' with all the data of the cartridge on it
 /// (which ik isn't the real meaning of an actual NES mapper)
 /// organized in a format that this emulator can read
 ///
-/// Parses the 16-byte iNES header to extract ROM layout information, slices the
-/// binary data into PRG and CHR regions, determines the mirroring mode, and
-/// constructs the mapper instance corresponding to the cartridge's mapper ID.
-///
-/// ### iNES Header Format
-///
-/// ```text
-/// Offset  Size  Description
-/// ──────────────────────────────────────────────────────────────────────
-/// 0–3     4     Magic: $4E $45 $53 $1A ("NES" + MS-DOS EOF marker)
-/// 4       1     PRG ROM size in 16 KB units
-/// 5       1     CHR ROM size in 8 KB units (0 = board uses CHR RAM)
-/// 6       1     Flags 6: [Mapper low nibble | 4-screen | trainer | battery | mirroring]
-/// 7       1     Flags 7: [Mapper high nibble | NES 2.0 | PlayChoice | VS Unisystem]
-/// 8       1     PRG RAM size (rarely used)
-/// 9       1     TV system (rarely used)
-/// 10      1     TV system / PRG RAM presence (unofficial)
-/// 11–15   5     Padding (should be zero)
-///
-/// Flags 6 bit layout:
-///   7 6 5 4   3         2        1          0
-///   ─────────────────────────────────────────
-///   Mapper lo │ 4-screen │ trainer │ battery │ mirroring
-///                                            └─ 0: horizontal arrangement
-///                                               1: vertical arrangement
-/// ```
+/// Parses the 16-byte header into a [`RomHeader`] (see it for the layout), slices the
+/// binary data into PRG and CHR regions and constructs the mapper instance corresponding
+/// to the cartridge's mapper ID, the mapper gets the header too.
 ///
 /// ### Arguments
 ///
-/// * `path` - Path to the `.nes` ROM file.
+/// * `rom_data` - Whole content of the `.nes` file.
+/// * `path` - Where the rom came from, only used to name its battery save.
+///
+/// Returns the mapper together with the parsed header.
 ///
 /// ### Errors
 ///
-/// Returns an error if:
-/// - The file cannot be read.
+/// Every length is checked before slicing, a [`RomLoadError`] is returned if:
+/// - The data is shorter than the header or doesn't have the iNES signature.
+/// - The header asks for more PRG/CHR data than there is, or for no PRG ROM at all.
+/// - The PRG ROM isn't a multiple of 16 KB or the CHR ROM of 8 KB.
 /// - The mapper ID extracted from the header is not yet implemented.
-/// - Four-screen mirroring is encountered (currently unimplemented).
 /// 
 /// **For more information about real NES Mappers, go to:** https://www.nesdev.org/wiki/Mapper
-pub fn load_rom_from_file(path: &Path) -> Result<Rc<RefCell<dyn Mapper>>, Box<dyn std::error::Error>> {
-
-    //reads the entire content of a file into a vector of bytes(which is excatly what i need)
-    let rom_data = std::fs::read(path)?;
-    let header = &rom_data[0..16];
-
-    let mapper_match = (header[7] & 0xF0) | (header[6] >> 4);
-
-    let has_trainer = (header[6] & 0b0000_0100) != 0;
-
-    if header[0..4] != NES_SIGNATURE {
-        console::print_logs(
-            LogType::Warning, 
-            format!("THE ROM HEADER DOESN'T HAVE A NES SIGNATURE, THIS ROM MIGHT BE INVALID")
-        );
-    }
+pub fn load_rom(rom_data: &[u8], path: &Path) -> Result<(Box<dyn Mapper>, RomHeader), RomLoadError> {
+    let header = RomHeader::from_rom(rom_data)?;
 
     console::print_logs(LogType::Info, format!("--- ROM HEADER INFO ---"));
-    console::print_logs(LogType::Info, format!("Byte 4 (PRG Banks): {}", rom_data[4]));
-    console::print_logs(LogType::Info, format!("Byte 5 (CHR Banks): {}", rom_data[5]));
-    console::print_logs(LogType::Info, format!("Byte 6 (Flags 6)  : {:08b}", rom_data[6]));
-    console::print_logs(LogType::Info, format!("Byte 7 (Flags 7)  : {:08b}", rom_data[7]));
-    console::print_logs(LogType::Info, format!("Mapper ID -> {}", mapper_match));
-    console::print_logs(LogType::Info, format!("Has Trainer -> {}", has_trainer));
-    
-    // the size of the PRG ROM may be 16kb or 32kb, 
-    //that info is at byte 4 as [1 if 16kb and 2 if 32kb]
-    let program_size = rom_data[4] as usize * 0x4000; 
-
-    let chr_size = rom_data[5] as usize * 0x2000; // then I take the chr size, which is at byte 5
-
-    let has_persistent_storage = if (rom_data[6] & 0x02) != 0 { true } else { false };
-    let mapper_save_path = if has_persistent_storage {
-        Some(path)
-    } else {
-        None
-    };
+    console::print_logs(LogType::Info, format!("Format            : {:?}", header.format));
+    console::print_logs(LogType::Info, format!("PRG ROM / CHR ROM : {} / {} bytes", header.prg_rom_size, header.chr_rom_size));
+    console::print_logs(LogType::Info, format!("PRG RAM / NVRAM   : {} / {} bytes", header.prg_ram_size, header.prg_nvram_size));
+    console::print_logs(LogType::Info, format!("CHR RAM / NVRAM   : {} / {} bytes", header.chr_ram_size, header.chr_nvram_size));
+    console::print_logs(LogType::Info, format!("Mapper ID -> {} (submapper {})", header.mapper, header.submapper));
+    console::print_logs(LogType::Info, format!("Has Trainer -> {}", header.has_trainer));
+    console::print_logs(LogType::Info, format!("Timing -> {:?} | Console -> {:?}", header.timing, header.console_type));
+
+    if header.prg_rom_size == 0 {
+        return Err(RomLoadError::NoPrgRom);
+    }
+    // the mappers bank the data in 16 KB / 8 KB pieces and index it modulo those sizes
+    if header.prg_rom_size % 0x4000 != 0 || header.chr_rom_size % 0x2000 != 0 {
+        return Err(RomLoadError::UnsupportedRomSize { prg_rom: header.prg_rom_size, chr_rom: header.chr_rom_size });
+    }
+    if rom_data.len() < header.expected_file_size() {
+        return Err(RomLoadError::SizeMismatch { expected: header.expected_file_size(), found: rom_data.len() });
+    }
+    if rom_data.len() > header.expected_file_size() {
+        console::print_logs(LogType::Warning, format!(
+            "The rom has {} bytes after the PRG/CHR data, they are ignored", rom_data.len() - header.expected_file_size()
+        ));
+    }
 
-    let prg_rom_start = 16 + if has_trainer {512} else {0}; //the header size is 16 bytes
-    let prg_rom_end = prg_rom_start + program_size; 
+    let prg_rom_start = header.prg_rom_offset();
+    let prg_rom_end = prg_rom_start + header.prg_rom_size; 
     let prg_rom_data = rom_data[prg_rom_start..prg_rom_end].into(); // mapping the actual game
     
     //The CHR ROM starts after the PRG ROM
-    let chr_rom_data = rom_data[prg_rom_end..(prg_rom_end + chr_size)].into();
-    
-    let mirroring_type: Mirroring;
+    let chr_rom_data = rom_data[prg_rom_end..(prg_rom_end + header.chr_rom_size)].into();
 
-    if (rom_data[6] & 0x08) != 0 {
-        todo!("FOUR SCREEN BIT")
-    } else if (rom_data[6] & 0x01) != 0 {
-        mirroring_type = Mirroring::Vertical;
-    } else {
-        mirroring_type = Mirroring::Horizontal;
-    }
+    let mapper = match header.mapper {
+        0 =>    box_mapper(mappers::InesMapper000::new(prg_rom_data, chr_rom_data, &header), &header),
+        1 =>    box_mapper(mappers::InesMapper001::new(prg_rom_data, chr_rom_data, &header, GameSave::new(path, &header)), &header),
+        2 =>    box_mapper(mappers::InesMapper002::new(prg_rom_data, &header, GameSave::new(path, &header)), &header),
+        3 =>    box_mapper(mappers::InesMapper003::new(prg_rom_data, chr_rom_data, &header, GameSave::new(path, &header)), &header),
+        4 =>    box_mapper(mappers::InesMapper004::new(prg_rom_data, chr_rom_data, &header, GameSave::new(path, &header)), &header),
+        163 =>  box_mapper(mappers::InesMapper163::new(prg_rom_data, chr_rom_data, &header, GameSave::new(path, &header)), &header),
 
-    match mapper_match {
-        0 =>    Ok(wrap_in_pointers(mappers::InesMapper000::new(prg_rom_data, chr_rom_data, mirroring_type))),
-        1 =>    Ok(wrap_in_pointers(mappers::InesMapper001::new(prg_rom_data, chr_rom_data, GameSave::new(path)))),
-        2 =>    Ok(wrap_in_pointers(mappers::InesMapper002::new(prg_rom_data, mirroring_type, GameSave::new(path)))),
-        3 =>    Ok(wrap_in_pointers(mappers::InesMapper003::new(prg_rom_data, chr_rom_data, mirroring_type, mapper_save_path))),
-        4 =>    Ok(wrap_in_pointers(mappers::InesMapper004::new(prg_rom_data, chr_rom_data, mirroring_type, GameSave::new(path)))),
-        163 =>  Ok(wrap_in_pointers(mappers::InesMapper163::new(prg_rom_data, chr_rom_data, mirroring_type, GameSave::new(path)))),
+        _ => return Err(RomLoadError::UnsupportedMapper { mapper: header.mapper, submapper: header.submapper })
+    };
 
-        _ => Err(format!("Mapper {} is not supported yet", mapper_match).into())
-    }
+    Ok((mapper, header))
 }
 
@@ -0,0 +1,77 @@
+use crate::engine::save_state::{SaveStateError, StateReader, StateWriter};
+
+/// Pending DMA transfers of the 2A03
+///
+/// Both DMAs take the bus away from the CPU: they halt it on its next read cycle and then
+/// use the bus for themselves, while the PPU and the APU keep running as usual.
+/// The transfers themselves are done by [`crate::memory::bus::BUS::run_pending_dma`],
+/// this only remembers what was requested.
+///
+/// for more info:
+/// https://www.nesdev.org/wiki/DMA
+#[derive(Debug, Default)]
+pub struct Dma {
+    /// Page written to $4014, copied to the OAM 1 byte every 2 cycles
+    oam_page: Option<u8>,
+    /// Address of the next sample byte the DMC channel asked for
+    dmc_address: Option<u16>,
+    /// Last byte fetched for the DMC channel, waiting to be taken by it
+    dmc_sample: Option<u8>,
+}
+
+impl Dma {
+    pub fn start_oam(&mut self, page: u8) {
+        self.oam_page = Some(page);
+    }
+
+    /// Hook for the DMC channel, asks for the byte at `addr` to be fetched, stealing cycles from the CPU
+    pub fn request_dmc(&mut self, addr: u16) {
+        self.dmc_address = Some(addr);
+    }
+
+    /// The byte fetched by the last DMC request, if it's done
+    pub fn take_dmc_sample(&mut self) -> Option<u8> {
+        self.dmc_sample.take()
+    }
+
+    #[inline]
+    pub fn is_pending(&self) -> bool {
+        self.oam_page.is_some() || self.dmc_address.is_some()
+    }
+
+    pub(crate) fn take_oam_page(&mut self) -> Option<u8> {
+        self.oam_page.take()
+    }
+
+    pub(crate) fn take_dmc_address(&mut self) -> Option<u16> {
+        self.dmc_address.take()
+    }
+
+    pub(crate) fn finish_dmc(&mut self, sample: u8) {
+        self.dmc_sample = Some(sample);
+    }
+
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_bool(self.oam_page.is_some());
+        w.write_u8(self.oam_page.unwrap_or(0));
+        w.write_bool(self.dmc_address.is_some());
+        w.write_u16(self.dmc_address.unwrap_or(0));
+        w.write_bool(self.dmc_sample.is_some());
+        w.write_u8(self.dmc_sample.unwrap_or(0));
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        let has_oam = r.read_bool()?;
+        let oam_page = r.read_u8()?;
+        self.oam_page = has_oam.then_some(oam_page);
+
+        let has_dmc = r.read_bool()?;
+        let dmc_address = r.read_u16()?;
+        self.dmc_address = has_dmc.then_some(dmc_address);
+
+        let has_sample = r.read_bool()?;
+        let dmc_sample = r.read_u8()?;
+        self.dmc_sample = has_sample.then_some(dmc_sample);
+        Ok(())
+    }
+}
@@ -1,18 +1,45 @@
 use std::{env, fs, path::{Path, PathBuf}};
 
+use crate::engine::save_state::{SaveStateError, StateReader, StateWriter};
+use crate::memory::rom_header::RomHeader;
+
+/// Folder where the battery saves and the save states are stored
+/// (created if it doesn't exist yet)
+pub fn saves_directory() -> PathBuf {
+    let save_path = if cfg!(debug_assertions) {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(".saves")
+    } else {
+        if let Ok(mut exec_path) = env::current_exe() {
+            exec_path.pop();
+            exec_path.push(".saves");
+            exec_path
+        } else {
+            PathBuf::from("./.saves")
+        }
+    };
+
+    let _ = fs::create_dir_all(&save_path);
+    save_path
+}
+
+/// The PRG RAM of the cartridge ($6000-$7FFF), sized by the [`RomHeader`]
+///
+/// Only kept on disk (`.saves/<game>.sav`) when the header says the board has a battery
 pub struct GameSave {
     file_path: PathBuf,
-    save_data: [u8; 0x2000],
+    save_data: Box<[u8]>,
     sram_enabled: bool,
+    has_battery: bool,
 }
 impl GameSave {
-    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+    pub fn new<P: AsRef<Path>>(path: P, header: &RomHeader) -> Self {
         let save_file_path = Self::get_save_path(path.as_ref());
 
         let mut save = Self {
             file_path: save_file_path,
-            save_data: [0; 0x2000],
+            save_data: vec![0; header.prg_ram_len()].into_boxed_slice(),
             sram_enabled: true,
+            has_battery: header.has_battery,
         };
         save.load_save_file();
         save
@@ -21,20 +48,7 @@ impl GameSave {
     fn get_save_path(rom_path: &Path) -> PathBuf {
         let game_name = rom_path.file_stem().unwrap_or_default();
 
-        let mut save_path = if cfg!(debug_assertions) {
-            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(".saves")
-        } else {
-            if let Ok(mut exec_path) = env::current_exe() {
-                exec_path.pop();
-                exec_path.push(".saves");
-                exec_path
-            } else {
-                PathBuf::from("./.saves")
-            }
-        };
-
-        let _ = fs::create_dir_all(&save_path);
-
+        let mut save_path = saves_directory();
         save_path.push(game_name);
         save_path.set_extension("sav");
 
@@ -42,8 +56,11 @@ impl GameSave {
     }
 
     pub fn load_save_file(&mut self) {
+        if !self.has_battery {
+            return;
+        }
         if let Ok(file) = fs::read(&self.file_path) {
-            if file.len() == 0x2000 {
+            if file.len() == self.save_data.len() {
                 self.save_data.copy_from_slice(&file);
             }
         }
@@ -64,8 +81,22 @@ impl GameSave {
     }
 
     pub fn save_to_disk(&self) {
+        if !self.has_battery {
+            return;
+        }
         let _ = fs::write(&self.file_path, &self.save_data);
     }
+
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_bytes(&self.save_data);
+        w.write_bool(self.sram_enabled);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        r.read_bytes_into(&mut self.save_data)?;
+        self.sram_enabled = r.read_bool()?;
+        Ok(())
+    }
 }
 
 impl Drop for GameSave {
@@ -1,3 +1,5 @@
+use crate::engine::save_state::{SaveStateError, StateReader, StateWriter};
+
 bitflags! {
     #[derive(Debug, PartialEq, Eq, Clone, Copy)]
     pub struct JoyPadButtons: u8 {
@@ -46,4 +48,17 @@ impl JoyPad {
             self.joypad_buttons.remove(button);
         }
     }
+    /// Replaces the state of every button at once
+    pub fn set_buttons(&mut self, buttons: JoyPadButtons) {
+        self.joypad_buttons = buttons;
+    }
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_u8(self.read_counter);
+        w.write_u8(self.joypad_buttons.bits());
+    }
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.read_counter = r.read_u8()?;
+        self.joypad_buttons = JoyPadButtons::from_bits_truncate(r.read_u8()?);
+        Ok(())
+    }
 }
\ No newline at end of file
@@ -1,9 +1,36 @@
-#[derive(Clone, Copy, Debug)]
+use crate::engine::save_state::{SaveStateError, StateReader, StateWriter};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Mirroring {
     Vertical,
     Horizontal,
     SingleScreenLower,
-    SingleScreenUpper
+    SingleScreenUpper,
+    /// Each nametable has its own memory, $2800-$2FFF live on the cartridge (see [`Mapper::read_nametable`])
+    FourScreen,
+}
+
+impl Mirroring {
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_u8(match self {
+            Mirroring::Vertical          => 0,
+            Mirroring::Horizontal        => 1,
+            Mirroring::SingleScreenLower => 2,
+            Mirroring::SingleScreenUpper => 3,
+            Mirroring::FourScreen        => 4,
+        });
+    }
+
+    pub fn load_state(r: &mut StateReader) -> Result<Self, SaveStateError> {
+        match r.read_u8()? {
+            0 => Ok(Mirroring::Vertical),
+            1 => Ok(Mirroring::Horizontal),
+            2 => Ok(Mirroring::SingleScreenLower),
+            3 => Ok(Mirroring::SingleScreenUpper),
+            4 => Ok(Mirroring::FourScreen),
+            _ => Err(SaveStateError::InvalidValue("mirroring")),
+        }
+    }
 }
 
 /// A memory mapper that abstracts over different NES cartridge board configurations.
@@ -14,7 +41,7 @@ pub enum Mirroring {
 ///
 /// Implementing this trait allows the emulator to treat all cartridges uniformly,
 /// regardless of their underlying mapper chip (e.g., NROM, MMC1, MMC3).
-pub trait Mapper {
+pub trait Mapper: Send {
     fn read(&self, addr: u16) -> u8;
 
     fn write(&mut self, addr: u16, val: u8);
@@ -25,8 +52,29 @@ pub trait Mapper {
 
     fn mirroring(&self) -> Mirroring;
 
+    /// Save state hook, must write the bank/IRQ registers and any RAM on the board
+    /// (starting with the mapper number, checked back with [`StateReader::expect_mapper`])
+    fn save_state(&self, w: &mut StateWriter);
+
+    /// Restores what [`Mapper::save_state`] wrote, in the same order
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError>;
+
     //optional (depends on the cartridge)
     fn irq_pending(&self) -> bool { false }
     fn acknowledge_irq(&mut self) {}
     fn notify_ppu_address(&mut self, _addr: u16) {}
+    /// Whether the board could raise an IRQ from what the PPU does (the MMC3 scanline counter),
+    /// the bus then runs the PPU in lockstep with the CPU instead of catching it up lazily
+    fn may_raise_irq(&self) -> bool { false }
+
+    /// Whether the board drives the data bus when the CPU reads `addr` ($4020-$FFFF),
+    /// reads of anything else return the open bus (the last value on the bus)
+    fn is_mapped(&self, addr: u16) -> bool { addr >= 0x6000 }
+
+    /// Nametable hook ($2000-$2FFF, already without the $3000 mirror), boards with their own VRAM
+    /// return `Some` and the console's 2 KB of VRAM aren't used for that address
+    fn read_nametable(&self, _addr: u16) -> Option<u8> { None }
+
+    /// Same as [`Mapper::read_nametable`], returns true if the board took the write
+    fn write_nametable(&mut self, _addr: u16, _val: u8) -> bool { false }
 }
\ No newline at end of file
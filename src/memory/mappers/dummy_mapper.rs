@@ -1,7 +1,6 @@
 #![allow(unused_variables)]
 
-use std::{cell::RefCell, rc::Rc};
-
+use crate::engine::save_state::{SaveStateError, StateReader, StateWriter};
 use crate::memory::mapper_base::*;
 
 pub struct TestMapper {
@@ -13,7 +12,7 @@ pub struct TestMapper {
 
 impl TestMapper {
     #[allow(unused)] // test only mapper
-    pub fn new(program: Vec<u8>, mirroring: Mirroring) -> Rc<RefCell<dyn Mapper>> {
+    pub fn boxed(program: Vec<u8>, mirroring: Mirroring) -> Box<dyn Mapper> {
         let ram = [0; 0x0800];
         
         let mut prg_rom_vec = vec![0; 0x8000];
@@ -31,15 +30,13 @@ impl TestMapper {
             chr_rom[i] = (i % 256) as u8; 
         }
 
-        Rc::new(
-            RefCell::new(
-                Self {
-                    ram,
-                    prg_rom: prg_rom_vec,
-                    chr_rom,
-                    mirroring
-                }
-            )
+        Box::new(
+            Self {
+                ram,
+                prg_rom: prg_rom_vec,
+                chr_rom,
+                mirroring
+            }
         )
     }
 }
@@ -72,4 +69,14 @@ impl Mapper for TestMapper {
     fn mirroring(&self) -> Mirroring {
         self.mirroring
     }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_u16(u16::MAX);
+        w.write_bytes(&self.ram);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        r.expect_mapper(u16::MAX)?;
+        r.read_bytes_into(&mut self.ram)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,95 @@
+use crate::engine::save_state::{SaveStateError, StateReader, StateWriter};
+use crate::memory::mapper_base::*;
+
+/// Extra 2 KB of VRAM on the cartridge, wrapped around the board's mapper
+///
+/// With them each of the 4 nametables has its own memory: $2000 and $2400 stay in the console VRAM,
+/// $2800 and $2C00 are answered by the cartridge. The mirroring bits of the header (and the mirroring
+/// registers of boards like the MMC3) are ignored.
+///
+/// Used by Gauntlet, Rad Racer II and a few other games (flag 6 bit 3 of the header).
+///
+/// https://www.nesdev.org/wiki/Mirroring#4-Screen
+pub struct FourScreenVram<M: Mapper> {
+    mapper: M,
+    vram: Box<[u8]>,
+}
+
+impl<M: Mapper> FourScreenVram<M> {
+    pub fn new(mapper: M) -> Self {
+        Self {
+            mapper,
+            vram: vec![0; 0x0800].into_boxed_slice(),
+        }
+    }
+}
+
+impl<M: Mapper> Mapper for FourScreenVram<M> {
+    fn read(&self, addr: u16) -> u8 {
+        self.mapper.read(addr)
+    }
+
+    fn write(&mut self, addr: u16, val: u8) {
+        self.mapper.write(addr, val)
+    }
+
+    fn read_chr(&self, addr: u16) -> u8 {
+        self.mapper.read_chr(addr)
+    }
+
+    fn write_chr(&mut self, addr: u16, val: u8) {
+        self.mapper.write_chr(addr, val)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        Mirroring::FourScreen
+    }
+
+    /// The wrapped mapper first (with its own id), then the extra VRAM
+    fn save_state(&self, w: &mut StateWriter) {
+        self.mapper.save_state(w);
+        w.write_bytes(&self.vram);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.mapper.load_state(r)?;
+        r.read_bytes_into(&mut self.vram)
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.mapper.irq_pending()
+    }
+
+    fn acknowledge_irq(&mut self) {
+        self.mapper.acknowledge_irq()
+    }
+
+    fn may_raise_irq(&self) -> bool {
+        self.mapper.may_raise_irq()
+    }
+
+    fn notify_ppu_address(&mut self, addr: u16) {
+        self.mapper.notify_ppu_address(addr)
+    }
+
+    fn is_mapped(&self, addr: u16) -> bool {
+        self.mapper.is_mapped(addr)
+    }
+
+    fn read_nametable(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x2800..=0x2FFF => Some(self.vram[(addr & 0x07FF) as usize]),
+            _ => None,
+        }
+    }
+
+    fn write_nametable(&mut self, addr: u16, val: u8) -> bool {
+        match addr {
+            0x2800..=0x2FFF => {
+                self.vram[(addr & 0x07FF) as usize] = val;
+                true
+            }
+            _ => false,
+        }
+    }
+}
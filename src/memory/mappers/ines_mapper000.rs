@@ -1,4 +1,6 @@
+use crate::engine::save_state::{SaveStateError, StateReader, StateWriter};
 use crate::memory::mapper_base::*;
+use crate::memory::rom_header::RomHeader;
 
 // TO DO ADD DOCUMENTATION
 pub struct InesMapper000 {
@@ -9,12 +11,12 @@ pub struct InesMapper000 {
 }
 
 impl InesMapper000 {
-    pub fn new(prg_rom_data: Box<[u8]>, chr_rom_data: Box<[u8]>, mirroring: Mirroring) -> Self {
+    pub fn new(prg_rom_data: Box<[u8]>, chr_rom_data: Box<[u8]>, header: &RomHeader) -> Self {
         InesMapper000 {
             prg_rom: prg_rom_data,
             chr_rom: chr_rom_data,
             prg_ram: vec![0; 8192].into_boxed_slice(),
-            mirroring: mirroring
+            mirroring: header.mirroring,
         }
     }
 }
@@ -73,4 +75,14 @@ impl Mapper for InesMapper000 {
         self.mirroring
     }
 
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_u16(0);
+        w.write_bytes(&self.prg_ram);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        r.expect_mapper(0)?;
+        r.read_bytes_into(&mut self.prg_ram)
+    }
+
 }
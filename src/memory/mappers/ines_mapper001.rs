@@ -1,6 +1,8 @@
+use crate::engine::save_state::{SaveStateError, StateReader, StateWriter};
 use crate::memory::mapper_base::*;
 
 use crate::memory::game_save::GameSave;
+use crate::memory::rom_header::RomHeader;
 
 // TO DO ADD DOCUMENTATION
 pub struct InesMapper001 {
@@ -21,8 +23,8 @@ pub struct InesMapper001 {
     prg_bank: u8,
 }
 impl InesMapper001 {
-    pub fn new(prg_rom: Box<[u8]>, chr_rom: Box<[u8]>, game_save: GameSave) -> Self {
-        let chr_ram = if chr_rom.is_empty() { vec![0; 8192].into() } else { vec![].into() };
+    pub fn new(prg_rom: Box<[u8]>, chr_rom: Box<[u8]>, header: &RomHeader, game_save: GameSave) -> Self {
+        let chr_ram = vec![0; header.chr_ram_len()].into();
         
         Self {
             game_save,
@@ -174,4 +176,29 @@ impl Mapper for InesMapper001 {
             _ => unreachable!()
         }
     }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_u16(1);
+        self.game_save.save_state(w);
+        w.write_bytes(&self.chr_ram);
+        w.write_u8(self.shift_register);
+        w.write_u8(self.shift_counter);
+        w.write_u8(self.control);
+        w.write_u8(self.chr_bank_0);
+        w.write_u8(self.chr_bank_1);
+        w.write_u8(self.prg_bank);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        r.expect_mapper(1)?;
+        self.game_save.load_state(r)?;
+        r.read_bytes_into(&mut self.chr_ram)?;
+        self.shift_register = r.read_u8()?;
+        self.shift_counter = r.read_u8()?;
+        self.control = r.read_u8()?;
+        self.chr_bank_0 = r.read_u8()?;
+        self.chr_bank_1 = r.read_u8()?;
+        self.prg_bank = r.read_u8()?;
+        Ok(())
+    }
 }
\ No newline at end of file
@@ -1,6 +1,8 @@
+use crate::engine::save_state::{SaveStateError, StateReader, StateWriter};
 use crate::memory::{
     game_save::GameSave, 
-    mapper_base::{Mapper, Mirroring}
+    mapper_base::{Mapper, Mirroring},
+    rom_header::RomHeader,
 };
 
 
@@ -35,15 +37,15 @@ pub struct InesMapper002 {
     bank_select: u8,
 }
 impl InesMapper002 {
-    pub fn new(prg_rom: Box<[u8]>, mirroring: Mirroring, game_save: GameSave) -> Self {
+    pub fn new(prg_rom: Box<[u8]>, header: &RomHeader, game_save: GameSave) -> Self {
         
         Self {
             game_save,
 
             prg_rom,
-            chr_ram: vec![0; 8192].into_boxed_slice(),
+            chr_ram: vec![0; header.chr_ram_len().max(0x2000)].into_boxed_slice(),
 
-            mirroring,
+            mirroring: header.mirroring,
 
             bank_select: 0,
         }
@@ -90,4 +92,19 @@ impl Mapper for InesMapper002 {
     fn mirroring(&self) -> Mirroring {
         self.mirroring
     }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_u16(2);
+        self.game_save.save_state(w);
+        w.write_bytes(&self.chr_ram);
+        w.write_u8(self.bank_select);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        r.expect_mapper(2)?;
+        self.game_save.load_state(r)?;
+        r.read_bytes_into(&mut self.chr_ram)?;
+        self.bank_select = r.read_u8()?;
+        Ok(())
+    }
 }
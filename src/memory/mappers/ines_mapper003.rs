@@ -1,16 +1,11 @@
-use std::path::Path;
-
-use crate::memory::{game_save::GameSave, mapper_base::{Mapper, Mirroring}};
-
-pub enum CpuRam {
-    Volatile([u8; 0x2000]),
-    Persistent(GameSave),
-}
+use crate::engine::save_state::{SaveStateError, StateReader, StateWriter};
+use crate::memory::{game_save::GameSave, mapper_base::{Mapper, Mirroring}, rom_header::RomHeader};
 
 ///https://www.nesdev.org/wiki/CNROM
 pub struct InesMapper003 {
     prg_rom: Box<[u8]>,
-    prg_ram: CpuRam,
+    /// $6000-$7FFF, only kept on disk if the header has the battery bit
+    prg_ram: GameSave,
     chr_rom: Box<[u8]>,
 
     mirroring: Mirroring,
@@ -18,20 +13,13 @@ pub struct InesMapper003 {
     chr_bank: u8,
     
 } impl InesMapper003 {
-    ///for the CNROM, anything different from zero in the "has_ram" means the mapper has 2kb of ram mirrored from $6000 to $7FFF
-    pub fn new<P: AsRef<Path>>(prg_rom: Box<[u8]>, chr_rom: Box<[u8]>, mirroring: Mirroring, has_save: Option<P>) -> Self {
-        let prg_ram = if let Some(path) = has_save { 
-            CpuRam::Persistent(GameSave::new(path))
-        } else { 
-            CpuRam::Volatile([0; 0x2000]) 
-        };
-
+    pub fn new(prg_rom: Box<[u8]>, chr_rom: Box<[u8]>, header: &RomHeader, prg_ram: GameSave) -> Self {
         Self {
             prg_rom,
             prg_ram,
             chr_rom,
 
-            mirroring,
+            mirroring: header.mirroring,
 
             chr_bank: 0,
         }
@@ -41,19 +29,7 @@ impl Mapper for InesMapper003 {
     fn read(&self, addr: u16) -> u8 {
         match addr {
             0x6000..=0x7FFF => {
-                match &self.prg_ram {
-                    CpuRam::Persistent(save) => {
-                        save.read(addr)
-                    }
-                    CpuRam::Volatile(data) => {
-                        if data.is_empty() {
-                            let index = (addr - 0x6000) as usize & 0x07FF; 
-                            data[index]
-                        } else {
-                            0
-                        }
-                    }
-                }
+                self.prg_ram.read(addr)
             }
             0x8000..=0xFFFF => {
                 let index = (addr - 0x8000) as usize % self.prg_rom.len();
@@ -66,17 +42,7 @@ impl Mapper for InesMapper003 {
     fn write(&mut self, addr: u16, val: u8) {
         match addr {
             0x6000..=0x7FFF => {
-                match &mut self.prg_ram {
-                    CpuRam::Persistent(save) => {
-                        save.write(addr, val)
-                    }
-                    CpuRam::Volatile(data) => {
-                        if !data.is_empty() {
-                            let index = (addr - 0x6000) as usize & 0x07FF;
-                            data[index] = val;
-                        }
-                    }
-                }
+                self.prg_ram.write(addr, val)
             }
             0x8000..=0xFFFF => {
                 self.chr_bank = val & 0x03;
@@ -96,4 +62,17 @@ impl Mapper for InesMapper003 {
     fn mirroring(&self) -> Mirroring {
         self.mirroring
     }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_u16(3);
+        self.prg_ram.save_state(w);
+        w.write_u8(self.chr_bank);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        r.expect_mapper(3)?;
+        self.prg_ram.load_state(r)?;
+        self.chr_bank = r.read_u8()?;
+        Ok(())
+    }
 }
\ No newline at end of file
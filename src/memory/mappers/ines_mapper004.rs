@@ -1,6 +1,8 @@
+use crate::engine::save_state::{SaveStateError, StateReader, StateWriter};
 use crate::memory::mapper_base::*;
 
 use crate::memory::game_save::GameSave;
+use crate::memory::rom_header::RomHeader;
 
 /// http://nesdev.org/wiki/MMC3
 /// 
@@ -81,8 +83,8 @@ pub struct InesMapper004 {
 }
 
 impl InesMapper004 {
-    pub fn new(prg_rom: Box<[u8]>, chr_rom: Box<[u8]>, mirroring: Mirroring, game_save: GameSave) -> Self {
-        let chr_ram = if chr_rom.is_empty() { vec![0; 8192].into() } else { vec![].into() };
+    pub fn new(prg_rom: Box<[u8]>, chr_rom: Box<[u8]>, header: &RomHeader, game_save: GameSave) -> Self {
+        let chr_ram = vec![0; header.chr_ram_len()].into();
 
         InesMapper004 {
             game_save,
@@ -90,7 +92,7 @@ impl InesMapper004 {
             chr_rom,
             chr_ram,
 
-            mirroring,
+            mirroring: header.mirroring,
 
             bank_select_register: 0,
             bank_registers: [0; 8],
@@ -287,6 +289,53 @@ impl Mapper for InesMapper004 {
         self.mirroring
     }
 
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_u16(4);
+        self.game_save.save_state(w);
+        w.write_bytes(&self.chr_ram);
+        self.mirroring.save_state(w);
+
+        w.write_u8(self.bank_select_register);
+        w.write_bytes(&self.bank_registers);
+        w.write_u8(self.bank_select as u8);
+
+        w.write_u8(self.irq_counter);
+        w.write_u8(self.irq_latch);
+        w.write_bool(self.irq_enabled);
+        w.write_bool(self.irq_pending);
+        w.write_bool(self.irq_reload);
+
+        w.write_bool(self.last_a12);
+        w.write_u32(self.a12_low_counter);
+
+        w.write_bool(self.prg_ram_chip_enable);
+        w.write_bool(self.prg_ram_w_protection);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        r.expect_mapper(4)?;
+        self.game_save.load_state(r)?;
+        r.read_bytes_into(&mut self.chr_ram)?;
+        self.mirroring = Mirroring::load_state(r)?;
+
+        self.bank_select_register = r.read_u8()?;
+        r.read_bytes_into(&mut self.bank_registers)?;
+        self.bank_select = (r.read_u8()? & 0x07) as usize;
+
+        self.irq_counter = r.read_u8()?;
+        self.irq_latch = r.read_u8()?;
+        self.irq_enabled = r.read_bool()?;
+        self.irq_pending = r.read_bool()?;
+        self.irq_reload = r.read_bool()?;
+
+        self.last_a12 = r.read_bool()?;
+        self.a12_low_counter = r.read_u32()?;
+
+        self.prg_ram_chip_enable = r.read_bool()?;
+        self.prg_ram_w_protection = r.read_bool()?;
+        Ok(())
+    }
+
     #[inline]
     fn acknowledge_irq(&mut self) {
         self.irq_pending = false
@@ -296,6 +345,10 @@ impl Mapper for InesMapper004 {
         self.irq_pending
     }
 
+    fn may_raise_irq(&self) -> bool {
+        self.irq_enabled
+    }
+
     fn notify_ppu_address(&mut self, addr: u16) {
         let a12 = addr & 0x1000 != 0;
 
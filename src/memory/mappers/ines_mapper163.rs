@@ -1,4 +1,6 @@
+use crate::engine::save_state::{SaveStateError, StateReader, StateWriter};
 use crate::memory::mapper_base::*;
+use crate::memory::rom_header::RomHeader;
 
 use crate::memory::game_save::GameSave;
 
@@ -17,21 +19,25 @@ pub struct InesMapper163 {
     bank_select: usize, 
 }
 impl InesMapper163 {
-    pub fn new(prg_rom: Box<[u8]>, chr_rom: Box<[u8]>, mirroring: Mirroring, game_save: GameSave) -> Self {
-        let chr_ram = if chr_rom.is_empty() { vec![0; 8192].into() } else { vec![].into() };
+    pub fn new(prg_rom: Box<[u8]>, chr_rom: Box<[u8]>, header: &RomHeader, game_save: GameSave) -> Self {
+        let chr_ram = vec![0; header.chr_ram_len()].into();
         Self {
             game_save,
             prg_rom,
             chr_rom,
             chr_ram,
 
-            mirroring,
+            mirroring: header.mirroring,
             security_latch: false,
             bank_select: 0,
         }
     }
 }
 impl Mapper for InesMapper163 {
+    fn is_mapped(&self, addr: u16) -> bool {
+        addr == 0x5100 || addr >= 0x6000
+    }
+
     fn read(&self, addr: u16) -> u8 {
         match addr {
             0x5000..=0x5FFF => {
@@ -90,4 +96,23 @@ impl Mapper for InesMapper163 {
     fn mirroring(&self) -> Mirroring {
         self.mirroring
     }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_u16(163);
+        self.game_save.save_state(w);
+        w.write_bytes(&self.chr_ram);
+        w.write_bool(self.security_latch);
+        self.mirroring.save_state(w);
+        w.write_u8(self.bank_select as u8);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        r.expect_mapper(163)?;
+        self.game_save.load_state(r)?;
+        r.read_bytes_into(&mut self.chr_ram)?;
+        self.security_latch = r.read_bool()?;
+        self.mirroring = Mirroring::load_state(r)?;
+        self.bank_select = (r.read_u8()? & 0x3F) as usize;
+        Ok(())
+    }
 }
@@ -4,6 +4,7 @@ pub mod ines_mapper002;
 pub mod ines_mapper003;
 pub mod ines_mapper004;
 pub mod ines_mapper163;
+pub mod four_screen;
 
 pub mod dummy_mapper;
 
@@ -12,4 +13,5 @@ pub use self::ines_mapper001::InesMapper001;
 pub use self::ines_mapper002::InesMapper002;
 pub use self::ines_mapper003::InesMapper003;
 pub use self::ines_mapper004::InesMapper004;
-pub use self::ines_mapper163::InesMapper163;
\ No newline at end of file
+pub use self::ines_mapper163::InesMapper163;
+pub use self::four_screen::FourScreenVram;
\ No newline at end of file
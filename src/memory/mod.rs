@@ -1,5 +1,7 @@
 pub mod bus;
+pub mod dma;
 pub mod mapper_base;
 pub mod joypads;
 pub mod game_save;
-pub mod mappers;
\ No newline at end of file
+pub mod mappers;
+pub mod rom_header;
\ No newline at end of file
@@ -0,0 +1,370 @@
+use std::fmt::Display;
+
+use crate::engine::region::Region;
+use crate::memory::mapper_base::Mirroring;
+
+/// Why a rom couldn't be loaded
+#[derive(Debug)]
+pub enum RomLoadError {
+    Io(std::io::Error),
+    /// Not even the 16 byte header is there
+    TooShort { found: usize },
+    /// The file doesn't start with "NES" + $1A
+    BadMagic,
+    UnsupportedMapper { mapper: u16, submapper: u8 },
+    /// The header asks for more PRG/CHR (and trainer) data than the file has
+    SizeMismatch { expected: usize, found: usize },
+    /// A cartridge without PRG ROM has nothing to run
+    NoPrgRom,
+    /// The PRG ROM isn't made of 16 KB banks or the CHR ROM of 8 KB ones,
+    /// which the NES 2.0 exponent-multiplier sizes allow
+    UnsupportedRomSize { prg_rom: usize, chr_rom: usize },
+}
+
+impl Display for RomLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "couldn't read the rom: {}", e),
+            Self::TooShort { found } => write!(f, "the file is too short to be a rom ({} bytes, the header alone has {})", found, RomHeader::SIZE),
+            Self::BadMagic => write!(f, "not a NES rom (the iNES signature is missing)"),
+            Self::UnsupportedMapper { mapper, submapper } => write!(f, "mapper {} (submapper {}) is not supported yet", mapper, submapper),
+            Self::SizeMismatch { expected, found } => write!(f, "the rom is truncated (the header asks for {} bytes, the file has {})", expected, found),
+            Self::NoPrgRom => write!(f, "the header says the rom has no PRG ROM"),
+            Self::UnsupportedRomSize { prg_rom, chr_rom } => write!(f,
+                "the PRG ROM must be a multiple of 16 KB and the CHR ROM of 8 KB (the header says {} / {} bytes)", prg_rom, chr_rom
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RomLoadError {}
+
+impl From<std::io::Error> for RomLoadError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Which version of the header the rom uses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderFormat {
+    INes,
+    /// identified by bits 2-3 of flags 7 being `10`
+    Nes2,
+}
+
+/// CPU/PPU timing asked by the header (NES 2.0 byte 12)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timing {
+    Ntsc,
+    Pal,
+    /// works on any console, NTSC is used
+    MultiRegion,
+    Dendy,
+}
+
+/// Console the rom was made for (flags 7 bits 0-1, extended by NES 2.0 byte 13)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleType {
+    Nes,
+    /// `ppu_type` and `hardware_type` come from the low/high nibble of byte 13
+    VsSystem { ppu_type: u8, hardware_type: u8 },
+    Playchoice10,
+    /// Famiclones, VT01... (low nibble of byte 13)
+    Extended(u8),
+}
+
+/// Everything the 16 byte header of a `.nes` file says about the cartridge
+///
+/// ```text
+/// Offset  Description
+/// ──────────────────────────────────────────────────────────────────────────────
+/// 0–3     Magic: $4E $45 $53 $1A ("NES" + MS-DOS EOF marker)
+/// 4       PRG ROM size LSB (16 KB units)
+/// 5       CHR ROM size LSB (8 KB units)
+/// 6       [Mapper D0..D3 | 4-screen | trainer | battery | mirroring]
+/// 7       [Mapper D4..D7 | NES 2.0 identifier (10) | console type]
+/// ─── NES 2.0 only (iNES: 8 is the PRG RAM size in 8 KB units, 9 bit 0 is PAL) ───
+/// 8       [Submapper | Mapper D8..D11]
+/// 9       [CHR ROM size MSB | PRG ROM size MSB]
+/// 10      [PRG NVRAM shift | PRG RAM shift]        (64 << shift bytes, 0 = none)
+/// 11      [CHR NVRAM shift | CHR RAM shift]
+/// 12      CPU/PPU timing (0: NTSC, 1: PAL, 2: multi-region, 3: Dendy)
+/// 13      Vs. System PPU/hardware type, or extended console type
+/// 14      Number of miscellaneous ROMs
+/// 15      Default expansion device
+/// ```
+/// When the MSB nibble of a ROM size is $F the LSB is an exponent-multiplier instead:
+/// `2^E * (MM * 2 + 1)` bytes, with `EEEEEE MM`.
+///
+/// for more info:
+/// https://www.nesdev.org/wiki/NES_2.0
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomHeader {
+    pub format: HeaderFormat,
+
+    pub mapper: u16,
+    pub submapper: u8,
+
+    /// in bytes
+    pub prg_rom_size: usize,
+    /// in bytes, 0 means the board uses CHR RAM
+    pub chr_rom_size: usize,
+
+    /// volatile PRG RAM ($6000-$7FFF), in bytes
+    pub prg_ram_size: usize,
+    /// battery backed PRG RAM, in bytes
+    pub prg_nvram_size: usize,
+    pub chr_ram_size: usize,
+    pub chr_nvram_size: usize,
+
+    /// Hard-wired mirroring, ignored by mappers that control it themselves
+    pub mirroring: Mirroring,
+    pub four_screen: bool,
+    pub has_battery: bool,
+    pub has_trainer: bool,
+
+    pub timing: Timing,
+    pub console_type: ConsoleType,
+
+    pub misc_roms: u8,
+    pub expansion_device: u8,
+}
+
+impl RomHeader {
+    pub const SIZE: usize = 16;
+    /// "NES" followed by the MS-DOS end of file
+    pub const MAGIC: [u8; 4] = [0x4E, 0x45, 0x53, 0x1A];
+    pub const TRAINER_SIZE: usize = 512;
+
+    /// Reads the header at the start of `rom_data`, checking its signature
+    pub fn from_rom(rom_data: &[u8]) -> Result<Self, RomLoadError> {
+        let header: &[u8; Self::SIZE] = rom_data.get(0..Self::SIZE)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or(RomLoadError::TooShort { found: rom_data.len() })?;
+
+        if header[0..4] != Self::MAGIC {
+            return Err(RomLoadError::BadMagic);
+        }
+        Ok(Self::parse(header))
+    }
+
+    pub fn parse(header: &[u8; Self::SIZE]) -> Self {
+        let is_nes2 = header[7] & 0x0C == 0x08;
+
+        let mirroring = if header[6] & 0x01 != 0 { Mirroring::Vertical } else { Mirroring::Horizontal };
+        let four_screen = header[6] & 0x08 != 0;
+        let has_battery = header[6] & 0x02 != 0;
+        let has_trainer = header[6] & 0x04 != 0;
+        let mapper_lo = ((header[7] & 0xF0) | (header[6] >> 4)) as u16;
+
+        let console_type = match header[7] & 0x03 {
+            0 => ConsoleType::Nes,
+            1 => ConsoleType::VsSystem {
+                ppu_type: if is_nes2 { header[13] & 0x0F } else { 0 },
+                hardware_type: if is_nes2 { header[13] >> 4 } else { 0 },
+            },
+            2 => ConsoleType::Playchoice10,
+            _ => ConsoleType::Extended(if is_nes2 { header[13] & 0x0F } else { 0 }),
+        };
+
+        if !is_nes2 {
+            let chr_rom_size = header[5] as usize * 0x2000;
+            // a 0 at byte 8 still means 8 KB, for compatibility
+            let prg_ram_size = (header[8].max(1)) as usize * 0x2000;
+
+            return Self {
+                format: HeaderFormat::INes,
+                mapper: mapper_lo,
+                submapper: 0,
+                prg_rom_size: header[4] as usize * 0x4000,
+                chr_rom_size,
+                prg_ram_size: if has_battery { 0 } else { prg_ram_size },
+                prg_nvram_size: if has_battery { prg_ram_size } else { 0 },
+                chr_ram_size: if chr_rom_size == 0 { 0x2000 } else { 0 },
+                chr_nvram_size: 0,
+                mirroring,
+                four_screen,
+                has_battery,
+                has_trainer,
+                timing: if header[9] & 0x01 != 0 { Timing::Pal } else { Timing::Ntsc },
+                console_type,
+                misc_roms: 0,
+                expansion_device: 0,
+            };
+        }
+
+        Self {
+            format: HeaderFormat::Nes2,
+            mapper: mapper_lo | (((header[8] & 0x0F) as u16) << 8),
+            submapper: header[8] >> 4,
+            prg_rom_size: rom_size(header[4], header[9] & 0x0F, 0x4000),
+            chr_rom_size: rom_size(header[5], header[9] >> 4, 0x2000),
+            prg_ram_size: shift_size(header[10] & 0x0F),
+            prg_nvram_size: shift_size(header[10] >> 4),
+            chr_ram_size: shift_size(header[11] & 0x0F),
+            chr_nvram_size: shift_size(header[11] >> 4),
+            mirroring,
+            four_screen,
+            has_battery,
+            has_trainer,
+            timing: match header[12] & 0x03 {
+                0 => Timing::Ntsc,
+                1 => Timing::Pal,
+                2 => Timing::MultiRegion,
+                _ => Timing::Dendy,
+            },
+            console_type,
+            misc_roms: header[14] & 0x03,
+            expansion_device: header[15] & 0x3F,
+        }
+    }
+
+    /// Offset of the PRG ROM in the file (after the header and the trainer)
+    pub fn prg_rom_offset(&self) -> usize {
+        Self::SIZE + if self.has_trainer { Self::TRAINER_SIZE } else { 0 }
+    }
+
+    /// Size the file must have for everything the header describes (misc roms aren't counted)
+    pub fn expected_file_size(&self) -> usize {
+        self.prg_rom_offset()
+            .saturating_add(self.prg_rom_size)
+            .saturating_add(self.chr_rom_size)
+    }
+
+    pub fn region(&self) -> Region {
+        match self.timing {
+            Timing::Ntsc | Timing::MultiRegion => Region::Ntsc,
+            Timing::Pal => Region::Pal,
+            Timing::Dendy => Region::Dendy,
+        }
+    }
+
+    /// Size of the RAM at $6000-$7FFF (volatile + battery backed), at least the 8 KB window
+    pub fn prg_ram_len(&self) -> usize {
+        (self.prg_ram_size + self.prg_nvram_size).max(0x2000)
+    }
+
+    /// Size of the CHR RAM, 8 KB when the board has no CHR ROM but the header doesn't tell
+    pub fn chr_ram_len(&self) -> usize {
+        let len = self.chr_ram_size + self.chr_nvram_size;
+        if len == 0 && self.chr_rom_size == 0 { 0x2000 } else { len }
+    }
+}
+
+/// ROM size in bytes from its LSB byte and MSB nibble, `unit` is 16 KB for the PRG and 8 KB for the CHR
+fn rom_size(lsb: u8, msb: u8, unit: usize) -> usize {
+    if msb == 0x0F {
+        let exponent = (lsb >> 2) as u32;
+        let multiplier = (lsb & 0x03) as usize * 2 + 1;
+        2usize.saturating_pow(exponent).saturating_mul(multiplier)
+    } else {
+        (((msb as usize) << 8) | lsb as usize) * unit
+    }
+}
+
+fn shift_size(shift: u8) -> usize {
+    if shift == 0 { 0 } else { 64 << shift }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ines_header() -> [u8; 16] {
+        // 2 x 16 KB PRG, 1 x 8 KB CHR, mapper 4, vertical, battery
+        [0x4E, 0x45, 0x53, 0x1A, 2, 1, 0x43, 0x00, 0, 0, 0, 0, 0, 0, 0, 0]
+    }
+
+    #[test]
+    fn parses_ines() {
+        let header = RomHeader::parse(&ines_header());
+        assert_eq!(header.format, HeaderFormat::INes);
+        assert_eq!(header.mapper, 4);
+        assert_eq!(header.prg_rom_size, 0x8000);
+        assert_eq!(header.chr_rom_size, 0x2000);
+        assert_eq!(header.mirroring, Mirroring::Vertical);
+        assert!(header.has_battery);
+        assert_eq!(header.prg_nvram_size, 0x2000);
+        assert_eq!(header.region(), Region::Ntsc);
+    }
+
+    #[test]
+    fn parses_nes2() {
+        let mut bytes = ines_header();
+        bytes[7] = 0x18;       // NES 2.0, mapper D4..D7 = 1
+        bytes[8] = 0x21;       // submapper 2, mapper D8..D11 = 1
+        bytes[9] = 0x10;       // CHR MSB 1
+        bytes[10] = 0x70;      // 8 KB NVRAM
+        bytes[11] = 0x07;      // 8 KB CHR RAM
+        bytes[12] = 0x03;      // Dendy
+        let header = RomHeader::parse(&bytes);
+
+        assert_eq!(header.format, HeaderFormat::Nes2);
+        assert_eq!(header.mapper, 0x114);
+        assert_eq!(header.submapper, 2);
+        assert_eq!(header.chr_rom_size, 0x101 * 0x2000);
+        assert_eq!(header.prg_ram_size, 0);
+        assert_eq!(header.prg_nvram_size, 0x2000);
+        assert_eq!(header.chr_ram_size, 0x2000);
+        assert_eq!(header.region(), Region::Dendy);
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        assert!(matches!(RomHeader::from_rom(&[0x4E, 0x45, 0x53]), Err(RomLoadError::TooShort { found: 3 })));
+
+        let mut bytes = ines_header();
+        bytes[0] = b'X';
+        assert!(matches!(RomHeader::from_rom(&bytes), Err(RomLoadError::BadMagic)));
+    }
+
+    #[test]
+    fn loader_checks_the_lengths() {
+        use crate::memory::bus::load_rom;
+        let path = std::path::Path::new("test.nes");
+
+        let mut rom = ines_header().to_vec();
+        rom.resize(16 + 0x8000 + 0x1000, 0);
+        assert!(matches!(
+            load_rom(&rom, path),
+            Err(RomLoadError::SizeMismatch { expected: 0xA010, found: 0x9010 })
+        ));
+
+        rom.resize(16 + 0x8000 + 0x2000, 0);
+        rom[6] = 0xF0;
+        assert!(matches!(load_rom(&rom, path), Err(RomLoadError::UnsupportedMapper { mapper: 15, submapper: 0 })));
+
+        rom[4] = 0;
+        assert!(matches!(load_rom(&rom, path), Err(RomLoadError::NoPrgRom)));
+    }
+
+    #[test]
+    fn loader_checks_the_bank_sizes() {
+        use crate::memory::bus::load_rom;
+        let path = std::path::Path::new("test.nes");
+
+        // NES 2.0 with a PRG ROM of 2^10 * 3 bytes
+        let mut rom = ines_header().to_vec();
+        rom[4] = 0b0010_1001;
+        rom[5] = 0;
+        rom[7] = 0x08;
+        rom[9] = 0x0F;
+        rom.resize(16 + 3072, 0);
+        assert!(matches!(load_rom(&rom, path), Err(RomLoadError::UnsupportedRomSize { prg_rom: 3072, chr_rom: 0 })));
+
+        // 16 KB of PRG ROM and 4 KB of CHR ROM
+        rom[4] = 0x01;
+        rom[5] = 0b0011_0000;
+        rom[9] = 0xF0;
+        rom.resize(16 + 0x4000 + 0x1000, 0);
+        assert!(matches!(load_rom(&rom, path), Err(RomLoadError::UnsupportedRomSize { prg_rom: 0x4000, chr_rom: 0x1000 })));
+    }
+
+    #[test]
+    fn exponent_multiplier_sizes() {
+        // 2^10 * (1 * 2 + 1)
+        assert_eq!(rom_size(0b0010_1001, 0x0F, 0x4000), 3072);
+        assert_eq!(rom_size(0x02, 0x01, 0x4000), 0x102 * 0x4000);
+    }
+}
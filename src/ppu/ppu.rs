@@ -1,8 +1,9 @@
 use crate::{memory::mapper_base::*};
+use crate::engine::region::Region;
+use crate::engine::save_state::{SaveStateError, StateReader, StateWriter};
 
 use core::panic;
-use std::{rc::Rc, vec};
-use std::cell::RefCell;
+use std::vec;
 
 use super::{
     ppuaddr::PPUAddress,
@@ -12,8 +13,8 @@ use super::{
     registers::{ PpuCtrlFlags, PpuStatusFlags, PpuMaskFlags },
 };
 
-const SCREEN_WIDTH: usize = 256;
-const SCREEN_HEIGHT: usize = 240;
+pub const SCREEN_WIDTH: usize = 256;
+pub const SCREEN_HEIGHT: usize = 240;
 
 
 //todo! maybe get rid of this code or find some way to implement it(in a more optmized way since calling this every single clock of the ppu isnt great)
@@ -86,12 +87,21 @@ pub struct PPU {
     sprite0_hit_possible:   bool,
 
     pub color_palette: PaletteTheme,
+
+    /// Length of the frame and of the vblank
+    pub region: Region,
 }
 
 
 
+impl Default for PPU {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl PPU {
-    pub fn new(mapper: Rc<RefCell<dyn Mapper>>) -> Self {
+    pub fn new() -> Self {
         PPU { 
             cycle: 0, 
             scanline: 0, 
@@ -101,7 +111,7 @@ impl PPU {
 
             //state:      State::Visible,
 
-            ppubus:     PPUBUS::new(mapper),
+            ppubus:     PPUBUS::new(),
 
             nmi_occurred: false,
 
@@ -134,10 +144,11 @@ impl PPU {
             sprite0_hit_possible: false,
             
             color_palette: PaletteTheme::DefaultNtsc,
+            region: Region::Ntsc,
         }
     }
 
-    pub fn peek(&self, addr: u16) -> u8 {
+    pub fn peek(&self, addr: u16, mapper: &dyn Mapper) -> u8 {
         match addr & 0x07 {
             // PPUCTRL, PPUMASK, OAMADDR, PPUADDR: 
             0x00 | 0x01 | 0x03 | 0x06 => self.data_buffer,
@@ -157,7 +168,7 @@ impl PPU {
             0x07 => {
                 let addr = self.v.addr & 0x3FFF;
                 if addr >= 0x3F00 {
-                    self.ppubus.peek(addr)
+                    self.ppubus.peek(addr, mapper)
                 } else {
                     self.data_buffer
                 }
@@ -168,7 +179,7 @@ impl PPU {
     }
 
     /// https://www.nesdev.org/wiki/PPU_registers#Summary
-    pub fn read_registers(&mut self, addr: u8) -> u8 {
+    pub fn read_registers(&mut self, addr: u8, mapper: &mut dyn Mapper) -> u8 {
         match addr & 0x07 {
 
             0x02 => {
@@ -188,7 +199,7 @@ impl PPU {
                 //reading is delayed by a cycle, it returns the old buffer and loads next one
 
                 let data = self.data_buffer;
-                self.data_buffer = self.ppubus.read_ppubus(self.v.addr);
+                self.data_buffer = self.ppubus.read_ppubus(self.v.addr, mapper);
 
                 // EXCEPTION: palette ram has no delay, it discards the old addr and returns the current one
                 let result = if self.v.addr >= 0x3F00 {
@@ -212,7 +223,7 @@ impl PPU {
     }
 
     /// https://www.nesdev.org/wiki/PPU_registers#Summary
-    pub fn write_registers(&mut self, addr: u16, val: u8) -> bool {
+    pub fn write_registers(&mut self, addr: u16, val: u8, mapper: &mut dyn Mapper) -> bool {
         match addr & 0x07 {
             0x00 => {
                 let nmi_before = self.ctrl.generate_vblank_nmi();
@@ -267,7 +278,7 @@ impl PPU {
             }
             0x07 => {
 
-                self.ppubus.write_ppubus(self.v.addr, val);
+                self.ppubus.write_ppubus(self.v.addr, val, mapper);
                 if self.ctrl.contains(PpuCtrlFlags::IncrementVRAM) {
                     self.v.addr = self.v.addr.wrapping_add(32)
                 } else {
@@ -280,19 +291,32 @@ impl PPU {
         false
     }
 
-    pub fn tick(&mut self, cycles: u16) -> bool {
+    /// Runs `cycles` dots, the cartridge is lent by the CPU bus for the pattern and nametable fetches
+    pub fn tick(&mut self, cycles: u32, mapper: &mut dyn Mapper) -> bool {
         for _ in 0..cycles {
-            self.clock();
+            self.clock(mapper);
         }
         self.frame_complete
     }
 
+    /// How many dots can run before the next event the CPU sees without touching the registers:
+    /// the vblank flag (and the NMI) being set, and the end of the frame
+    pub fn dots_until_next_event(&self) -> u32 {
+        let position = |scanline: i16, cycle: u16| (scanline as i32 + 1) as u32 * 341 + cycle as u32;
+        let now = position(self.scanline, self.cycle);
+        let vblank = position(self.region.vblank_scanline(), 1);
+        let frame_end = position(self.region.last_scanline(), 340);
+
+        // counting the dot of the event itself
+        if now <= vblank { vblank - now + 1 } else { frame_end - now + 1 }
+    }
+
     #[inline(always)]
-    fn clock(&mut self) {
+    fn clock(&mut self, mapper: &mut dyn Mapper) {
         match self.scanline {
-            -1 | 0..=239 => self.render_scanline(),
-            240           => {} // idle
-            241           => {
+            -1..=239 => self.render_scanline(mapper),
+            // idle (240 up to the end of the frame), the vblank starts at 241 (291 on the Dendy)
+            scanline if scanline == self.region.vblank_scanline() => {
                 if self.cycle == 1 {
                     self.status.insert(PpuStatusFlags::VblankFlag);
                     if self.ctrl.generate_vblank_nmi() {
@@ -300,7 +324,7 @@ impl PPU {
                     }
                 }
             }
-            _             => {} // scanline 241 - 260
+            _             => {}
         }
         self.increase_cycle();
     }
@@ -310,17 +334,17 @@ impl PPU {
         if self.cycle > 340 {
             self.cycle = 0;
             self.scanline += 1;
-            if self.scanline > 260 {
+            if self.scanline > self.region.last_scanline() {
                 self.scanline = -1;
                 self.frame_complete = true;
             }
         }
     }
     #[inline]
-    fn render_scanline(&mut self) {
-        self.render_scanline_background();
+    fn render_scanline(&mut self, mapper: &mut dyn Mapper) {
+        self.render_scanline_background(mapper);
         if self.cycle == 257 && self.scanline >= 0 {
-            self.render_scanline_sprites();
+            self.render_scanline_sprites(mapper);
         }
 
         let is_prerender = self.scanline == -1;
@@ -341,13 +365,13 @@ impl PPU {
         }
 
         if is_visible && self.cycle >= 1 && self.cycle <= 256 {
-            self.render_pixel();
+            self.render_pixel(mapper);
         }
     }
     #[inline]
-    fn render_scanline_sprites(&mut self) {
+    fn render_scanline_sprites(&mut self, mapper: &mut dyn Mapper) {
         self.evaluate_sprites();
-        self.load_sprite_shifters();
+        self.load_sprite_shifters(mapper);
     }
     #[inline]
     fn evaluate_sprites(&mut self) {
@@ -384,7 +408,7 @@ impl PPU {
         self.sprite_shifter_hi = [0; 8];
     }
     #[inline]
-    fn render_scanline_background(&mut self) { 
+    fn render_scanline_background(&mut self, mapper: &mut dyn Mapper) { 
         let in_fetch_range = (self.cycle >= 1 && self.cycle <= 256)
         || (self.cycle >= 321 && self.cycle <= 336); // fetch tile range (cycles 1-256 and 321-336)
 
@@ -396,20 +420,20 @@ impl PPU {
             match (self.cycle - 1) % 8 {
                 0 => {
                     self.load_background_shifters();
-                    self.bg_next_tile_id = self.ppubus.read_ppubus(self.v.get_nametable_addr())
+                    self.bg_next_tile_id = self.ppubus.read_ppubus(self.v.get_nametable_addr(), mapper)
                 } //reads tiles from nametable (which tile to draw)
                 2 => {
-                    let attr = self.ppubus.read_ppubus(self.v.get_attribute_addr());
+                    let attr = self.ppubus.read_ppubus(self.v.get_attribute_addr(), mapper);
                     let shift = ((self.v.get_coarse_y() & 0b10) << 1) | (self.v.get_coarse_x() & 0b10);
                     self.bg_next_tile_attr = (attr >> shift) & 0b11;
                 } //reads atribute (which palete to use)
                 4 => {
                     let addr = self.v.get_pattern_table_addr(self.ctrl, self.bg_next_tile_id);
-                    self.bg_next_tile_lo = self.ppubus.read_ppubus(addr);
+                    self.bg_next_tile_lo = self.ppubus.read_ppubus(addr, mapper);
                 } //reads low bit plane   (pattern table, plane 0)
                 6 => {
                     let addr = self.v.get_pattern_table_addr(self.ctrl, self.bg_next_tile_id) + 8;
-                    self.bg_next_tile_hi = self.ppubus.read_ppubus(addr);
+                    self.bg_next_tile_hi = self.ppubus.read_ppubus(addr, mapper);
                 }
                 7 => {
                     if rendering_enabled {
@@ -446,7 +470,7 @@ impl PPU {
     }
 
     #[inline]
-    fn load_sprite_shifters(&mut self) {
+    fn load_sprite_shifters(&mut self, mapper: &mut dyn Mapper) {
         let sprite_size_16 = self.ctrl.contains(PpuCtrlFlags::SpriteSize);
 
         for i in 0..8 {
@@ -476,15 +500,15 @@ impl PPU {
                 };
 
                 let addr = pattern_base + tile * 16 + tile_row as u16;
-                self.sprite_shifter_lo[i] = self.ppubus.read_ppubus(addr);
-                self.sprite_shifter_hi[i] = self.ppubus.read_ppubus(addr + 8);
+                self.sprite_shifter_lo[i] = self.ppubus.read_ppubus(addr, mapper);
+                self.sprite_shifter_hi[i] = self.ppubus.read_ppubus(addr + 8, mapper);
                 
             } else {
                 let bank: u16 = if self.ctrl.contains(PpuCtrlFlags::SpritePattern) { 0x1000 } else { 0x0000 };
                 let dummy_addr = bank | 0x0FF0; 
                 
-                self.ppubus.read_ppubus(dummy_addr);
-                self.ppubus.read_ppubus(dummy_addr + 8);
+                self.ppubus.read_ppubus(dummy_addr, mapper);
+                self.ppubus.read_ppubus(dummy_addr + 8, mapper);
             }
         }
     }
@@ -499,7 +523,7 @@ impl PPU {
         }
     }
     #[inline]
-    fn render_pixel(&mut self) {
+    fn render_pixel(&mut self, mapper: &mut dyn Mapper) {
         let mux: u16 = 0x8000 >> self.fine_x;
         let x = (self.cycle - 1) as u8;
 
@@ -575,7 +599,7 @@ impl PPU {
             0x3F00 | (final_palette as u16) << 2 | final_pixel as u16
         };
 
-        let color_idx = (self.ppubus.read_ppubus(palette_addr) as usize) & 0x3F;
+        let color_idx = (self.ppubus.read_ppubus(palette_addr, mapper) as usize) & 0x3F;
         let color = self.color_palette.get_collors()[color_idx];
 
         let x = (self.cycle - 1) as usize;
@@ -592,6 +616,87 @@ impl PPU {
     pub fn oam_dma_write(&mut self, data: &[u8; 256]) {
         self.oam.copy_from_slice(data);
     }
+
+    /// The frame buffer and the color palette are not saved,
+    /// the first frame rendered after loading redraws everything anyway
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_u16(self.cycle);
+        w.write_i16(self.scanline);
+        w.write_bool(self.frame_complete);
+        w.write_bool(self.nmi_occurred);
+
+        w.write_u8(self.ctrl.bits());
+        w.write_u8(self.mask.bits());
+        w.write_u8(self.status.bits());
+        w.write_u8(self.data_buffer);
+        w.write_bytes(&self.oam);
+
+        w.write_u16(self.v.addr);
+        w.write_u16(self.t.addr);
+        w.write_u8(self.fine_x);
+        w.write_bool(self.w);
+
+        w.write_u8(self.bg_next_tile_id);
+        w.write_u8(self.bg_next_tile_attr);
+        w.write_u8(self.bg_next_tile_lo);
+        w.write_u8(self.bg_next_tile_hi);
+
+        w.write_u16(self.bg_shift_lo);
+        w.write_u16(self.bg_shift_hi);
+        w.write_u16(self.bg_attr_shift_lo);
+        w.write_u16(self.bg_attr_shift_hi);
+
+        for (y, tile_id, attributes, x) in self.sprite_scanline {
+            w.write_u8(y);
+            w.write_u8(tile_id);
+            w.write_u8(attributes);
+            w.write_u8(x);
+        }
+        w.write_u8(self.sprite_count as u8);
+        w.write_bytes(&self.sprite_shifter_lo);
+        w.write_bytes(&self.sprite_shifter_hi);
+        w.write_bool(self.sprite0_hit_possible);
+
+        self.ppubus.save_state(w);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.cycle = r.read_u16()?;
+        self.scanline = r.read_i16()?;
+        self.frame_complete = r.read_bool()?;
+        self.nmi_occurred = r.read_bool()?;
+
+        self.ctrl = PpuCtrlFlags::from_bits_truncate(r.read_u8()?);
+        self.mask = PpuMaskFlags::from_bits_truncate(r.read_u8()?);
+        self.status = PpuStatusFlags::from_bits_truncate(r.read_u8()?);
+        self.data_buffer = r.read_u8()?;
+        r.read_bytes_into(&mut self.oam)?;
+
+        self.v.addr = r.read_u16()?;
+        self.t.addr = r.read_u16()?;
+        self.fine_x = r.read_u8()?;
+        self.w = r.read_bool()?;
+
+        self.bg_next_tile_id = r.read_u8()?;
+        self.bg_next_tile_attr = r.read_u8()?;
+        self.bg_next_tile_lo = r.read_u8()?;
+        self.bg_next_tile_hi = r.read_u8()?;
+
+        self.bg_shift_lo = r.read_u16()?;
+        self.bg_shift_hi = r.read_u16()?;
+        self.bg_attr_shift_lo = r.read_u16()?;
+        self.bg_attr_shift_hi = r.read_u16()?;
+
+        for sprite in self.sprite_scanline.iter_mut() {
+            *sprite = (r.read_u8()?, r.read_u8()?, r.read_u8()?, r.read_u8()?);
+        }
+        self.sprite_count = (r.read_u8()? as usize).min(8);
+        r.read_bytes_into(&mut self.sprite_shifter_lo)?;
+        r.read_bytes_into(&mut self.sprite_shifter_hi)?;
+        self.sprite0_hit_possible = r.read_bool()?;
+
+        self.ppubus.load_state(r)
+    }
 }
 
 mod write_registers_tests {
@@ -599,22 +704,22 @@ mod write_registers_tests {
     use crate::memory::mappers::dummy_mapper::TestMapper;
 
     #[allow(unused)]
-    fn make_ppu() -> PPU {
-        PPU::new(TestMapper::new(vec![], Mirroring::Horizontal))
+    fn make_ppu() -> (PPU, Box<dyn Mapper>) {
+        (PPU::new(), TestMapper::boxed(vec![], Mirroring::Horizontal))
     }
 
     #[test]
     fn write_ppuctrl_updates_nametable_in_t() {
-        let mut ppu = make_ppu();
-        ppu.write_registers(0x00, 0b00000011); // nametable = 3
+        let (mut ppu, mut mapper) = make_ppu();
+        ppu.write_registers(0x00, 0b00000011, &mut *mapper); // nametable = 3
         assert_eq!((ppu.t.addr >> 10) & 0x03, 3);
     }
 
     #[test]
     fn write_ppuscroll_first_write_updates_fine_x_and_coarse_x() {
-        let mut ppu = make_ppu();
+        let (mut ppu, mut mapper) = make_ppu();
         // val = 0b00101_011 -> coarse_x = 5, fine_x = 3
-        ppu.write_registers(0x05, 0b00101_011);
+        ppu.write_registers(0x05, 0b00101_011, &mut *mapper);
         assert_eq!(ppu.fine_x, 3);
         assert_eq!(ppu.t.get_coarse_x(), 5);
         assert!(ppu.w);
@@ -622,9 +727,9 @@ mod write_registers_tests {
 
     #[test]
     fn write_ppuscroll_second_write_updates_coarse_y_and_fine_y() {
-        let mut ppu = make_ppu();
-        ppu.write_registers(0x05, 0b00101_011); // first
-        ppu.write_registers(0x05, 0b01000_110); // second: coarse_y = 8, fine_y = 6
+        let (mut ppu, mut mapper) = make_ppu();
+        ppu.write_registers(0x05, 0b00101_011, &mut *mapper); // first
+        ppu.write_registers(0x05, 0b01000_110, &mut *mapper); // second: coarse_y = 8, fine_y = 6
         assert_eq!(ppu.t.get_coarse_y(), 8);
         assert_eq!(ppu.t.get_fine_y(), 6);
         assert!(!ppu.w);
@@ -632,35 +737,35 @@ mod write_registers_tests {
 
     #[test]
     fn write_ppuaddr_clears_bit_15() {
-        let mut ppu = make_ppu();
-        ppu.write_registers(0x06, 0xFF);
-        ppu.write_registers(0x06, 0x00);
+        let (mut ppu, mut mapper) = make_ppu();
+        ppu.write_registers(0x06, 0xFF, &mut *mapper);
+        ppu.write_registers(0x06, 0x00, &mut *mapper);
         assert_eq!(ppu.v.addr & 0x8000, 0); // bit 15 must be zero
     }
 
     #[test]
     fn write_ppuaddr_two_writes_update_v() {
-        let mut ppu = make_ppu();
-        ppu.write_registers(0x06, 0x21); // high byte
-        ppu.write_registers(0x06, 0x00); // low byte -> v = $2100
+        let (mut ppu, mut mapper) = make_ppu();
+        ppu.write_registers(0x06, 0x21, &mut *mapper); // high byte
+        ppu.write_registers(0x06, 0x00, &mut *mapper); // low byte -> v = $2100
         assert_eq!(ppu.v.addr, 0x2100);
     }
     #[test]
     fn write_ppudata_increments_v_by_1_by_default() {
-        let mut ppu = make_ppu();
-        ppu.write_registers(0x06, 0x20);
-        ppu.write_registers(0x06, 0x00);
-        ppu.write_registers(0x07, 0xAB);
+        let (mut ppu, mut mapper) = make_ppu();
+        ppu.write_registers(0x06, 0x20, &mut *mapper);
+        ppu.write_registers(0x06, 0x00, &mut *mapper);
+        ppu.write_registers(0x07, 0xAB, &mut *mapper);
         assert_eq!(ppu.v.addr, 0x2001);
     }
 
     #[test]
     fn write_ppudata_increments_v_by_32_when_flag_set() {
-        let mut ppu = make_ppu();
-        ppu.write_registers(0x00, 0b00000100); // IncrementVRAM
-        ppu.write_registers(0x06, 0x20);
-        ppu.write_registers(0x06, 0x00);
-        ppu.write_registers(0x07, 0xAB);
+        let (mut ppu, mut mapper) = make_ppu();
+        ppu.write_registers(0x00, 0b00000100, &mut *mapper); // IncrementVRAM
+        ppu.write_registers(0x06, 0x20, &mut *mapper);
+        ppu.write_registers(0x06, 0x00, &mut *mapper);
+        ppu.write_registers(0x07, 0xAB, &mut *mapper);
         assert_eq!(ppu.v.addr, 0x2020);
     }
 }
@@ -669,50 +774,50 @@ mod read_registers_tests {
     use crate::memory::mappers::dummy_mapper::TestMapper;
 
     #[allow(unused)]
-    fn make_ppu() -> PPU {
-        PPU::new(TestMapper::new(vec![], Mirroring::Horizontal))
+    fn make_ppu() -> (PPU, Box<dyn Mapper>) {
+        (PPU::new(), TestMapper::boxed(vec![], Mirroring::Horizontal))
     }
 
     #[test]
     fn read_ppustatus_returns_vblank_flag() {
-        let mut ppu = make_ppu();
+        let (mut ppu, mut mapper) = make_ppu();
         ppu.status.insert(PpuStatusFlags::VblankFlag);
-        let val = ppu.read_registers(0x02);
+        let val = ppu.read_registers(0x02, &mut *mapper);
         assert!(val & 0x80 != 0);
     }
 
     #[test]
     fn read_ppustatus_clears_vblank_flag() {
-        let mut ppu = make_ppu();
+        let (mut ppu, mut mapper) = make_ppu();
         ppu.status.insert(PpuStatusFlags::VblankFlag);
-        ppu.read_registers(0x02);
+        ppu.read_registers(0x02, &mut *mapper);
         assert!(!ppu.status.contains(PpuStatusFlags::VblankFlag));
     }
 
     #[test]
     fn read_ppustatus_resets_write_latch() {
-        let mut ppu = make_ppu();
+        let (mut ppu, mut mapper) = make_ppu();
         ppu.w = true;
-        ppu.read_registers(0x02);
+        ppu.read_registers(0x02, &mut *mapper);
         assert!(!ppu.w);
     }
 
     #[test]
     fn read_ppudata_is_buffered() {
-        let mut ppu = make_ppu();
+        let (mut ppu, mut mapper) = make_ppu();
         // writes something in VRAM at $2000
-        ppu.write_registers(0x06, 0x20);
-        ppu.write_registers(0x06, 0x00);
-        ppu.write_registers(0x07, 0xAB);
+        ppu.write_registers(0x06, 0x20, &mut *mapper);
+        ppu.write_registers(0x06, 0x00, &mut *mapper);
+        ppu.write_registers(0x07, 0xAB, &mut *mapper);
 
         // resets addr to $2000
-        ppu.write_registers(0x06, 0x20);
-        ppu.write_registers(0x06, 0x00);
+        ppu.write_registers(0x06, 0x20, &mut *mapper);
+        ppu.write_registers(0x06, 0x00, &mut *mapper);
 
         // first read: returns buffer's trash (no data yet)
-        let first = ppu.read_registers(0x07);
+        let first = ppu.read_registers(0x07, &mut *mapper);
         //second read: now returns 0xAB
-        let second = ppu.read_registers(0x07);
+        let second = ppu.read_registers(0x07, &mut *mapper);
 
         assert_ne!(first, 0xAB);
         assert_eq!(second, 0xAB);
@@ -720,35 +825,92 @@ mod read_registers_tests {
 
     #[test]
     fn read_ppudata_palette_has_no_delay() {
-        let mut ppu = make_ppu();
+        let (mut ppu, mut mapper) = make_ppu();
 
-        ppu.write_registers(0x06, 0x3F);
-        ppu.write_registers(0x06, 0x05);
-        ppu.write_registers(0x07, 0x15);
+        ppu.write_registers(0x06, 0x3F, &mut *mapper);
+        ppu.write_registers(0x06, 0x05, &mut *mapper);
+        ppu.write_registers(0x07, 0x15, &mut *mapper);
 
-        ppu.write_registers(0x06, 0x3F);
-        ppu.write_registers(0x06, 0x05);
+        ppu.write_registers(0x06, 0x3F, &mut *mapper);
+        ppu.write_registers(0x06, 0x05, &mut *mapper);
 
-        let val = ppu.read_registers(0x07);
+        let val = ppu.read_registers(0x07, &mut *mapper);
         assert_eq!(val, 0x15);
     }
 
     #[test]
     fn read_ppudata_increments_v_by_1() {
-        let mut ppu = make_ppu();
-        ppu.write_registers(0x06, 0x20);
-        ppu.write_registers(0x06, 0x00);
-        ppu.read_registers(0x07);
+        let (mut ppu, mut mapper) = make_ppu();
+        ppu.write_registers(0x06, 0x20, &mut *mapper);
+        ppu.write_registers(0x06, 0x00, &mut *mapper);
+        ppu.read_registers(0x07, &mut *mapper);
         assert_eq!(ppu.v.addr, 0x2001);
     }
 
     #[test]
     fn read_ppudata_increments_v_by_32_when_flag_set() {
-        let mut ppu = make_ppu();
-        ppu.write_registers(0x00, 0b00000100); // IncrementVRAM
-        ppu.write_registers(0x06, 0x20);
-        ppu.write_registers(0x06, 0x00);
-        ppu.read_registers(0x07);
+        let (mut ppu, mut mapper) = make_ppu();
+        ppu.write_registers(0x00, 0b00000100, &mut *mapper); // IncrementVRAM
+        ppu.write_registers(0x06, 0x20, &mut *mapper);
+        ppu.write_registers(0x06, 0x00, &mut *mapper);
+        ppu.read_registers(0x07, &mut *mapper);
         assert_eq!(ppu.v.addr, 0x2020);
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod timing_tests {
+    use super::*;
+    use crate::memory::mappers::dummy_mapper::TestMapper;
+
+    fn dots_per_frame(region: Region) -> (u32, i16) {
+        let mut ppu = PPU::new();
+        let mut mapper = TestMapper::boxed(vec![], Mirroring::Horizontal);
+        ppu.region = region;
+        // the power up frame starts at scanline 0, not at the pre-render line
+        while !ppu.frame_complete {
+            ppu.tick(1, &mut *mapper);
+        }
+        ppu.frame_complete = false;
+
+        let mut dots = 0;
+        let mut vblank_scanline = 0;
+        while !ppu.frame_complete {
+            let was_in_vblank = ppu.status.contains(PpuStatusFlags::VblankFlag);
+            ppu.tick(1, &mut *mapper);
+            dots += 1;
+            if !was_in_vblank && ppu.status.contains(PpuStatusFlags::VblankFlag) {
+                vblank_scanline = ppu.scanline;
+            }
+        }
+        (dots, vblank_scanline)
+    }
+
+    #[test]
+    fn frame_length_depends_on_the_region() {
+        assert_eq!(dots_per_frame(Region::Ntsc), (341 * 262, 241));
+        assert_eq!(dots_per_frame(Region::Pal), (341 * 312, 241));
+        assert_eq!(dots_per_frame(Region::Dendy), (341 * 312, 291));
+    }
+
+    #[test]
+    fn next_event_is_the_vblank_or_the_frame_end() {
+        for region in [Region::Ntsc, Region::Pal, Region::Dendy] {
+            let mut ppu = PPU::new();
+        let mut mapper = TestMapper::boxed(vec![], Mirroring::Horizontal);
+            ppu.region = region;
+            ppu.ctrl.insert(PpuCtrlFlags::VblankNMI);
+
+            for _ in 0..4 {
+                let dots = ppu.dots_until_next_event();
+                ppu.tick(dots - 1, &mut *mapper);
+                assert!(!ppu.nmi_occurred && !ppu.frame_complete, "{:?} at {}:{}", region, ppu.scanline, ppu.cycle);
+
+                ppu.tick(1, &mut *mapper);
+                assert!(ppu.nmi_occurred ^ ppu.frame_complete, "{:?} at {}:{}", region, ppu.scanline, ppu.cycle);
+                ppu.nmi_occurred = false;
+                ppu.frame_complete = false;
+            }
+        }
+    }
+}
@@ -1,24 +1,32 @@
 use crate::memory::mapper_base::*;
+use crate::engine::save_state::{SaveStateError, StateReader, StateWriter};
 
-use std::rc::Rc; // Importe Rc
-use std::cell::RefCell;
 
+/// The PPU memory map: the palettes and the console's 2 KB of VRAM
+///
+/// The cartridge isn't kept here, it belongs to the CPU bus ([`crate::memory::bus::BUS`])
+/// and is lent to every access that can reach it (the pattern tables and the nametables)
 pub struct PPUBUS {
     //32 byte pallete [16 for backgroudn 16 for foreground]
     palette_ram: [u8; 0x20],
-    vram: [u8; 0x0800], 
-    pub mapper: Rc<RefCell<dyn Mapper>>, // 2KB VRAM
+    vram: [u8; 0x0800], // 2KB VRAM
 }
+
+impl Default for PPUBUS {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl PPUBUS {
-    pub fn new(mapper: Rc<RefCell<dyn Mapper>>) -> PPUBUS {
+    pub fn new() -> PPUBUS {
         PPUBUS {
             palette_ram: [0; 0x20],
             vram: [0; 0x0800],
-            mapper,
         }
     }
 
-    pub fn peek(&self, addr: u16) -> u8 {
+    pub fn peek(&self, addr: u16, mapper: &dyn Mapper) -> u8 {
         let addr =  addr & 0x3FFF;
         match addr {
             0x3F00..=0x3FFF => {
@@ -32,10 +40,10 @@ impl PPUBUS {
                 
             }
             0..=0x1FFF => {
-                self.mapper.borrow().read_chr(addr)
+                mapper.read_chr(addr)
             }
             0x2000..=0x3EFF  => {
-                self.read_vram(addr)
+                self.read_vram(addr, mapper)
             }
             _ => {
                 todo!();
@@ -43,16 +51,16 @@ impl PPUBUS {
         }     
     }
 
-    pub fn write_ppubus(&mut self, addr: u16, data: u8) {
+    pub fn write_ppubus(&mut self, addr: u16, data: u8, mapper: &mut dyn Mapper) {
         let addr =  addr & 0x3FFF;
         match addr {
 
             0..=0x1FFF => {
-                self.mapper.borrow_mut().write_chr(addr, data);
+                mapper.write_chr(addr, data);
             }
             //VRAM (or nametable)
             0x2000..=0x3EFF  => {
-                self.write_vram(addr, data);
+                self.write_vram(addr, data, mapper);
             }
             0x3F00..=0x3FFF => {
                 //mirroring the last addr of the palletes
@@ -71,11 +79,11 @@ impl PPUBUS {
     }
     
     //TODO remover a referencia mutavel e limpar esse codigo depois que funcionar
-    pub fn read_ppubus(&mut self, addr: u16) -> u8 {
+    pub fn read_ppubus(&mut self, addr: u16, mapper: &mut dyn Mapper) -> u8 {
         let addr =  addr & 0x3FFF;
 
         if addr < 0x3F00 {
-            self.mapper.borrow_mut().notify_ppu_address(addr);
+            mapper.notify_ppu_address(addr);
         }
 
         match addr {
@@ -92,11 +100,11 @@ impl PPUBUS {
                 
             }
             0..=0x1FFF => {
-                self.mapper.borrow().read_chr(addr)
+                mapper.read_chr(addr)
             }
             //VRAM (or nametable)
             0x2000..=0x3EFF  => {
-                self.read_vram(addr)
+                self.read_vram(addr, mapper)
             }
             _ => {
                 todo!();
@@ -104,10 +112,12 @@ impl PPUBUS {
         }
     }
 
-    fn match_mirroring_addr(&self, addr: u16) -> usize {
+    fn match_mirroring_addr(mirroring: Mirroring, addr: u16) -> usize {
         let addr = (addr - 0x2000) & 0x0FFF;
-        match self.mapper.borrow().mirroring() {
+        match mirroring {
             Mirroring::Vertical => (addr & 0x07FF) as usize,
+            // only $2000 and $2400 use the console VRAM, the cartridge answers for the other two
+            Mirroring::FourScreen => (addr & 0x07FF) as usize,
             Mirroring::Horizontal => ((addr & 0x03FF) + ((addr & 0x0800) >> 1)) as usize,
             Mirroring::SingleScreenLower => (addr & 0x03FF) as usize,
             Mirroring::SingleScreenUpper => ((addr & 0x03FF) + 0x0400) as usize,
@@ -120,14 +130,32 @@ impl PPUBUS {
         }
     }
 
-    fn write_vram(&mut self, addr: u16, data: u8) {
-        let addr = self.match_mirroring_addr(addr);
+    fn write_vram(&mut self, addr: u16, data: u8, mapper: &mut dyn Mapper) {
+        let addr = 0x2000 | (addr & 0x0FFF);
+        if mapper.write_nametable(addr, data) {
+            return;
+        }
+        let addr = Self::match_mirroring_addr(mapper.mirroring(), addr);
         self.vram[addr] = data;
     }
 
-    fn read_vram(&self, addr: u16) -> u8 {
-        let addr = self.match_mirroring_addr(addr);
-        self.vram[addr]
+    fn read_vram(&self, addr: u16, mapper: &dyn Mapper) -> u8 {
+        let addr = 0x2000 | (addr & 0x0FFF);
+        if let Some(data) = mapper.read_nametable(addr) {
+            return data;
+        }
+        self.vram[Self::match_mirroring_addr(mapper.mirroring(), addr)]
+    }
+
+    /// CHR memory belongs to the cartridge, so it is saved by the mapper instead
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_bytes(&self.palette_ram);
+        w.write_bytes(&self.vram);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        r.read_bytes_into(&mut self.palette_ram)?;
+        r.read_bytes_into(&mut self.vram)
     }
 }
 
@@ -136,12 +164,28 @@ mod tests {
     use super::*;
     use crate::memory::mappers::dummy_mapper::TestMapper;
 
-    fn make_bus_horizontal() -> PPUBUS {
-        PPUBUS::new(TestMapper::new(vec![], Mirroring::Horizontal))
+    /// The PPU bus and the cartridge it reaches through
+    struct TestBus {
+        bus: PPUBUS,
+        mapper: Box<dyn Mapper>,
+    }
+
+    impl TestBus {
+        fn write_ppubus(&mut self, addr: u16, data: u8) {
+            self.bus.write_ppubus(addr, data, &mut *self.mapper)
+        }
+
+        fn read_ppubus(&mut self, addr: u16) -> u8 {
+            self.bus.read_ppubus(addr, &mut *self.mapper)
+        }
+    }
+
+    fn make_bus_horizontal() -> TestBus {
+        TestBus { bus: PPUBUS::new(), mapper: TestMapper::boxed(vec![], Mirroring::Horizontal) }
     }
     
-    fn make_bus_vertical() -> PPUBUS {
-        PPUBUS::new(TestMapper::new(vec![], Mirroring::Horizontal))
+    fn make_bus_vertical() -> TestBus {
+        TestBus { bus: PPUBUS::new(), mapper: TestMapper::boxed(vec![], Mirroring::Horizontal) }
     }
 
     // ── Palette RAM ──────────────────────────────────────────────────────
@@ -310,4 +354,28 @@ mod tests {
         bus.write_ppubus(0x2005, 0xAB);
         assert_eq!(bus.read_ppubus(0x3005), 0xAB);
     }
+
+    // ── VRAM — four-screen ──────────────────────────────────────────────
+    // A, B → VRAM do console | C, D → VRAM extra do cartucho
+
+    fn make_bus_four_screen() -> TestBus {
+        use crate::memory::{mappers::{FourScreenVram, InesMapper000}, rom_header::RomHeader};
+
+        let header = RomHeader::parse(&[0x4E, 0x45, 0x53, 0x1A, 1, 1, 0x08, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let mapper = InesMapper000::new(vec![0; 0x4000].into(), vec![0; 0x2000].into(), &header);
+        TestBus { bus: PPUBUS::new(), mapper: Box::new(FourScreenVram::new(mapper)) }
+    }
+
+    #[test]
+    fn vram_four_screen_nametables_independentes() {
+        let mut bus = make_bus_four_screen();
+        for (i, nametable) in [0x2000, 0x2400, 0x2800, 0x2C00].into_iter().enumerate() {
+            bus.write_ppubus(nametable + 5, i as u8 + 1);
+        }
+        for (i, nametable) in [0x2000, 0x2400, 0x2800, 0x2C00].into_iter().enumerate() {
+            assert_eq!(bus.read_ppubus(nametable + 5), i as u8 + 1);
+            // mirror de $3000–$3EFF
+            assert_eq!(bus.read_ppubus(nametable + 0x1005), i as u8 + 1);
+        }
+    }
 }
@@ -0,0 +1,256 @@
+//! Runs the accuracy test roms that report through the blargg `$6000` protocol
+//!
+//! ```text
+//! $6000        status: $80 still running, $81 needs the reset button to be pressed, $00-$7F final result (0 = passed)
+//! $6001-$6003  DE B0 61, tells that the status is valid
+//! $6004-       zero-terminated text with the result
+//! ```
+//! The roms aren't distributed with the emulator, put the suites in `tests/roms/` (see the readme there)
+//! or point `NES_TEST_ROMS` to a copy of the repository, and run the ignored tests:
+//! `cargo test --test blargg -- --ignored --nocapture`. A missing rom fails its suite.
+//! [`Expected`] is the state of the emulator on each rom, a rom marked as [`Expected::Pass`]
+//! that doesn't pass fails the test.
+//!
+//! https://github.com/christopherpow/nes-test-roms
+
+use std::path::PathBuf;
+
+use nes_emulator::{apu::audio::NullSink, engine::instance::EmulatorInstance};
+
+const ROMS_DIR: &str = "tests/roms";
+
+const SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+const STATUS_RUNNING: u8 = 0x80;
+const STATUS_NEEDS_RESET: u8 = 0x81;
+
+/// The slowest roms of the suites take around 25 seconds
+const MAX_FRAMES: u32 = 60 * 40;
+/// The roms ask for the button to be held at least 100 ms
+const RESET_DELAY_FRAMES: u32 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Expected {
+    Pass,
+    /// Known failure, it doesn't fail the test but passing it is reported so the table gets updated
+    Fail,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Outcome {
+    Passed,
+    Failed { code: u8, message: String },
+    /// The status never settled (or the rom never wrote the signature)
+    Timeout { message: String },
+    Halted { pc: u16 },
+}
+
+use Expected::*;
+
+const INSTR_TEST_V5: &[(&str, Expected)] = &[
+    ("instr_test-v5/rom_singles/01-basics.nes", Pass),
+    ("instr_test-v5/rom_singles/02-implied.nes", Pass),
+    ("instr_test-v5/rom_singles/03-immediate.nes", Pass),
+    ("instr_test-v5/rom_singles/04-zero_page.nes", Pass),
+    ("instr_test-v5/rom_singles/05-zp_xy.nes", Pass),
+    ("instr_test-v5/rom_singles/06-absolute.nes", Pass),
+    ("instr_test-v5/rom_singles/07-abs_xy.nes", Pass),
+    ("instr_test-v5/rom_singles/08-ind_x.nes", Pass),
+    ("instr_test-v5/rom_singles/09-ind_y.nes", Pass),
+    ("instr_test-v5/rom_singles/10-branches.nes", Pass),
+    ("instr_test-v5/rom_singles/11-stack.nes", Pass),
+    ("instr_test-v5/rom_singles/12-jmp_jsr.nes", Pass),
+    ("instr_test-v5/rom_singles/13-rts.nes", Pass),
+    ("instr_test-v5/rom_singles/14-rti.nes", Pass),
+    ("instr_test-v5/rom_singles/15-brk.nes", Pass),
+    ("instr_test-v5/rom_singles/16-special.nes", Pass),
+];
+
+const CPU_INTERRUPTS_V2: &[(&str, Expected)] = &[
+    ("cpu_interrupts_v2/rom_singles/1-cli_latency.nes", Fail),
+    ("cpu_interrupts_v2/rom_singles/2-nmi_and_brk.nes", Fail),
+    ("cpu_interrupts_v2/rom_singles/3-nmi_and_irq.nes", Fail),
+    ("cpu_interrupts_v2/rom_singles/4-irq_and_dma.nes", Fail),
+    ("cpu_interrupts_v2/rom_singles/5-branch_delays_irq.nes", Fail),
+];
+
+const PPU_VBL_NMI: &[(&str, Expected)] = &[
+    ("ppu_vbl_nmi/rom_singles/01-vbl_basics.nes", Pass),
+    ("ppu_vbl_nmi/rom_singles/02-vbl_set_time.nes", Fail),
+    ("ppu_vbl_nmi/rom_singles/03-vbl_clear_time.nes", Fail),
+    ("ppu_vbl_nmi/rom_singles/04-nmi_control.nes", Fail),
+    ("ppu_vbl_nmi/rom_singles/05-nmi_timing.nes", Fail),
+    ("ppu_vbl_nmi/rom_singles/06-suppression.nes", Fail),
+    ("ppu_vbl_nmi/rom_singles/07-nmi_on_timing.nes", Fail),
+    ("ppu_vbl_nmi/rom_singles/08-nmi_off_timing.nes", Fail),
+    ("ppu_vbl_nmi/rom_singles/09-even_odd_frames.nes", Fail),
+    ("ppu_vbl_nmi/rom_singles/10-even_odd_timing.nes", Fail),
+];
+
+const APU_TEST: &[(&str, Expected)] = &[
+    ("apu_test/rom_singles/1-len_ctr.nes", Pass),
+    ("apu_test/rom_singles/2-len_table.nes", Pass),
+    ("apu_test/rom_singles/3-irq_flag.nes", Fail),
+    ("apu_test/rom_singles/4-jitter.nes", Fail),
+    ("apu_test/rom_singles/5-len_timing.nes", Fail),
+    ("apu_test/rom_singles/6-irq_flag_timing.nes", Fail),
+    ("apu_test/rom_singles/7-dmc_basics.nes", Fail),
+    ("apu_test/rom_singles/8-dmc_rates.nes", Fail),
+];
+
+const MMC3_TEST: &[(&str, Expected)] = &[
+    ("mmc3_test_2/rom_singles/1-clocking.nes", Pass),
+    ("mmc3_test_2/rom_singles/2-details.nes", Pass),
+    ("mmc3_test_2/rom_singles/3-A12_clocking.nes", Pass),
+    ("mmc3_test_2/rom_singles/4-scanline_timing.nes", Fail),
+    ("mmc3_test_2/rom_singles/5-MMC3.nes", Pass),
+    ("mmc3_test_2/rom_singles/6-MMC6.nes", Fail),
+];
+
+/// Zero-terminated text from $6004
+fn read_message(emulator: &EmulatorInstance) -> String {
+    let mut message = Vec::new();
+    for addr in 0x6004..0x7000u16 {
+        let byte = emulator.cpu.bus.peek(addr);
+        if byte == 0 { break }
+        message.push(byte);
+    }
+    String::from_utf8_lossy(&message).trim().to_string()
+}
+
+fn has_signature(emulator: &EmulatorInstance) -> bool {
+    (0..3).all(|i| emulator.cpu.bus.peek(0x6001 + i) == SIGNATURE[i as usize])
+}
+
+/// Emulates until the rom reports a final result
+fn run_test_rom(mut emulator: EmulatorInstance) -> Outcome {
+    let mut reset_at = None;
+
+    for frame in 0..MAX_FRAMES {
+        emulator.run_frame(&mut NullSink);
+        if emulator.is_halted {
+            return Outcome::Halted { pc: emulator.cpu.program_counter };
+        }
+
+        if !has_signature(&emulator) { continue }
+
+        match emulator.cpu.bus.peek(0x6000) {
+            STATUS_RUNNING => {}
+            STATUS_NEEDS_RESET => match reset_at {
+                None => reset_at = Some(frame + RESET_DELAY_FRAMES),
+                Some(at) if frame >= at => {
+                    emulator.soft_reset();
+                    reset_at = None;
+                }
+                Some(_) => {}
+            },
+            0 => return Outcome::Passed,
+            code => return Outcome::Failed { code, message: read_message(&emulator) },
+        }
+    }
+    Outcome::Timeout { message: read_message(&emulator) }
+}
+
+fn roms_dir() -> PathBuf {
+    std::env::var("NES_TEST_ROMS").map(PathBuf::from).unwrap_or(PathBuf::from(ROMS_DIR))
+}
+
+/// Runs every rom of the table, prints a line for each and fails on regressions or missing roms
+fn run_suite(suite: &[(&str, Expected)]) {
+    let dir = roms_dir();
+    let missing: Vec<_> = suite.iter().map(|(rom, _)| *rom).filter(|rom| !dir.join(rom).exists()).collect();
+    assert!(missing.is_empty(), "test roms not found in {}: {:?}", dir.display(), missing);
+
+    let mut regressions = Vec::new();
+
+    for &(rom, expected) in suite {
+        let path = dir.join(rom);
+
+        let emulator = EmulatorInstance::new(PathBuf::from(&path))
+            .unwrap_or_else(|e| panic!("couldn't load {}: {}", rom, e));
+        let outcome = run_test_rom(emulator);
+
+        match (&outcome, expected) {
+            (Outcome::Passed, Pass) => println!("[PASS]    {}", rom),
+            (Outcome::Passed, Fail) => println!("[PASS]    {} (known failure is passing now, update the table)", rom),
+            (outcome, Fail) => println!("[FAIL]    {} (known failure) {:?}", rom, outcome),
+            (outcome, Pass) => {
+                println!("[FAIL]    {} {:?}", rom, outcome);
+                regressions.push(rom);
+            }
+        }
+    }
+
+    assert!(regressions.is_empty(), "test roms that used to pass are failing: {:?}", regressions);
+}
+
+#[test]
+#[ignore = "needs the test roms, see the module docs"]
+fn blargg_instr_test_v5() {
+    run_suite(INSTR_TEST_V5);
+}
+
+#[test]
+#[ignore = "needs the test roms, see the module docs"]
+fn blargg_cpu_interrupts_v2() {
+    run_suite(CPU_INTERRUPTS_V2);
+}
+
+#[test]
+#[ignore = "needs the test roms, see the module docs"]
+fn blargg_ppu_vbl_nmi() {
+    run_suite(PPU_VBL_NMI);
+}
+
+#[test]
+#[ignore = "needs the test roms, see the module docs"]
+fn blargg_apu_test() {
+    run_suite(APU_TEST);
+}
+
+#[test]
+#[ignore = "needs the test roms, see the module docs"]
+fn blargg_mmc3_test() {
+    run_suite(MMC3_TEST);
+}
+
+/// Mapper 0 rom that talks the protocol: asks for a reset, then reports `status` with the message "ok"
+fn protocol_rom(status: u8) -> Vec<u8> {
+    let sta = |val: u8, addr: u8| [0xA9, val, 0x8D, addr, 0x60]; // LDA #val, STA $60addr
+
+    let mut prg = vec![
+        0xAD, 0x00, 0x60,   // LDA $6000
+        0xC9, 0x81,         // CMP #$81
+        0xF0, 28,           // BEQ after_reset
+    ];
+    for (val, addr) in [(STATUS_RUNNING, 0), (0xDE, 1), (0xB0, 2), (0x61, 3), (STATUS_NEEDS_RESET, 0)] {
+        prg.extend(sta(val, addr));
+    }
+    let wait = 0xC000 + prg.len() as u16;
+    prg.extend([0x4C, wait as u8, (wait >> 8) as u8]);
+
+    // after_reset:
+    for (val, addr) in [(b'o', 4), (b'k', 5), (0, 6), (status, 0)] {
+        prg.extend(sta(val, addr));
+    }
+    let end = 0xC000 + prg.len() as u16;
+    prg.extend([0x4C, end as u8, (end >> 8) as u8]);
+
+    prg.resize(0x4000, 0xEA);
+    prg[0x3FFC..0x4000].copy_from_slice(&[0x00, 0xC0, 0x00, 0xC0]);
+
+    let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    rom.extend(prg);
+    rom.extend(vec![0; 0x2000]);
+    rom
+}
+
+#[test]
+fn harness_follows_the_protocol() {
+    let rom = protocol_rom(0);
+    let emulator = EmulatorInstance::from_bytes(&rom, PathBuf::from("protocol.nes")).unwrap();
+    assert_eq!(run_test_rom(emulator), Outcome::Passed);
+
+    let rom = protocol_rom(3);
+    let emulator = EmulatorInstance::from_bytes(&rom, PathBuf::from("protocol.nes")).unwrap();
+    assert_eq!(run_test_rom(emulator), Outcome::Failed { code: 3, message: "ok".into() });
+}
@@ -9,11 +9,11 @@ use nes_emulator::debug::cpu_debug::log_state_nestest;
 fn test_nestest_execution() {
     #[cfg(feature = "debug_log")]
     {
-        use nes_emulator::apu::audio::AudioOutput;
+        use nes_emulator::apu::audio::NullSink;
 
         let mut emulator = EmulatorInstance::new(std::path::PathBuf::from("tests/nestest/nestest.nes"))
             .unwrap_or_else(|_| panic!("couldn't start the emulator, nestest.nes might have been moved"));
-        let mut audio  = AudioOutput::new(44100);
+        let mut audio = NullSink;
         
         let mut logger = log_state_nestest(
             Some("tests/nestest_output.log"), 
@@ -22,6 +22,8 @@ fn test_nestest_execution() {
 
         emulator.cpu.reset_interrupt();
         emulator.cpu.program_counter = 0xC000;
+        // the log starts counting after the 7 cycles of the reset sequence
+        emulator.cpu.cycles = 7;
 
         for _ in 0..=10_000 {
             emulator.run_frame_with_debug_logger(&mut audio, &mut logger);
@@ -58,7 +60,9 @@ fn test_nestest_execution() {
 }
 
 
-///compares onyl the pc and registers, ignores the remainder
+///compares only the pc, registers and cycle count, ignores the remainder
+/// 
+///the whole log is compared, including the unofficial opcodes section that starts at $C6BD
 #[cfg(feature = "debug_log")]
 fn compare_files() {
     let output_file = std::fs::File::open("tests/nestest_output.log")
@@ -66,24 +70,29 @@ fn compare_files() {
     let expected_file = std::fs::File::open("tests/nestest/nestest.log")
         .expect("Couldn't open the official log");
 
-    let output_lines = std::io::BufReader::new(output_file).lines();
-    let expected_lines = std::io::BufReader::new(expected_file).lines();
-
-    for (i, (actual_res, expected_res)) in output_lines.zip(expected_lines).enumerate() {
-        let actual = actual_res.unwrap();
-        let expected = expected_res.unwrap();
+    let output_lines: Vec<String> = std::io::BufReader::new(output_file).lines().map(Result::unwrap).collect();
+    let expected_lines: Vec<String> = std::io::BufReader::new(expected_file).lines().map(Result::unwrap).collect();
 
+    for (i, (actual, expected)) in output_lines.iter().zip(expected_lines.iter()).enumerate() {
         let expected_pc = &expected[0..4];
         
         let expected_regs = &expected[48..73]; 
+
+        let expected_cycles = &expected[expected.find("CYC:").unwrap()..];
         
-        let expected_clean = format!("{} {}", expected_pc, expected_regs);
+        let expected_clean = format!("{} {} {}", expected_pc, expected_regs, expected_cycles);
 
-        if actual != expected_clean {
+        if *actual != expected_clean {
             panic!(
                 "\nFailed at line {}!\nExpected: {}\nGot:   {}\n", 
                 i + 1, expected_clean, actual
             );
         }
     }
-}
\ No newline at end of file
+
+    assert!(
+        output_lines.len() >= expected_lines.len(),
+        "nestest stopped after {} of {} instructions",
+        output_lines.len(), expected_lines.len()
+    );
+}
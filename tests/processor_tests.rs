@@ -0,0 +1,194 @@
+//! Runs the single-step CPU tests of the ProcessorTests suite (the `nes6502` set)
+//!
+//! Each `XX.json` file has thousands of vectors for the opcode `XX`: the registers and the RAM before
+//! and after one instruction, and the bus activity of every cycle. Each vector runs through one
+//! [`CPU::step`] on a flat 64 KB RAM bus, checking the registers, the RAM and every cycle
+//! (address, value, read or write), the dummy reads and writes included.
+//!
+//! The vectors aren't distributed with the emulator, point `PROCESSOR_TESTS_DIR` to the `nes6502/v1`
+//! folder (or put it in `tests/ProcessorTests/nes6502/v1`) and run the ignored tests:
+//! `cargo test --test processor_tests -- --ignored`, it fails if they aren't there.
+//!
+//! https://github.com/SingleStepTests/ProcessorTests
+
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use nes_emulator::cpu::{
+    cpu::{CPU, CpuFlags},
+    cpu_bus::{BusCycle, CpuBus, RamBus},
+};
+
+const DEFAULT_DIR: &str = "tests/ProcessorTests/nes6502/v1";
+
+/// JAM opcodes, they halt the CPU so there is no final state to compare
+const SKIPPED_OPCODES: [u8; 12] = [0x02, 0x12, 0x22, 0x32, 0x42, 0x52, 0x62, 0x72, 0x92, 0xB2, 0xD2, 0xF2];
+
+/// Bits 4 (B) and 5 don't exist in the register, they are only set on the copy pushed to the stack
+const STATUS_MASK: u8 = 0b1100_1111;
+
+#[derive(Deserialize, Clone)]
+struct TestVector {
+    name: String,
+    initial: CpuState,
+    #[serde(rename = "final")]
+    expected: CpuState,
+    cycles: Vec<(u16, u8, String)>,
+}
+
+#[derive(Deserialize, Clone)]
+struct CpuState {
+    pc: u16,
+    s: u8,
+    a: u8,
+    x: u8,
+    y: u8,
+    p: u8,
+    ram: Vec<(u16, u8)>,
+}
+
+fn load_state(cpu: &mut CPU<RamBus>, state: &CpuState) {
+    cpu.program_counter = state.pc;
+    cpu.stack_pointer = state.s;
+    cpu.register_a = state.a;
+    cpu.register_x = state.x;
+    cpu.register_y = state.y;
+    cpu.status = CpuFlags::from_bits_truncate(state.p);
+
+    let ram = cpu.bus.ram_mut();
+    for &(addr, val) in &state.ram {
+        ram[addr as usize] = val;
+    }
+}
+
+/// Differences between the CPU and the expected state, empty if it passed
+fn compare_state(cpu: &CPU<RamBus>, expected: &CpuState) -> Vec<String> {
+    let mut errors = Vec::new();
+    let mut check = |what: &str, found: u16, expected: u16| {
+        if found != expected {
+            errors.push(format!("{} is ${:02X}, expected ${:02X}", what, found, expected));
+        }
+    };
+
+    check("PC", cpu.program_counter, expected.pc);
+    check("S", cpu.stack_pointer as u16, expected.s as u16);
+    check("A", cpu.register_a as u16, expected.a as u16);
+    check("X", cpu.register_x as u16, expected.x as u16);
+    check("Y", cpu.register_y as u16, expected.y as u16);
+    check("P", (cpu.status.bits() & STATUS_MASK) as u16, (expected.p & STATUS_MASK) as u16);
+
+    for &(addr, val) in &expected.ram {
+        check(&format!("${:04X}", addr), cpu.bus.peek(addr) as u16, val as u16);
+    }
+    errors
+}
+
+fn describe(cycle: &BusCycle) -> String {
+    match cycle {
+        BusCycle::Read(addr, val) => format!("read ${:04X} = ${:02X}", addr, val),
+        BusCycle::Write(addr, val) => format!("write ${:04X} = ${:02X}", addr, val),
+        BusCycle::Internal => "internal".into(),
+    }
+}
+
+/// The first cycle that doesn't match the expected bus activity, if any
+fn compare_cycles(found: &[BusCycle], expected: &[(u16, u8, String)]) -> Option<String> {
+    if found.len() != expected.len() {
+        return Some(format!("took {} cycles, expected {}", found.len(), expected.len()));
+    }
+
+    for (i, (cycle, (addr, val, kind))) in found.iter().zip(expected).enumerate() {
+        let matches = match (cycle, kind.as_str()) {
+            (BusCycle::Read(a, v), "read") | (BusCycle::Write(a, v), "write") => a == addr && v == val,
+            _ => false,
+        };
+        if !matches {
+            return Some(format!("cycle {} was {}, expected {} ${:04X} = ${:02X}", i, describe(cycle), kind, addr, val));
+        }
+    }
+    None
+}
+
+/// Runs one vector and cleans the RAM it used, returns what went wrong
+fn run_vector(cpu: &mut CPU<RamBus>, test: &TestVector) -> Result<(), String> {
+    load_state(cpu, &test.initial);
+    cpu.bus.take_cycles();
+    let (halted, cycles) = cpu.step();
+    let bus_cycles = cpu.bus.take_cycles();
+
+    let mut errors = compare_state(cpu, &test.expected);
+    if halted {
+        errors.push("the CPU halted".into());
+    }
+    if cycles as usize != bus_cycles.len() {
+        errors.push(format!("reported {} cycles but the bus saw {}", cycles, bus_cycles.len()));
+    }
+    errors.extend(compare_cycles(&bus_cycles, &test.cycles));
+
+    let ram = cpu.bus.ram_mut();
+    for &(addr, _) in test.initial.ram.iter().chain(&test.expected.ram) {
+        ram[addr as usize] = 0;
+    }
+
+    if errors.is_empty() { Ok(()) } else { Err(errors.join(", ")) }
+}
+
+fn new_cpu() -> CPU<RamBus> {
+    let mut bus = RamBus::new();
+    bus.record_cycles = true;
+    CPU::with_bus(bus)
+}
+
+#[test]
+#[ignore = "needs the ProcessorTests vectors, see the module docs"]
+fn processor_tests_nes6502() {
+    let dir = std::env::var("PROCESSOR_TESTS_DIR").map(PathBuf::from).unwrap_or(PathBuf::from(DEFAULT_DIR));
+    assert!(dir.exists(), "no test vectors at {}", dir.display());
+
+    let mut cpu = new_cpu();
+    let mut failed_opcodes = Vec::new();
+
+    for opcode in 0..=0xFFu8 {
+        if SKIPPED_OPCODES.contains(&opcode) { continue }
+
+        let path = dir.join(format!("{:02x}.json", opcode));
+        let text = std::fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("can't read {}: {}", path.display(), e));
+        let tests: Vec<TestVector> = serde_json::from_str(&text)
+            .unwrap_or_else(|e| panic!("{} is invalid: {}", path.display(), e));
+
+        let mut failures = tests.iter().filter_map(|test| run_vector(&mut cpu, test).err().map(|e| (&test.name, e)));
+        match failures.next() {
+            None => println!("[PASS]    {:02X} ({} tests)", opcode, tests.len()),
+            Some((name, error)) => {
+                println!("[FAIL]    {:02X} {} more failed, first: \"{}\": {}", opcode, failures.count(), name, error);
+                failed_opcodes.push(format!("{:02X}", opcode));
+            }
+        }
+    }
+
+    assert!(failed_opcodes.is_empty(), "opcodes failing the single-step tests: {:?}", failed_opcodes);
+}
+
+#[test]
+fn harness_runs_a_vector() {
+    // LDA #$80
+    let test: TestVector = serde_json::from_str(r#"{
+        "name": "a9 80 00",
+        "initial": { "pc": 512, "s": 253, "a": 0, "x": 0, "y": 0, "p": 38, "ram": [[512, 169], [513, 128]] },
+        "final":   { "pc": 514, "s": 253, "a": 128, "x": 0, "y": 0, "p": 164, "ram": [[512, 169], [513, 128]] },
+        "cycles": [[512, 169, "read"], [513, 128, "read"]]
+    }"#).unwrap();
+
+    let mut cpu = new_cpu();
+    assert_eq!(run_vector(&mut cpu, &test), Ok(()));
+
+    let mut wrong = test.clone();
+    wrong.expected.a = 0x7F;
+    assert_eq!(run_vector(&mut cpu, &wrong), Err("A is $80, expected $7F".into()));
+
+    let mut wrong = test;
+    wrong.cycles[1] = (513, 128, "write".into());
+    assert_eq!(run_vector(&mut cpu, &wrong), Err("cycle 1 was read $0201 = $80, expected write $0201 = $80".into()));
+}